@@ -1,22 +1,46 @@
 mod attachments;
+mod background_shell;
+mod checkpoints;
+mod client_tools;
 mod developer_tools;
+mod legacy_sse;
 #[cfg(target_os = "macos")]
 mod macos_login_path;
+mod maple_mcp;
+mod mcp_context;
+mod mcp_diagnostics;
+mod mcp_elicitation;
+mod mcp_health;
+mod mcp_interchange;
+mod mcp_sampling;
+mod mcp_tool_permissions;
+mod permission_rules;
 pub(crate) mod provider;
+mod search_tools;
+mod session_archive;
+mod session_search;
 mod shell_permission;
 mod system_prompt;
 mod tool_context;
+mod transcript;
 mod transient_mcp;
+mod unified_diff;
 mod web_permission;
 mod web_tools;
+mod workspace_roots;
 
 use crate::maple_api::{account_scope, MapleApiSession};
-use attachments::{
-    AgentAttachmentStore, AgentImageAttachment, AgentImageUpload, PreparedAgentImage,
+pub(crate) use attachments::AgentImageUpload;
+use attachments::{AgentAttachmentStore, AgentImageAttachment, PreparedAgentImage};
+use checkpoints::{ActiveCheckpointRuns, AgentCheckpointStore};
+pub(crate) use checkpoints::{AgentFileChange, AgentRunChanges};
+pub(crate) use client_tools::{
+    AgentClientCommand, AgentClientCommandOutput, AgentClientToolCapabilities, AgentClientTools,
 };
 use developer_tools::MapleDeveloperClient;
 #[cfg(test)]
 use developer_tools::EXTERNAL_MCP_TOOL_NAME;
+pub(crate) use developer_tools::{file_diff_texts, FILE_DIFF_KEY};
 use futures_util::StreamExt;
 use goose::agents::extension::Envs;
 use goose::agents::mcp_client::McpClientTrait;
@@ -29,31 +53,63 @@ use goose::config::{
     DEFAULT_EXTENSION_TIMEOUT,
 };
 use goose::conversation::message::{
-    ActionRequiredData, ErrorContent, Message, MessageContent, MessageErrorKind,
+    ActionRequiredData, ErrorContent, Message, MessageContent, MessageErrorKind, MessageUsage,
     SystemNotificationContent, SystemNotificationType,
 };
 use goose::conversation::{fix_conversation, Conversation};
 use goose::execution::manager::{AgentManager, AgentManagerGetResult, RuntimeContext};
 use goose::permission::permission_confirmation::PrincipalType;
 use goose::permission::{Permission, PermissionConfirmation};
+use goose::providers::base::Provider;
 use goose::session::session_manager::{Session, SessionType};
 use goose::session::SessionManager;
 use goose::skills::{SkillsClient, EXTENSION_NAME as SKILLS_EXTENSION_NAME};
+use goose_providers::conversation::token_usage::ProviderUsage;
 use icu_properties::{props::DefaultIgnorableCodePoint, CodePointSetData};
+use legacy_sse::LegacySseMcpClient;
+use maple_mcp::MapleMcpClient;
+pub(crate) use mcp_context::{
+    escape_attribute, escape_delimited_body, AgentGetMcpPromptRequest, AgentMcpComposerText,
+    AgentMcpContextCatalog, AgentReadMcpResourceRequest,
+};
+pub(crate) use mcp_diagnostics::AgentMcpServerTest;
+use mcp_elicitation::{
+    elicitation_requests, validate_elicitation_content, McpElicitationHost, McpElicitationReply,
+    PendingElicitations,
+};
+pub(crate) use mcp_elicitation::{
+    AgentElicitationAction, AgentElicitationRequest, AgentElicitationResponse,
+};
+pub(crate) use mcp_health::{AgentMcpHealthStatus, AgentMcpServerHealth};
+use mcp_health::{McpHealthMonitor, McpHealthRegistry, McpHealthTiming};
+pub(crate) use mcp_interchange::{AgentMcpImportReport, AgentMcpImportRequest};
+pub(crate) use mcp_sampling::AgentMcpSampling;
+use mcp_sampling::{
+    MapleMcpClientHandler, McpSamplingApproval, McpSamplingApprovals, McpSamplingCompletion,
+    McpSamplingHost,
+};
+use mcp_tool_permissions::McpToolPermissions;
+pub(crate) use mcp_tool_permissions::{AgentMcpToolPermission, AgentMcpToolPermissionLevel};
+use permission_rules::ProjectPermissionRules;
+pub(crate) use permission_rules::{
+    AgentPermissionRule, AgentPermissionRuleDecision, AgentPermissionRuleTool,
+};
 use provider::{MapleProvider, MAPLE_PROVIDER_NAME};
 use rmcp::model::{
     CallToolResult, ContentBlock, InitializeResult, JsonObject, ListToolsResult, ServerNotification,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+pub(crate) use session_search::AgentSessionSearchHit;
+use session_search::SessionSearchIndex;
 use shell_permission::{
-    local_read_image_request_id, local_read_request_id, ShellPermissionClassifier,
-    ShellPermissionOutcome, ShellPermissionRequest,
+    local_read_image_request_id, local_read_request_id, local_search_request_id,
+    ShellPermissionClassifier, ShellPermissionOutcome, ShellPermissionRequest,
 };
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Weak};
@@ -68,6 +124,9 @@ use web_permission::{
     WebPermissionOutcome,
 };
 use web_tools::WebToolState;
+use workspace_roots::{
+    admitted_session_workspace_roots, normalize_workspace_roots, set_session_workspace_roots,
+};
 
 const DEFAULT_AGENT_MODEL: &str = "glm-5-2";
 const LEGACY_AGENT_DEFAULT_MODEL: &str = "auto:powerful";
@@ -76,11 +135,13 @@ const DEFAULT_GOOSE_MODE: &str = "smart_approve";
 // policy at every tool boundary, including when the user changes it mid-run.
 const GOOSE_PERMISSION_ROUTING_MODE: GooseMode = GooseMode::SmartApprove;
 #[cfg(test)]
-const MAPLE_DEVELOPER_TOOLS: [&str; 8] = [
+const MAPLE_DEVELOPER_TOOLS: [&str; 10] = [
     "read",
     "shell",
     "edit",
     "write",
+    "glob",
+    "grep",
     "read_image",
     "web_search",
     "open_url",
@@ -93,11 +154,15 @@ const MAPLE_SKILLS_CLIENT_KEY: &str = "maple-skills-extension";
 const MAPLE_GOOSE_PERMISSION_CONFIG: &str = r#"user:
   always_allow:
   - load_skill
+  - shell_output
+  - shell_kill
   ask_before:
   - read
   - shell
   - edit
   - write
+  - glob
+  - grep
   - read_image
   - web_search
   - open_url
@@ -117,10 +182,13 @@ const SESSION_TITLE_MAX_INPUT_CHARS: usize = 500;
 const SESSION_TITLE_SYSTEM_PROMPT: &str = "You are a helpful assistant that generates concise, meaningful titles (3-5 words) for chat conversations based on the user's first message. Return only the title without quotes or explanations.";
 const DEFAULT_AGENT_SESSION_TITLE: &str = "New task";
 const DEFAULT_MCP_TIMEOUT_SECONDS: u64 = 300;
+/// Leading placeholder in a stdio working directory for the task's project folder.
+const MCP_PROJECT_ROOT_PLACEHOLDER: &str = "${projectRoot}";
 const MAX_AGENT_SESSION_TITLE_CHARS: usize = 80;
 const MAX_AGENT_ERROR_CHARS: usize = 1_200;
 const MAX_MCP_CONNECTION_ERRORS: usize = 3;
 const MAX_MCP_SERVER_NAME_CHARS: usize = 64;
+const MAX_MCP_TOOL_FILTER_NAMES: usize = 256;
 const MAX_MCP_CONNECTION_ERROR_CHARS: usize = 200;
 const MCP_CONNECTION_ERROR_PREFIX: &str = "Some MCP servers could not connect:";
 const AGENT_RUN_EVENT_CAPACITY: usize = 256;
@@ -164,6 +232,8 @@ pub struct AgentConfig {
     pub default_model: String,
     #[serde(default)]
     pub mcp_servers: Vec<AgentMcpServer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mcp_tool_permissions: Vec<AgentMcpToolPermission>,
     #[serde(
         default,
         rename = "projectTrust",
@@ -172,6 +242,10 @@ pub struct AgentConfig {
     )]
     pub project_trust: Vec<AgentProjectTrust>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permission_rules: Vec<AgentPermissionRule>,
+    #[serde(default, skip_serializing_if = "AgentFilesystemBoundary::is_unset")]
+    pub filesystem_boundary: AgentFilesystemBoundary,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub removed_project_roots: Vec<String>,
 }
 
@@ -200,7 +274,10 @@ impl Default for AgentConfig {
             default_project_root: None,
             default_model: default_agent_model(),
             mcp_servers: Vec::new(),
+            mcp_tool_permissions: Vec::new(),
             project_trust: Vec::new(),
+            permission_rules: Vec::new(),
+            filesystem_boundary: AgentFilesystemBoundary::default(),
             removed_project_roots: Vec::new(),
         }
     }
@@ -228,6 +305,33 @@ pub struct AgentProjectTrustStatus {
     pub protected_features: Vec<AgentProjectTrustFeature>,
 }
 
+/// Optional limit on which folders the read, edit, and write tools may reach.
+/// When enabled, each task may use its own project root plus
+/// `extra_directories`; symlinks are resolved before the check.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFilesystemBoundary {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_directories: Vec<String>,
+}
+
+impl AgentFilesystemBoundary {
+    fn is_unset(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentAddPermissionRuleRequest {
+    pub project_root: String,
+    pub tool: AgentPermissionRuleTool,
+    pub pattern: String,
+    pub decision: AgentPermissionRuleDecision,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentMcpKeyValue {
@@ -236,12 +340,20 @@ pub struct AgentMcpKeyValue {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "snake_case")]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
 pub enum AgentMcpTransport {
     Stdio {
         command: String,
         #[serde(default)]
         environment: Vec<AgentMcpKeyValue>,
+        /// Where the server starts; Maple's own working directory when unset.
+        /// A leading `${projectRoot}` stands for the task's project folder.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        working_directory: Option<String>,
     },
     StreamableHttp {
         url: String,
@@ -250,6 +362,29 @@ pub enum AgentMcpTransport {
         #[serde(default)]
         headers: Vec<AgentMcpKeyValue>,
     },
+    /// The legacy HTTP+SSE transport. Maple connects these servers itself.
+    Sse {
+        url: String,
+        #[serde(default)]
+        environment: Vec<AgentMcpKeyValue>,
+        #[serde(default)]
+        headers: Vec<AgentMcpKeyValue>,
+    },
+    /// Streamable HTTP served on a Unix domain socket. Every request goes to
+    /// the socket; `url` only supplies the request path and `Host` header.
+    UnixSocket {
+        socket_path: String,
+        #[serde(default = "default_unix_socket_mcp_url")]
+        url: String,
+        #[serde(default)]
+        environment: Vec<AgentMcpKeyValue>,
+        #[serde(default)]
+        headers: Vec<AgentMcpKeyValue>,
+    },
+}
+
+fn default_unix_socket_mcp_url() -> String {
+    "http://localhost/mcp".to_string()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -263,6 +398,15 @@ pub struct AgentMcpServer {
     #[serde(default = "default_mcp_timeout_seconds")]
     pub timeout_seconds: u64,
     pub transport: AgentMcpTransport,
+    /// When non-empty, only these tools are offered to the model.
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    /// Tools that are never offered to the model, even when allowed.
+    #[serde(default)]
+    pub blocked_tools: Vec<String>,
+    /// Lets the server request completions from Maple's models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<AgentMcpSampling>,
 }
 
 /// An MCP server supplied by an external Agent surface for one leased session.
@@ -277,6 +421,8 @@ pub(crate) struct AgentTransientMcpServer {
     pub(crate) description: String,
     pub(crate) timeout_seconds: u64,
     pub(crate) transport: AgentTransientMcpTransport,
+    pub(crate) allowed_tools: Vec<String>,
+    pub(crate) blocked_tools: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -306,6 +452,7 @@ pub struct AgentSessionMcpServer {
     pub transport: String,
     pub enabled: bool,
     pub available: bool,
+    pub health: AgentMcpServerHealth,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -360,6 +507,9 @@ pub struct AgentCreateSessionRequest {
     pub context_limit: Option<usize>,
     pub mode: Option<String>,
     pub mcp_server_names: Option<Vec<String>>,
+    /// Secondary workspace roots the task may work in beside `project_root`.
+    #[serde(default)]
+    pub workspace_roots: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -388,6 +538,65 @@ pub struct AgentRenameSessionRequest {
     pub title: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentForkSessionRequest {
+    pub session_id: String,
+    pub timeline_item_id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentExportSessionRequest {
+    pub session_id: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentImportSessionRequest {
+    pub path: String,
+    #[serde(default)]
+    pub project_root: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSearchSessionsRequest {
+    pub query: String,
+    #[serde(default)]
+    pub project_root: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentTranscriptFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentTranscriptRequest {
+    pub session_id: String,
+    pub format: AgentTranscriptFormat,
+    #[serde(default)]
+    pub include_thinking: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentTranscript {
+    pub session_id: String,
+    pub title: String,
+    pub format: AgentTranscriptFormat,
+    pub content: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentPermissionResponse {
@@ -520,6 +729,7 @@ type AgentRunSetup = (
     SharedAgentToolContext,
     bool,
     AgentRunUsage,
+    Session,
 );
 
 impl AgentRunUsage {
@@ -580,6 +790,25 @@ impl AgentRunPermissionResponder {
             )
             .await
     }
+
+    /// Answer an MCP elicitation raised by this run. An accepted reply must
+    /// match the server's requested schema.
+    pub(crate) async fn respond_to_elicitation(
+        &self,
+        request_id: String,
+        action: AgentElicitationAction,
+        content: Option<Value>,
+    ) -> Result<(), String> {
+        self.agent
+            .elicitation_respond_for_run(
+                self.session_id.as_ref(),
+                self.run_id.as_ref(),
+                request_id,
+                action,
+                content,
+            )
+            .await
+    }
 }
 
 /// Opaque cancellation capability for one run owned by a calling surface.
@@ -610,6 +839,8 @@ impl AgentRunCancellation {
 pub(crate) struct CreatedAgentSession {
     pub(crate) detail: AgentSessionDetail,
     pub(crate) tool_context_lease: Option<AgentToolContextLease>,
+    /// The secondary workspace roots the attached developer tools admitted.
+    pub(crate) workspace_roots: Vec<PathBuf>,
 }
 
 /// Controls whether a surface's events are also projected into Maple Desktop.
@@ -881,6 +1112,10 @@ pub(crate) enum AgentRunEvent {
         request: AgentPermissionRequest,
         item: AgentTimelineItem,
     },
+    ElicitationRequested {
+        request: AgentElicitationRequest,
+        item: AgentTimelineItem,
+    },
     SetupWarning(String),
     HistoryReplaced,
     Error(AgentTimelineItem),
@@ -914,7 +1149,7 @@ pub(crate) enum AgentServiceEvent {
     },
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentSessionSummary {
     pub id: String,
@@ -936,7 +1171,7 @@ pub struct AgentSessionDetail {
     pub queue: AgentDesktopQueueSnapshot,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentTimelineItem {
     pub id: String,
@@ -967,9 +1202,66 @@ struct ActiveAgentRun {
     cancelled_permission_ids: CancelledPermissionIds,
     accepting_queue: Arc<AtomicBool>,
     steered_unacked: Arc<Mutex<Vec<Message>>>,
+    deferred_usage: DeferredRunUsage,
     task_handle: tokio::task::JoinHandle<()>,
 }
 
+/// Usage from helper completions made during a run, such as MCP sampling.
+/// Goose rewrites the task's usage while the run streams, so these are
+/// recorded once the run has settled instead of beside Goose's own writes.
+#[derive(Clone)]
+struct DeferredRunUsage(Arc<Mutex<Option<Vec<ProviderUsage>>>>);
+
+impl DeferredRunUsage {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(Some(Vec::new()))))
+    }
+
+    /// Queue `usage` for the run, or hand it back once the run has settled.
+    async fn defer(&self, usage: ProviderUsage) -> Option<ProviderUsage> {
+        match self.0.lock().await.as_mut() {
+            Some(pending) => {
+                pending.push(usage);
+                None
+            }
+            None => Some(usage),
+        }
+    }
+
+    /// Take everything queued; later usage is handed back to its caller.
+    async fn settle(&self) -> Vec<ProviderUsage> {
+        self.0.lock().await.take().unwrap_or_default()
+    }
+}
+
+/// Add a helper completion's usage to the task without changing the primary
+/// model's context counters.
+async fn record_helper_usage(
+    session_manager: &SessionManager,
+    session_id: &str,
+    usage: &ProviderUsage,
+) {
+    let session = match session_manager.get_session(session_id, false).await {
+        Ok(session) => session,
+        Err(error) => {
+            log::warn!("Could not load Agent session to record helper usage: {error}");
+            return;
+        }
+    };
+    if let Err(error) = session_manager
+        .record_usage_metrics(
+            session_id,
+            session.schedule_id,
+            session.usage,
+            &usage.model,
+            &MessageUsage::from_provider_usage(usage, false),
+        )
+        .await
+    {
+        log::warn!("Could not record helper usage: {error}");
+    }
+}
+
 struct DesktopSessionQueue {
     revision: u64,
     items: VecDeque<AgentQueuedMessage>,
@@ -1426,7 +1718,14 @@ pub struct MapleAgentService {
     session_lifecycle: Arc<Mutex<()>>,
     session_title_lifecycles: SessionTitleLifecycles,
     pending_permissions: PendingPermissions,
+    pending_elicitations: PendingElicitations,
     live_timelines: LiveTimelines,
+    session_search: SessionSearchIndex,
+    permission_rules: ProjectPermissionRules,
+    mcp_tool_permissions: McpToolPermissions,
+    mcp_health: McpHealthRegistry,
+    mcp_sampling_approvals: McpSamplingApprovals,
+    checkpoint_runs: ActiveCheckpointRuns,
     desktop_queues: Arc<Mutex<HashMap<(String, String), DesktopSessionQueue>>>,
     admission: Arc<AtomicU8>,
 }
@@ -1511,7 +1810,14 @@ impl MapleAgentService {
             session_lifecycle: Arc::new(Mutex::new(())),
             session_title_lifecycles: Arc::new(Mutex::new(HashMap::new())),
             pending_permissions: Arc::new(Mutex::new(HashMap::new())),
+            pending_elicitations: PendingElicitations::default(),
             live_timelines: Arc::new(Mutex::new(HashMap::new())),
+            session_search: SessionSearchIndex::default(),
+            permission_rules: ProjectPermissionRules::default(),
+            mcp_tool_permissions: McpToolPermissions::default(),
+            mcp_health: McpHealthRegistry::default(),
+            mcp_sampling_approvals: McpSamplingApprovals::default(),
+            checkpoint_runs: ActiveCheckpointRuns::default(),
             desktop_queues: Arc::new(Mutex::new(HashMap::new())),
             admission: Arc::new(AtomicU8::new(AGENT_SERVICE_OPEN)),
         }
@@ -2216,6 +2522,8 @@ async fn stop_runtime_inner(
     let cancelled_permissions =
         cancel_pending_permissions_for_runs(&state.pending_permissions, &run_ids, &agents_by_run)
             .await;
+    cancel_pending_elicitations_for_runs(&state.pending_elicitations, &run_ids, &agents_by_run)
+        .await;
     for ((_, request_id), pending) in cancelled_permissions {
         if let Some(cancelled_permission_ids) = cancelled_permission_ids_by_run.get(&pending.run_id)
         {
@@ -2227,7 +2535,14 @@ async fn stop_runtime_inner(
     join_agent_tasks(task_handles, RUN_SHUTDOWN_TIMEOUT).await;
 
     state.pending_permissions.lock().await.clear();
+    state.pending_elicitations.clear().await;
     state.live_timelines.lock().await.clear();
+    state.session_search.clear().await;
+    state.permission_rules.clear().await;
+    state.mcp_tool_permissions.clear().await;
+    state.mcp_health.clear().await;
+    state.mcp_sampling_approvals.clear().await;
+    state.checkpoint_runs.clear();
     web_tool_state.clear_all().await;
     *state.inner.lock().await = None;
     Ok(())
@@ -2362,7 +2677,7 @@ async fn start_runtime_for_user(
     let permission_manager = Arc::new(PermissionManager::new(goose_path_root.join("config")));
     let goose_config = GooseAgentConfig::new(
         Arc::clone(&session_manager),
-        permission_manager,
+        Arc::clone(&permission_manager),
         None,
         GOOSE_PERMISSION_ROUTING_MODE,
         // Maple schedules its title-specific provider request as a tracked,
@@ -2384,6 +2699,15 @@ async fn start_runtime_for_user(
         .set_default_provider(Arc::new(MapleProvider::new(Arc::clone(&maple_api_session))))
         .await;
 
+    state.session_search.reset_for_account(&account_scope).await;
+    state
+        .permission_rules
+        .replace(agent_config.permission_rules.clone())
+        .await;
+    state
+        .mcp_tool_permissions
+        .install(permission_manager, &agent_config.mcp_tool_permissions)
+        .await;
     let runtime = AgentRuntime {
         agent_manager,
         session_manager,
@@ -2544,16 +2868,145 @@ impl AgentRuntimeHandle {
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
         self.verify_generation().await?;
         self.ensure_accepting_new_work()?;
+        self.save_mcp_servers_locked(servers).await
+    }
+
+    /// Plan an import of an `mcpServers` document and, unless it is a dry
+    /// run, save the result when it adds or replaces at least one server.
+    pub(crate) async fn import_mcp_servers(
+        &self,
+        request: AgentMcpImportRequest,
+    ) -> Result<AgentMcpImportReport, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        let existing = normalize_mcp_servers(config.mcp_servers)?;
+        let mut report =
+            mcp_interchange::plan_mcp_import(&request.json, &existing, request.replace_existing)?;
+        if !request.dry_run && report.changes_servers() {
+            self.ensure_accepting_new_work()?;
+            report.servers = self.save_mcp_servers_locked(report.servers).await?;
+            report.saved = true;
+        }
+        Ok(report)
+    }
+
+    pub(crate) async fn export_mcp_servers(&self) -> Result<String, String> {
+        let servers = self.list_mcp_servers().await?;
+        mcp_interchange::export_mcp_servers(&servers)
+    }
+
+    /// Save the account's MCP servers. The caller holds the runtime lifecycle
+    /// lock and has admitted the change.
+    async fn save_mcp_servers_locked(
+        &self,
+        servers: Vec<AgentMcpServer>,
+    ) -> Result<Vec<AgentMcpServer>, String> {
+        let state = &self.service;
         let servers = normalize_mcp_servers(servers)?;
         let mut config =
             load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
         config.mcp_servers = servers.clone();
+        // Choices for a removed server must not silently apply to a later
+        // server that reuses its name.
+        let server_keys = servers
+            .iter()
+            .map(|server| goose::config::extensions::name_to_key(&server.name))
+            .collect::<HashSet<_>>();
+        config.mcp_tool_permissions.retain(|permission| {
+            server_keys.contains(&goose::config::extensions::name_to_key(&permission.server))
+        });
         save_agent_config_inner(&state.host.paths, &self.user_id, &config)
             .map_err(|e| e.to_string())?;
+        self.refresh_running_mcp_tool_permissions(&config).await;
 
         Ok(servers)
     }
 
+    pub(crate) async fn list_mcp_tool_permissions(
+        &self,
+    ) -> Result<Vec<AgentMcpToolPermission>, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        Ok(config.mcp_tool_permissions)
+    }
+
+    /// Replace the user's per-tool choices for configured MCP servers. Tools
+    /// without a choice keep the default derived from their annotations.
+    pub(crate) async fn save_mcp_tool_permissions(
+        &self,
+        permissions: Vec<AgentMcpToolPermission>,
+    ) -> Result<Vec<AgentMcpToolPermission>, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        let mut config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        let server_names = config
+            .mcp_servers
+            .iter()
+            .map(|server| server.name.clone())
+            .collect::<Vec<_>>();
+        config.mcp_tool_permissions =
+            mcp_tool_permissions::normalize_mcp_tool_permissions(permissions, &server_names)?;
+        save_agent_config_inner(&state.host.paths, &self.user_id, &config)
+            .map_err(|e| e.to_string())?;
+        self.refresh_running_mcp_tool_permissions(&config).await;
+        Ok(config.mcp_tool_permissions)
+    }
+
+    /// Publish saved MCP tool choices to this account's running runtime. They
+    /// apply to the next tool call, including in-flight runs.
+    async fn refresh_running_mcp_tool_permissions(&self, config: &AgentConfig) {
+        let state = &self.service;
+        let runtime = state.inner.lock().await;
+        if runtime
+            .as_ref()
+            .is_some_and(|current| ensure_runtime_account(current, &self.account_scope).is_ok())
+        {
+            state
+                .mcp_tool_permissions
+                .replace(&config.mcp_tool_permissions)
+                .await;
+        }
+    }
+
+    /// Connect to `server` outside any session and report what it offers.
+    /// The server does not need to be saved, and nothing is persisted.
+    pub(crate) async fn test_mcp_server(
+        &self,
+        server: AgentMcpServer,
+    ) -> Result<AgentMcpServerTest, String> {
+        let server = {
+            let state = &self.service;
+            let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+            self.verify_generation().await?;
+            self.ensure_accepting_new_work()?;
+            let server = normalize_mcp_servers(vec![server])?.remove(0);
+            // A server that starts in the project folder is checked in the
+            // runtime's current project, or the default one when stopped.
+            let project_root = match state.inner.lock().await.as_ref() {
+                Some(current) if ensure_runtime_account(current, &self.account_scope).is_ok() => {
+                    Some(current.project_root.clone())
+                }
+                _ => load_agent_config_inner(&state.host.paths, &self.user_id)
+                    .map_err(|error| error.to_string())?
+                    .default_project_root
+                    .and_then(|root| normalize_project_root(Path::new(&root)).ok()),
+            };
+            resolve_mcp_working_directory(&server, project_root.as_deref())?
+        };
+        // The check can take up to the server's timeout per request; keep it
+        // outside the lifecycle lock so other Agent operations are not held up.
+        Ok(mcp_diagnostics::test_mcp_server(&server).await)
+    }
+
     pub(crate) async fn list_recent_project_roots(&self) -> Result<Vec<RecentProjectRoot>, String> {
         let state = &self.service;
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
@@ -2856,56 +3309,277 @@ impl AgentRuntimeHandle {
         Ok(project_trust_status(&config, &project_root, true))
     }
 
-    pub(crate) async fn save_project_root_order(
+    pub(crate) async fn get_filesystem_boundary(&self) -> Result<AgentFilesystemBoundary, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        Ok(config.filesystem_boundary)
+    }
+
+    /// Save the filesystem boundary. Extra directories are canonicalized here
+    /// so the stored list names real folders; each task picks the boundary up
+    /// when its next message starts.
+    pub(crate) async fn set_filesystem_boundary(
         &self,
-        paths: Vec<String>,
-    ) -> Result<Vec<RecentProjectRoot>, String> {
+        boundary: AgentFilesystemBoundary,
+    ) -> Result<AgentFilesystemBoundary, String> {
         let state = &self.service;
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
         self.verify_generation().await?;
         self.ensure_accepting_new_work()?;
-        save_project_root_order_inner(&state.host.paths, &self.user_id, paths)
-            .map_err(|e| e.to_string())
+        let boundary = normalize_filesystem_boundary(boundary)?;
+        let mut config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        config.filesystem_boundary = boundary.clone();
+        save_agent_config_inner(&state.host.paths, &self.user_id, &config)
+            .map_err(|e| e.to_string())?;
+        Ok(boundary)
     }
-}
 
-impl AgentRuntimeHandle {
-    pub(crate) async fn create_session(
+    /// File changes made by one run of a task, or by its latest run when
+    /// `run_id` is omitted, with a unified diff for each.
+    pub(crate) async fn list_run_changes(
         &self,
-        request: Option<AgentCreateSessionRequest>,
-    ) -> Result<AgentSessionDetail, String> {
-        Ok(self
-            .create_session_with_tool_context(request, None, AgentHostEventPolicy::Publish)
-            .await?
-            .detail)
+        session_id: String,
+        run_id: Option<String>,
+    ) -> Result<AgentRunChanges, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let session_id = session_id.trim();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let run_id = run_id
+            .as_deref()
+            .map(str::trim)
+            .filter(|run_id| !run_id.is_empty());
+        let session_id = session_id.to_string();
+        let run_id = run_id.map(str::to_string);
+        let store = account_checkpoint_store(&state.host.paths, &self.user_id)?;
+        tokio::task::spawn_blocking(move || store.run_changes(&session_id, run_id.as_deref()))
+            .await
+            .map_err(|error| format!("Agent checkpoint task failed: {error}"))?
     }
 
-    pub(crate) async fn create_session_with_tool_context(
+    /// Restore every file a run changed. Nothing is written unless each file
+    /// still holds exactly what the run left in it.
+    pub(crate) async fn revert_run_changes(
         &self,
-        request: Option<AgentCreateSessionRequest>,
-        tool_context: Option<AgentToolContextSpec>,
-        host_events: AgentHostEventPolicy,
-    ) -> Result<CreatedAgentSession, String> {
-        self.create_session_with_surface_context(
-            request,
-            tool_context,
-            Vec::new(),
-            CancellationToken::new(),
-            host_events,
-        )
-        .await
+        session_id: String,
+        run_id: String,
+    ) -> Result<Vec<AgentFileChange>, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        let session_id = session_id.trim();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        self.ensure_no_active_run_for_revert(session_id).await?;
+        let session_id = session_id.to_string();
+        let run_id = run_id.trim().to_string();
+        let store = account_checkpoint_store(&state.host.paths, &self.user_id)?;
+        tokio::task::spawn_blocking(move || store.revert_run(&session_id, &run_id))
+            .await
+            .map_err(|error| format!("Agent checkpoint task failed: {error}"))?
     }
 
-    pub(crate) async fn create_session_with_surface_context(
+    pub(crate) async fn revert_file_change(
         &self,
-        request: Option<AgentCreateSessionRequest>,
-        tool_context: Option<AgentToolContextSpec>,
-        transient_mcp_servers: Vec<AgentTransientMcpServer>,
-        setup_cancel: CancellationToken,
-        host_events: AgentHostEventPolicy,
-    ) -> Result<CreatedAgentSession, String> {
+        session_id: String,
+        change_id: String,
+    ) -> Result<AgentFileChange, String> {
         let state = &self.service;
-        let user_id = self.user_id.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        let session_id = session_id.trim();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        self.ensure_no_active_run_for_revert(session_id).await?;
+        let session_id = session_id.to_string();
+        let change_id = change_id.trim().to_string();
+        let store = account_checkpoint_store(&state.host.paths, &self.user_id)?;
+        tokio::task::spawn_blocking(move || store.revert_change(&session_id, &change_id))
+            .await
+            .map_err(|error| format!("Agent checkpoint task failed: {error}"))?
+    }
+
+    async fn ensure_no_active_run_for_revert(&self, session_id: &str) -> Result<(), String> {
+        let runtime = self.service.inner.lock().await;
+        if let Some(current) = runtime.as_ref() {
+            ensure_runtime_account(current, &self.account_scope)?;
+            if has_active_session_run(&current.active_runs, session_id) {
+                return Err("Stop the running agent before reverting its changes".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn list_permission_rules(
+        &self,
+        project_root: Option<String>,
+    ) -> Result<Vec<AgentPermissionRule>, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let project_root = match project_root.as_deref().map(str::trim) {
+            Some(path) if !path.is_empty() => {
+                Some(path_string(&normalize_project_root(Path::new(path))?))
+            }
+            _ => None,
+        };
+        let config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        Ok(config
+            .permission_rules
+            .into_iter()
+            .filter(|rule| {
+                project_root
+                    .as_ref()
+                    .is_none_or(|root| &rule.project_root == root)
+            })
+            .collect())
+    }
+
+    /// Save a durable answer for matching permission requests in one project.
+    /// Re-adding an existing tool/pattern pair replaces its decision instead of
+    /// stacking a contradictory duplicate.
+    pub(crate) async fn add_permission_rule(
+        &self,
+        request: AgentAddPermissionRuleRequest,
+    ) -> Result<AgentPermissionRule, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        let project_root = path_string(&normalize_project_root(Path::new(
+            request.project_root.trim(),
+        ))?);
+        let pattern = permission_rules::normalize_rule_pattern(request.tool, &request.pattern)?;
+        let mut config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        let rule = if let Some(existing) = config.permission_rules.iter_mut().find(|rule| {
+            rule.project_root == project_root
+                && rule.tool == request.tool
+                && rule.pattern == pattern
+        }) {
+            existing.decision = request.decision;
+            existing.clone()
+        } else {
+            if config.permission_rules.len() >= permission_rules::MAX_PERMISSION_RULES {
+                return Err(format!(
+                    "Maple can keep at most {} permission rules. Remove an old rule first.",
+                    permission_rules::MAX_PERMISSION_RULES
+                ));
+            }
+            let rule = AgentPermissionRule {
+                id: format!("rule_{:032x}", rand::random::<u128>()),
+                project_root,
+                tool: request.tool,
+                pattern,
+                decision: request.decision,
+                created_ms: unix_ms(),
+            };
+            config.permission_rules.push(rule.clone());
+            rule
+        };
+        save_agent_config_inner(&state.host.paths, &self.user_id, &config)
+            .map_err(|e| e.to_string())?;
+        self.refresh_running_permission_rules(&config).await;
+        Ok(rule)
+    }
+
+    pub(crate) async fn remove_permission_rule(&self, rule_id: String) -> Result<(), String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let mut config =
+            load_agent_config_inner(&state.host.paths, &self.user_id).map_err(|e| e.to_string())?;
+        let before = config.permission_rules.len();
+        config.permission_rules.retain(|rule| rule.id != rule_id);
+        if config.permission_rules.len() == before {
+            return Err("Permission rule not found".to_string());
+        }
+        save_agent_config_inner(&state.host.paths, &self.user_id, &config)
+            .map_err(|e| e.to_string())?;
+        self.refresh_running_permission_rules(&config).await;
+        Ok(())
+    }
+
+    /// Publish saved rules to this account's running runtime. Revocation takes
+    /// effect for the next permission request, including in-flight runs.
+    async fn refresh_running_permission_rules(&self, config: &AgentConfig) {
+        let state = &self.service;
+        let runtime = state.inner.lock().await;
+        if runtime
+            .as_ref()
+            .is_some_and(|current| ensure_runtime_account(current, &self.account_scope).is_ok())
+        {
+            state
+                .permission_rules
+                .replace(config.permission_rules.clone())
+                .await;
+        }
+    }
+
+    pub(crate) async fn save_project_root_order(
+        &self,
+        paths: Vec<String>,
+    ) -> Result<Vec<RecentProjectRoot>, String> {
+        let state = &self.service;
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        save_project_root_order_inner(&state.host.paths, &self.user_id, paths)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl AgentRuntimeHandle {
+    pub(crate) async fn create_session(
+        &self,
+        request: Option<AgentCreateSessionRequest>,
+    ) -> Result<AgentSessionDetail, String> {
+        Ok(self
+            .create_session_with_tool_context(request, None, AgentHostEventPolicy::Publish)
+            .await?
+            .detail)
+    }
+
+    pub(crate) async fn create_session_with_tool_context(
+        &self,
+        request: Option<AgentCreateSessionRequest>,
+        tool_context: Option<AgentToolContextSpec>,
+        host_events: AgentHostEventPolicy,
+    ) -> Result<CreatedAgentSession, String> {
+        self.create_session_with_surface_context(
+            request,
+            tool_context,
+            Vec::new(),
+            CancellationToken::new(),
+            host_events,
+        )
+        .await
+    }
+
+    pub(crate) async fn create_session_with_surface_context(
+        &self,
+        request: Option<AgentCreateSessionRequest>,
+        tool_context: Option<AgentToolContextSpec>,
+        transient_mcp_servers: Vec<AgentTransientMcpServer>,
+        setup_cancel: CancellationToken,
+        host_events: AgentHostEventPolicy,
+    ) -> Result<CreatedAgentSession, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
         let account_scope = self.account_scope.as_ref();
         let has_external_tool_context = tool_context.is_some();
         if !has_external_tool_context && !transient_mcp_servers.is_empty() {
@@ -2927,6 +3601,7 @@ impl AgentRuntimeHandle {
             context_limit: None,
             mode: None,
             mcp_server_names: None,
+            workspace_roots: None,
         });
         let (
             agent_manager,
@@ -2962,6 +3637,11 @@ impl AgentRuntimeHandle {
             _ => runtime_project_root,
         };
         ensure_session_project_root_is_visible(&root, &config.removed_project_roots)?;
+        let workspace_roots = normalize_workspace_roots(
+            &root,
+            request.workspace_roots.as_deref().unwrap_or_default(),
+            &config.removed_project_roots,
+        )?;
         let title = request
             .title
             .filter(|value| !value.trim().is_empty())
@@ -2978,10 +3658,10 @@ impl AgentRuntimeHandle {
         let configured_mcp = normalize_mcp_servers(config.mcp_servers)?;
         let selected_mcp =
             select_mcp_servers(&configured_mcp, request.mcp_server_names.as_deref())?;
-        let selected_extensions = selected_mcp
-            .iter()
-            .map(mcp_server_to_extension)
-            .collect::<Result<Vec<_>, _>>()?;
+        let mut selected_extensions = Vec::with_capacity(selected_mcp.len());
+        for server in &selected_mcp {
+            selected_extensions.push(mcp_server_to_session_extension(server, &root)?);
+        }
         let selected_extension_keys = mcp_extension_keys(&selected_extensions);
         ensure_extension_sets_do_not_conflict(&selected_extensions, &transient_mcp_servers)?;
         let session_type = if has_external_tool_context {
@@ -3087,6 +3767,14 @@ impl AgentRuntimeHandle {
                             );
                         }
                     }
+                    if !workspace_roots.is_empty() {
+                        persist_session_workspace_roots(
+                            &session_manager,
+                            &session.id,
+                            &workspace_roots,
+                        )
+                        .await?;
+                    }
                     let session = session_manager
                         .get_session(&session.id, true)
                         .await
@@ -3110,6 +3798,9 @@ impl AgentRuntimeHandle {
                             mode: &mode,
                             primary_model_supports_vision: false,
                             tool_context: &tool_context,
+                            checkpoint_runs: &state.checkpoint_runs,
+                            mcp_tool_permissions: &state.mcp_tool_permissions,
+                            mcp_sampling: McpSamplingScope::new(state, account_scope, &session.id),
                         },
                     )
                     .await?;
@@ -3123,9 +3814,30 @@ impl AgentRuntimeHandle {
                             &session,
                         )?;
                         detach_transient_skills_client(&agent).await;
+                        let maple_keys = selected_mcp
+                            .iter()
+                            .filter(|server| maple_connects_mcp_server(server))
+                            .map(|server| goose::config::extensions::name_to_key(&server.name))
+                            .collect::<HashSet<_>>();
+                        let goose_extensions = selected_extensions
+                            .into_iter()
+                            .filter(|config| !maple_keys.contains(&config.key()))
+                            .collect::<Vec<_>>();
                         let extension_result = agent
-                            .add_extensions_bulk(selected_extensions, &session.id)
+                            .add_extensions_bulk(goose_extensions, &session.id)
                             .await;
+                        let maple_errors = attach_maple_mcp_servers(
+                            &agent,
+                            &selected_mcp,
+                            &root,
+                            &McpSamplingScope::new(state, account_scope, &session.id),
+                        )
+                        .await;
+                        let maple_persist_result = if !maple_keys.is_empty() {
+                            agent.persist_extension_state(&session.id).await
+                        } else {
+                            Ok(())
+                        };
                         attach_prepared_skills_client(&agent, skills_client).await;
                         match extension_result {
                             Ok(results) => mcp_errors
@@ -3135,6 +3847,17 @@ impl AgentRuntimeHandle {
                                 error: error.to_string(),
                             }),
                         }
+                        mcp_errors.extend(maple_errors);
+                        if let Err(error) = maple_persist_result {
+                            mcp_errors.push(AgentMcpConnectionError {
+                                name: "MCP servers".to_string(),
+                                error: error.to_string(),
+                            });
+                        }
+                        state
+                            .mcp_tool_permissions
+                            .apply_to_session(&agent, &session.id, &selected_extension_keys)
+                            .await;
                     }
                     {
                         let mut runtime = state.inner.lock().await;
@@ -3234,6 +3957,7 @@ impl AgentRuntimeHandle {
         Ok(CreatedAgentSession {
             detail,
             tool_context_lease,
+            workspace_roots,
         })
     }
 
@@ -3242,11 +3966,13 @@ impl AgentRuntimeHandle {
     /// This is intentionally distinct from the Desktop-facing `load_session`:
     /// it atomically acquires the task's external tool/MCP lease, rejects live
     /// or already-leased tasks, and returns persisted history without Desktop
-    /// overlays or actionable permission routing.
+    /// overlays or actionable permission routing. `workspace_roots` replaces
+    /// the task's secondary roots; `None` keeps the persisted set.
     pub(crate) async fn attach_session_with_surface_context(
         &self,
         session_id: String,
         project_root: String,
+        workspace_roots: Option<Vec<String>>,
         tool_context: AgentToolContextSpec,
         transient_mcp_servers: Vec<AgentTransientMcpServer>,
         setup_cancel: CancellationToken,
@@ -3269,6 +3995,9 @@ impl AgentRuntimeHandle {
         let config = load_agent_config_inner(&state.host.paths, user_id)
             .map_err(|error| error.to_string())?;
         ensure_session_project_root_is_visible(&root, &config.removed_project_roots)?;
+        let workspace_roots = workspace_roots
+            .map(|roots| normalize_workspace_roots(&root, &roots, &config.removed_project_roots))
+            .transpose()?;
         let session_lifecycle_guard = state.session_lifecycle.lock().await;
         let (
             agent_manager,
@@ -3360,6 +4089,7 @@ impl AgentRuntimeHandle {
         // exact provisional lease remains authoritative for Desktop callers.
         drop(session_lifecycle_guard);
         drop(runtime_lifecycle_guard);
+        let mut admitted_roots = Vec::new();
         let setup_result: Result<AgentSessionDetail, String> = run_external_surface_setup(
             &setup_cancel,
             &tool_context,
@@ -3401,6 +4131,10 @@ impl AgentRuntimeHandle {
                         );
                     }
                 }
+                if let Some(workspace_roots) = workspace_roots.as_deref() {
+                    persist_session_workspace_roots(&session_manager, &session_id, workspace_roots)
+                        .await?;
+                }
                 let session = session_manager
                     .get_session(&session_id, true)
                     .await
@@ -3411,6 +4145,11 @@ impl AgentRuntimeHandle {
                     );
                 }
                 ensure_external_surface_loadable_session(&session)?;
+                admitted_roots = admitted_session_workspace_roots(
+                    &session.extension_data,
+                    &root,
+                    &config.removed_project_roots,
+                );
                 if let Some(conflict) = session_mcp_extension_keys(&session)
                     .into_iter()
                     .find(|key| transient_keys.contains(key))
@@ -3444,6 +4183,9 @@ impl AgentRuntimeHandle {
                         mode: &mode,
                         primary_model_supports_vision: false,
                         tool_context: &tool_context,
+                        checkpoint_runs: &state.checkpoint_runs,
+                        mcp_tool_permissions: &state.mcp_tool_permissions,
+                        mcp_sampling: McpSamplingScope::new(state, account_scope, &session.id),
                     },
                 )
                 .await?;
@@ -3521,6 +4263,7 @@ impl AgentRuntimeHandle {
         Ok(CreatedAgentSession {
             detail,
             tool_context_lease: Some(lease),
+            workspace_roots: admitted_roots,
         })
     }
 
@@ -3569,6 +4312,90 @@ impl AgentRuntimeHandle {
         Ok(sessions)
     }
 
+    /// Search task titles, user and assistant text, tool titles, and the
+    /// file paths tools touched across every task in this account.
+    pub(crate) async fn search_sessions(
+        &self,
+        request: AgentSearchSessionsRequest,
+    ) -> Result<Vec<AgentSessionSearchHit>, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let account_scope = self.account_scope.as_ref();
+        let query = request.query.trim();
+        if query.is_empty() {
+            return Err("Enter text to search Agent tasks".to_string());
+        }
+        if query.chars().count() > session_search::MAX_SEARCH_QUERY_CHARS {
+            return Err(format!(
+                "Agent task search is limited to {} characters",
+                session_search::MAX_SEARCH_QUERY_CHARS
+            ));
+        }
+        let limit = request
+            .limit
+            .unwrap_or(session_search::DEFAULT_SEARCH_RESULTS)
+            .clamp(1, session_search::MAX_SEARCH_RESULTS);
+        let filter_root = request
+            .project_root
+            .as_deref()
+            .filter(|value| !value.trim().is_empty())
+            .map(|path| normalize_project_root(Path::new(path)))
+            .transpose()?;
+
+        // Only the task list is read under the lifecycle lock. Reloading stale
+        // histories can take a while on a large account, and the index drops
+        // rows for an account that is no longer the indexed one.
+        let (session_manager, stale) = {
+            let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+            self.verify_generation().await?;
+            let session_manager = {
+                let runtime = state.inner.lock().await;
+                match runtime.as_ref() {
+                    Some(current) => {
+                        ensure_runtime_account(current, account_scope)?;
+                        Arc::clone(&current.session_manager)
+                    }
+                    None => account_session_manager(&state.host.paths, user_id)?,
+                }
+            };
+            let sessions = session_manager
+                .list_all_sessions()
+                .await
+                .map_err(|e| format!("Failed to list Agent tasks: {e}"))?
+                .into_iter()
+                .filter(|session| !is_unprompted_acp_session(session))
+                .map(|session| session_summary(&session))
+                .collect::<Vec<_>>();
+            let stale = state
+                .session_search
+                .stale_sessions(account_scope, &sessions)
+                .await;
+            (session_manager, stale)
+        };
+        for session_id in stale {
+            let session = match session_manager.get_session(&session_id, true).await {
+                Ok(session) => session,
+                Err(error) => {
+                    log::warn!("Skipping Agent task {session_id} while indexing search: {error}");
+                    continue;
+                }
+            };
+            let timeline = session
+                .conversation
+                .as_ref()
+                .map(conversation_to_timeline_items)
+                .unwrap_or_default();
+            state
+                .session_search
+                .replace_session(account_scope, session_summary(&session), &timeline)
+                .await;
+        }
+        Ok(state
+            .session_search
+            .search(account_scope, query, filter_root.as_deref(), limit)
+            .await)
+    }
+
     pub(crate) async fn available_model_ids(&self) -> Result<Vec<String>, String> {
         let state = &self.service;
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
@@ -3674,6 +4501,7 @@ impl AgentRuntimeHandle {
             .iter()
             .filter(|((pending_session_id, _), _)| pending_session_id == &session_id)
             .map(|((_, request_id), pending)| (request_id.clone(), pending.routing))
+            .chain(state.pending_elicitations.routes(&session_id).await)
             .collect::<HashMap<_, _>>();
         reconcile_desktop_permission_items(&mut timeline, &pending_routes, calling_surface_active);
 
@@ -3754,35 +4582,56 @@ impl AgentRuntimeHandle {
         Ok(summary)
     }
 
-    pub(crate) async fn load_image_attachment(
+    /// Copy a task's persisted history up to one timeline row into a new task.
+    ///
+    /// The fork keeps the source task's project root, locked model, MCP
+    /// selection, permission mode, and the image attachments its retained
+    /// messages reference. The source task is never modified.
+    pub(crate) async fn fork_session(
         &self,
-        session_id: String,
-        attachment_id: String,
-    ) -> Result<String, String> {
-        let state = &self.service;
-        let user_id = self.user_id.as_ref();
-        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
-        self.verify_generation().await?;
-        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
-        account_session_manager(&state.host.paths, user_id)?
-            .get_session(&session_id, false)
-            .await
-            .map_err(|error| format!("Failed to find Agent task {session_id}: {error}"))?;
-        let store = account_attachment_store(&state.host.paths, user_id)?;
-        tokio::task::spawn_blocking(move || store.data_url(&session_id, &attachment_id))
+        request: AgentForkSessionRequest,
+    ) -> Result<AgentSessionDetail, String> {
+        let timeline_item_id = request.timeline_item_id.trim();
+        if timeline_item_id.is_empty() {
+            return Err("Choose a timeline item to fork from".to_string());
+        }
+        self.fork_session_at(request.session_id, Some(timeline_item_id), request.title)
             .await
-            .map_err(|error| format!("Agent image attachment task failed: {error}"))?
     }
 
-    pub(crate) async fn list_session_mcp_servers(
+    /// Copy a task's complete saved history into a new durable task.
+    pub(crate) async fn fork_complete_session(
         &self,
         session_id: String,
-    ) -> Result<Vec<AgentSessionMcpServer>, String> {
+        title: Option<String>,
+    ) -> Result<AgentSessionDetail, String> {
+        self.fork_session_at(session_id, None, title).await
+    }
+
+    async fn fork_session_at(
+        &self,
+        session_id: String,
+        timeline_item_id: Option<&str>,
+        title: Option<String>,
+    ) -> Result<AgentSessionDetail, String> {
         let state = &self.service;
         let user_id = self.user_id.as_ref();
         let account_scope = self.account_scope.as_ref();
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
         self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+
+        let source_id = session_id.trim().to_string();
+        if source_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let requested_title = title
+            .as_deref()
+            .filter(|title| !title.trim().is_empty())
+            .map(normalize_user_provided_session_title)
+            .transpose()?;
+
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
         let session_manager = {
             let runtime = state.inner.lock().await;
             match runtime.as_ref() {
@@ -3793,19 +4642,412 @@ impl AgentRuntimeHandle {
                 None => account_session_manager(&state.host.paths, user_id)?,
             }
         };
+        let source = session_manager
+            .get_session(&source_id, true)
+            .await
+            .map_err(|error| format!("Failed to load Agent task to fork: {error}"))?;
+        let conversation = source
+            .conversation
+            .as_ref()
+            .ok_or_else(|| "Agent task history was not loaded".to_string())?;
+        let messages = match timeline_item_id {
+            Some(timeline_item_id) => {
+                fork_conversation_prefix(conversation.messages(), timeline_item_id)?
+            }
+            None => fork_complete_conversation(conversation.messages())?,
+        };
+        let attachment_ids = messages
+            .iter()
+            .flat_map(message_image_attachments)
+            .map(|attachment| attachment.id)
+            .collect::<Vec<_>>();
+        let title = requested_title
+            .clone()
+            .unwrap_or_else(|| forked_session_title(&source.name));
+
+        let forked = session_manager
+            .create_session(
+                source.working_dir.clone(),
+                title.clone(),
+                source.session_type,
+                source.goose_mode,
+            )
+            .await
+            .map_err(|error| format!("Failed to create forked Agent task: {error}"))?;
+        let store = account_attachment_store(&state.host.paths, user_id)?;
+        let copy_result: Result<(), String> = async {
+            let mut update = session_manager
+                .update(&forked.id)
+                .extension_data(source.extension_data.clone());
+            if let Some(provider_name) = source.provider_name.clone() {
+                update = update.provider_name(provider_name);
+            }
+            if let Some(model_config) = source.model_config.clone() {
+                update = update.model_config(model_config);
+            }
+            if requested_title.is_some() {
+                update = update.user_provided_name(title.clone());
+            }
+            update
+                .apply()
+                .await
+                .map_err(|error| format!("Failed to configure forked Agent task: {error}"))?;
+            if !messages.is_empty() {
+                session_manager
+                    .replace_conversation(&forked.id, &Conversation::new_unvalidated(messages))
+                    .await
+                    .map_err(|error| format!("Failed to copy Agent task history: {error}"))?;
+            }
+            let store = store.clone();
+            let from = source.id.clone();
+            let to = forked.id.clone();
+            tokio::task::spawn_blocking(move || store.copy_session(&from, &to, &attachment_ids))
+                .await
+                .map_err(|error| format!("Agent image attachment task failed: {error}"))?
+        }
+        .await;
+        if let Err(error) = copy_result {
+            if let Err(cleanup_error) = session_manager.delete_session(&forked.id).await {
+                log::warn!(
+                    "Failed to remove incomplete forked Agent task {}: {cleanup_error}",
+                    forked.id
+                );
+            }
+            if let Err(cleanup_error) = store.delete_session(&forked.id) {
+                log::warn!(
+                    "Failed to remove images of incomplete forked Agent task {}: {cleanup_error}",
+                    forked.id
+                );
+            }
+            return Err(error);
+        }
+
         let session = session_manager
-            .get_session(session_id.trim(), false)
+            .get_session(&forked.id, true)
             .await
-            .map_err(|error| format!("Failed to load Agent task: {error}"))?;
-        let configured = normalize_mcp_servers(
-            load_agent_config_inner(&state.host.paths, user_id)
-                .map_err(|error| format!("Failed to load MCP servers: {error}"))?
-                .mcp_servers,
-        )?;
-        Ok(session_mcp_servers(&configured, &session))
+            .map_err(|error| format!("Failed to load forked Agent task: {error}"))?;
+        let timeline = session
+            .conversation
+            .as_ref()
+            .map(conversation_to_timeline_items)
+            .unwrap_or_default();
+        let summary = session_summary(&session);
+        emit_agent_event(
+            &state.host.events,
+            AgentServiceEvent::SessionCreated(summary.clone()),
+        );
+        Ok(AgentSessionDetail {
+            session: summary,
+            timeline,
+            mcp_errors: Vec::new(),
+            queue: empty_desktop_queue_snapshot(),
+        })
     }
 
-    pub(crate) async fn set_session_mcp_server_enabled(
+    /// Write one task's conversation, timeline, summary, usage, and image
+    /// attachments to a versioned archive at `request.path`.
+    pub(crate) async fn export_session(
+        &self,
+        request: AgentExportSessionRequest,
+    ) -> Result<AgentSessionSummary, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let account_scope = self.account_scope.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+
+        let session_id = request.session_id.trim().to_string();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let path = PathBuf::from(request.path.trim());
+        if !path.is_absolute() {
+            return Err("Choose where to save the exported Agent task".to_string());
+        }
+
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        let session_manager = {
+            let runtime = state.inner.lock().await;
+            match runtime.as_ref() {
+                Some(current) => {
+                    ensure_runtime_account(current, account_scope)?;
+                    Arc::clone(&current.session_manager)
+                }
+                None => account_session_manager(&state.host.paths, user_id)?,
+            }
+        };
+        let session = session_manager
+            .get_session(&session_id, true)
+            .await
+            .map_err(|error| format!("Failed to load Agent task to export: {error}"))?;
+        let messages = session
+            .conversation
+            .as_ref()
+            .ok_or_else(|| "Agent task history was not loaded".to_string())?
+            .messages()
+            .to_vec();
+        let mut seen_attachments = HashSet::new();
+        let referenced_attachments = messages
+            .iter()
+            .flat_map(message_image_attachments)
+            .filter(|attachment| seen_attachments.insert(attachment.id.clone()))
+            .collect::<Vec<_>>();
+        let summary = session_summary(&session);
+        let timeline =
+            conversation_to_timeline_items(&Conversation::new_unvalidated(messages.clone()));
+        let usage = AgentRunUsage::from_accumulated_session(&session);
+        let store = account_attachment_store(&state.host.paths, user_id)?;
+
+        tokio::task::spawn_blocking(move || {
+            let attachments = store.export_session(&session_id, &referenced_attachments)?;
+            let archive = session_archive::SessionArchive {
+                manifest: session_archive::SessionArchiveManifest {
+                    format: session_archive::SESSION_ARCHIVE_FORMAT.to_string(),
+                    version: session_archive::SESSION_ARCHIVE_VERSION,
+                    exported_ms: unix_ms(),
+                    session: summary.clone(),
+                    usage: usage.into(),
+                    attachments: attachments
+                        .iter()
+                        .map(|(attachment, _)| attachment.clone())
+                        .collect(),
+                },
+                conversation: messages,
+                timeline,
+                attachments,
+            };
+            session_archive::write_session_archive(&path, &archive)?;
+            Ok(summary)
+        })
+        .await
+        .map_err(|error| format!("Agent task export failed: {error}"))?
+    }
+
+    /// Create a new task from an archive written by [`Self::export_session`].
+    ///
+    /// The archive is untrusted: imported tasks always start in Read only
+    /// mode, do not inherit MCP selections from the exporting machine, and
+    /// keep the archived usage only as part of the archive rather than adding
+    /// it to this account's usage ledger.
+    pub(crate) async fn import_session(
+        &self,
+        request: AgentImportSessionRequest,
+    ) -> Result<AgentSessionDetail, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let account_scope = self.account_scope.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+
+        let path = PathBuf::from(request.path.trim());
+        if !path.is_absolute() {
+            return Err("Choose a Maple task archive to import".to_string());
+        }
+        let archive =
+            tokio::task::spawn_blocking(move || session_archive::read_session_archive(&path))
+                .await
+                .map_err(|error| format!("Agent task import failed: {error}"))??;
+
+        let root = match request.project_root.as_deref() {
+            Some(path) if !path.trim().is_empty() => normalize_project_root(Path::new(path))?,
+            _ => normalize_project_root(Path::new(&archive.manifest.session.project_root))
+                .map_err(|_| {
+                    "The archived project folder is not on this device. Choose a project folder"
+                        .to_string()
+                })?,
+        };
+        let config = load_agent_config_inner(&state.host.paths, user_id)
+            .map_err(|error| error.to_string())?;
+        ensure_session_project_root_is_visible(&root, &config.removed_project_roots)?;
+        let title = normalize_user_provided_session_title(&archive.manifest.session.title)
+            .unwrap_or_else(|_| DEFAULT_AGENT_SESSION_TITLE.to_string());
+        let model_config = archive
+            .manifest
+            .session
+            .model
+            .as_deref()
+            .filter(|model| selectable_agent_model_id(model))
+            .and_then(|model| maple_model_config(model, None).ok());
+
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        let session_manager = {
+            let runtime = state.inner.lock().await;
+            match runtime.as_ref() {
+                Some(current) => {
+                    ensure_runtime_account(current, account_scope)?;
+                    Arc::clone(&current.session_manager)
+                }
+                None => account_session_manager(&state.host.paths, user_id)?,
+            }
+        };
+        let imported = session_manager
+            .create_session(root, title, SessionType::User, GooseMode::SmartApprove)
+            .await
+            .map_err(|error| format!("Failed to create imported Agent task: {error}"))?;
+        let store = account_attachment_store(&state.host.paths, user_id)?;
+        let import_result: Result<(), String> = async {
+            if let Some(model_config) = model_config {
+                session_manager
+                    .update(&imported.id)
+                    .provider_name(MAPLE_PROVIDER_NAME.to_string())
+                    .model_config(model_config)
+                    .apply()
+                    .await
+                    .map_err(|error| format!("Failed to configure imported Agent task: {error}"))?;
+            }
+            if !archive.conversation.is_empty() {
+                session_manager
+                    .replace_conversation(
+                        &imported.id,
+                        &Conversation::new_unvalidated(archive.conversation),
+                    )
+                    .await
+                    .map_err(|error| format!("Failed to import Agent task history: {error}"))?;
+            }
+            let store = store.clone();
+            let session_id = imported.id.clone();
+            let attachments = archive.attachments;
+            tokio::task::spawn_blocking(move || store.import_session(&session_id, &attachments))
+                .await
+                .map_err(|error| format!("Agent image attachment task failed: {error}"))?
+        }
+        .await;
+        if let Err(error) = import_result {
+            if let Err(cleanup_error) = session_manager.delete_session(&imported.id).await {
+                log::warn!(
+                    "Failed to remove incomplete imported Agent task {}: {cleanup_error}",
+                    imported.id
+                );
+            }
+            if let Err(cleanup_error) = store.delete_session(&imported.id) {
+                log::warn!(
+                    "Failed to remove images of incomplete imported Agent task {}: {cleanup_error}",
+                    imported.id
+                );
+            }
+            return Err(error);
+        }
+
+        let session = session_manager
+            .get_session(&imported.id, true)
+            .await
+            .map_err(|error| format!("Failed to load imported Agent task: {error}"))?;
+        let timeline = session
+            .conversation
+            .as_ref()
+            .map(conversation_to_timeline_items)
+            .unwrap_or_default();
+        let summary = session_summary(&session);
+        emit_agent_event(
+            &state.host.events,
+            AgentServiceEvent::SessionCreated(summary.clone()),
+        );
+        Ok(AgentSessionDetail {
+            session: summary,
+            timeline,
+            mcp_errors: Vec::new(),
+            queue: empty_desktop_queue_snapshot(),
+        })
+    }
+
+    /// Render a task's timeline as a shareable Markdown or self-contained
+    /// HTML transcript with its image attachments inlined.
+    pub(crate) async fn render_session_transcript(
+        &self,
+        request: AgentTranscriptRequest,
+    ) -> Result<AgentTranscript, String> {
+        let session_id = request.session_id.trim().to_string();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let detail = self.load_session(session_id.clone()).await?;
+        let store = account_attachment_store(&self.service.host.paths, self.user_id.as_ref())?;
+        let attachment_ids = transcript::transcript_attachment_ids(&detail.timeline);
+        let images = tokio::task::spawn_blocking(move || {
+            attachment_ids
+                .into_iter()
+                .filter_map(|id| match store.data_url(&session_id, &id) {
+                    Ok(data_url) => Some((id, data_url)),
+                    Err(error) => {
+                        log::warn!("Omitting Agent image {id} from transcript: {error}");
+                        None
+                    }
+                })
+                .collect::<HashMap<_, _>>()
+        })
+        .await
+        .map_err(|error| format!("Agent image attachment task failed: {error}"))?;
+        let content = transcript::render_transcript(
+            &detail.session,
+            &detail.timeline,
+            &transcript::TranscriptOptions {
+                format: request.format,
+                include_thinking: request.include_thinking,
+                images: &images,
+            },
+        );
+        Ok(AgentTranscript {
+            session_id: detail.session.id,
+            title: detail.session.title,
+            format: request.format,
+            content,
+        })
+    }
+
+    pub(crate) async fn load_image_attachment(
+        &self,
+        session_id: String,
+        attachment_id: String,
+    ) -> Result<String, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        account_session_manager(&state.host.paths, user_id)?
+            .get_session(&session_id, false)
+            .await
+            .map_err(|error| format!("Failed to find Agent task {session_id}: {error}"))?;
+        let store = account_attachment_store(&state.host.paths, user_id)?;
+        tokio::task::spawn_blocking(move || store.data_url(&session_id, &attachment_id))
+            .await
+            .map_err(|error| format!("Agent image attachment task failed: {error}"))?
+    }
+
+    pub(crate) async fn list_session_mcp_servers(
+        &self,
+        session_id: String,
+    ) -> Result<Vec<AgentSessionMcpServer>, String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let account_scope = self.account_scope.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        let session_manager = {
+            let runtime = state.inner.lock().await;
+            match runtime.as_ref() {
+                Some(current) => {
+                    ensure_runtime_account(current, account_scope)?;
+                    Arc::clone(&current.session_manager)
+                }
+                None => account_session_manager(&state.host.paths, user_id)?,
+            }
+        };
+        let session = session_manager
+            .get_session(session_id.trim(), false)
+            .await
+            .map_err(|error| format!("Failed to load Agent task: {error}"))?;
+        let configured = normalize_mcp_servers(
+            load_agent_config_inner(&state.host.paths, user_id)
+                .map_err(|error| format!("Failed to load MCP servers: {error}"))?
+                .mcp_servers,
+        )?;
+        let health = state.mcp_health.session(&session.id).await;
+        Ok(session_mcp_servers(&configured, &session, &health))
+    }
+
+    pub(crate) async fn set_session_mcp_server_enabled(
         &self,
         request: AgentSetSessionMcpServerRequest,
     ) -> Result<Vec<AgentSessionMcpServer>, String> {
@@ -3899,13 +5141,38 @@ impl AgentRuntimeHandle {
                                 request.name.trim()
                             )
                         })?;
-                    let extension = mcp_server_to_extension(server)?;
-                    agent
-                        .add_extension(extension, &session_id)
-                        .await
-                        .map_err(|error| {
-                            format!("Failed to connect MCP server '{}': {error}", server.name)
-                        })?;
+                    if maple_connects_mcp_server(server) {
+                        attach_maple_mcp_server(
+                            &agent,
+                            server,
+                            &session.working_dir,
+                            &McpSamplingScope::new(state, account_scope, &session_id),
+                        )
+                        .await?;
+                        agent
+                            .persist_extension_state(&session_id)
+                            .await
+                            .map_err(|error| {
+                                format!("Failed to save task MCP settings: {error}")
+                            })?;
+                    } else {
+                        let extension =
+                            mcp_server_to_session_extension(server, &session.working_dir)?;
+                        agent
+                            .add_extension(extension, &session_id)
+                            .await
+                            .map_err(|error| {
+                                format!("Failed to connect MCP server '{}': {error}", server.name)
+                            })?;
+                    }
+                    state
+                        .mcp_tool_permissions
+                        .apply_to_session(
+                            &agent,
+                            &session_id,
+                            &HashSet::from([requested_key.clone()]),
+                        )
+                        .await;
                 }
             } else if let Some(config) = active_config {
                 agent
@@ -3936,53 +5203,186 @@ impl AgentRuntimeHandle {
             .get_session(&session_id, false)
             .await
             .map_err(|error| format!("Failed to reload Agent task: {error}"))?;
-        Ok(session_mcp_servers(&configured, &refreshed))
+        // The user's own reconnect or disconnect supersedes any earlier
+        // automatic attempt.
+        state.mcp_health.forget(&session_id, &requested_key).await;
+        let health = state.mcp_health.session(&session_id).await;
+        Ok(session_mcp_servers(&configured, &refreshed, &health))
     }
 
-    pub(crate) async fn delete_session(&self, session_id: String) -> Result<(), String> {
-        self.delete_session_inner(session_id, true).await
+    pub(crate) async fn list_session_mcp_context(
+        &self,
+        session_id: String,
+    ) -> Result<AgentMcpContextCatalog, String> {
+        let session_id = session_id.trim().to_string();
+        let clients = self.session_mcp_clients(&session_id).await?;
+        Ok(mcp_context::list_context(&clients, &session_id).await)
     }
 
-    async fn delete_session_inner(
+    pub(crate) async fn read_session_mcp_resource(
         &self,
-        session_id: String,
-        require_admission: bool,
-    ) -> Result<(), String> {
+        request: AgentReadMcpResourceRequest,
+    ) -> Result<AgentMcpComposerText, String> {
+        let session_id = request.session_id.trim().to_string();
+        let uri = request.uri.trim();
+        if uri.is_empty() {
+            return Err("MCP resource URI cannot be empty".to_string());
+        }
+        let server = self
+            .session_mcp_client(&session_id, &request.server)
+            .await?;
+        mcp_context::read_resource(&server, &session_id, uri)
+            .await
+            .map_err(|error| format!("Failed to read MCP resource '{uri}': {error}"))
+    }
+
+    pub(crate) async fn get_session_mcp_prompt(
+        &self,
+        request: AgentGetMcpPromptRequest,
+    ) -> Result<AgentMcpComposerText, String> {
+        let session_id = request.session_id.trim().to_string();
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err("MCP prompt name cannot be empty".to_string());
+        }
+        let server = self
+            .session_mcp_client(&session_id, &request.server)
+            .await?;
+        mcp_context::expand_prompt(&server, &session_id, name, request.arguments)
+            .await
+            .map_err(|error| format!("Failed to expand MCP prompt '{name}': {error}"))
+    }
+
+    async fn session_mcp_client(
+        &self,
+        session_id: &str,
+        server: &str,
+    ) -> Result<mcp_context::SessionMcpClient, String> {
+        let requested_key = goose::config::extensions::name_to_key(server.trim());
+        self.session_mcp_clients(session_id)
+            .await?
+            .into_iter()
+            .find(|client| goose::config::extensions::name_to_key(&client.name) == requested_key)
+            .ok_or_else(|| {
+                format!(
+                    "MCP server '{}' is not connected to this task",
+                    server.trim()
+                )
+            })
+    }
+
+    /// Clients for the user MCP servers connected to a session. The lifecycle
+    /// locks are released on return so slow servers never block other work.
+    async fn session_mcp_clients(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<mcp_context::SessionMcpClient>, String> {
         let state = &self.service;
-        let user_id = self.user_id.as_ref();
         let account_scope = self.account_scope.as_ref();
         let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
         self.verify_generation().await?;
-        if require_admission {
-            self.ensure_accepting_new_work()?;
-        }
-        let session_id = session_id.trim().to_string();
+        self.ensure_accepting_new_work()?;
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
         if session_id.is_empty() {
             return Err("Agent task ID cannot be empty".to_string());
         }
 
-        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
-        let (agent_manager, session_manager, permission_modes, web_tool_state, title_task) = {
-            let mut runtime = state.inner.lock().await;
-            match runtime.as_mut() {
-                Some(current) => {
-                    ensure_runtime_account(current, account_scope)?;
-                    if has_active_session_run(&current.active_runs, &session_id) {
-                        return Err("Stop the running agent before deleting this task".to_string());
-                    }
-                    if current
-                        .session_tool_contexts
-                        .get(&session_id)
-                        .is_some_and(|installed| installed.owner == AgentToolContextOwner::Leased)
-                    {
-                        return Err(
-                            "Disconnect the external Agent surface before deleting this task"
-                                .to_string(),
-                        );
-                    }
-                    (
-                        Some(Arc::clone(&current.agent_manager)),
-                        Arc::clone(&current.session_manager),
+        let (agent_manager, session_manager, maple_api_session) = {
+            let runtime = state.inner.lock().await;
+            let current = runtime
+                .as_ref()
+                .ok_or_else(|| "Agent runtime is not running".to_string())?;
+            ensure_runtime_account(current, account_scope)?;
+            // An external surface owns the session's tools while it holds
+            // the lease, including the MCP servers it reads context from.
+            if current
+                .session_tool_contexts
+                .get(session_id)
+                .is_some_and(|installed| installed.owner == AgentToolContextOwner::Leased)
+            {
+                return Err(
+                    "Disconnect the external Agent surface before using this task's MCP servers"
+                        .to_string(),
+                );
+            }
+            (
+                Arc::clone(&current.agent_manager),
+                Arc::clone(&current.session_manager),
+                Arc::clone(&current.maple_api_session),
+            )
+        };
+        let session = session_manager
+            .get_session(session_id, false)
+            .await
+            .map_err(|error| format!("Failed to load Agent task: {error}"))?;
+        let manager_result = get_or_create_session_agent(
+            &agent_manager,
+            &maple_api_session,
+            &session,
+            RuntimeContext::default(),
+        )
+        .await
+        .map_err(|error| format!("Failed to load Goose agent: {error}"))?;
+        let agent = manager_result.agent;
+        let mut clients = Vec::new();
+        for config in agent.get_extension_configs().await {
+            if mcp_transport_label(&config).is_none() {
+                continue;
+            }
+            if let Some(client) = agent.extension_manager.get_mcp_client(&config.key()).await {
+                clients.push(mcp_context::SessionMcpClient {
+                    name: config.name(),
+                    client,
+                });
+            }
+        }
+        Ok(clients)
+    }
+
+    pub(crate) async fn delete_session(&self, session_id: String) -> Result<(), String> {
+        self.delete_session_inner(session_id, true).await
+    }
+
+    async fn delete_session_inner(
+        &self,
+        session_id: String,
+        require_admission: bool,
+    ) -> Result<(), String> {
+        let state = &self.service;
+        let user_id = self.user_id.as_ref();
+        let account_scope = self.account_scope.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        if require_admission {
+            self.ensure_accepting_new_work()?;
+        }
+        let session_id = session_id.trim().to_string();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        let (agent_manager, session_manager, permission_modes, web_tool_state, title_task) = {
+            let mut runtime = state.inner.lock().await;
+            match runtime.as_mut() {
+                Some(current) => {
+                    ensure_runtime_account(current, account_scope)?;
+                    if has_active_session_run(&current.active_runs, &session_id) {
+                        return Err("Stop the running agent before deleting this task".to_string());
+                    }
+                    if current
+                        .session_tool_contexts
+                        .get(&session_id)
+                        .is_some_and(|installed| installed.owner == AgentToolContextOwner::Leased)
+                    {
+                        return Err(
+                            "Disconnect the external Agent surface before deleting this task"
+                                .to_string(),
+                        );
+                    }
+                    (
+                        Some(Arc::clone(&current.agent_manager)),
+                        Arc::clone(&current.session_manager),
                         Some(Arc::clone(&current.permission_modes)),
                         Some(Arc::clone(&current.web_tool_state)),
                         current.session_title_tasks.remove(&session_id),
@@ -4011,6 +5411,8 @@ impl AgentRuntimeHandle {
             &session_id,
         )
         .await?;
+        state.session_search.forget_session(&session_id).await;
+        state.mcp_health.forget_session(&session_id).await;
         match account_attachment_store(&state.host.paths, user_id)
             .and_then(|store| store.delete_session(&session_id))
         {
@@ -4019,6 +5421,11 @@ impl AgentRuntimeHandle {
                 log::warn!("Deleted Agent task {session_id}, but failed to clear images: {error}")
             }
         }
+        if let Err(error) = account_checkpoint_store(&state.host.paths, user_id)
+            .and_then(|store| store.delete_session(&session_id))
+        {
+            log::warn!("Deleted Agent task {session_id}, but failed to clear checkpoints: {error}");
+        }
         if let Some(agent_manager) = agent_manager {
             if let Err(error) = agent_manager.remove_session_if_loaded(&session_id).await {
                 log::warn!(
@@ -4320,9 +5727,6 @@ impl AgentRuntimeHandle {
         self.verify_generation().await?;
         self.ensure_accepting_new_work()?;
         let text = request.text.trim().to_string();
-        if !request.attachments.is_empty() && desktop_send == DesktopSendDisposition::StartOnly {
-            return Err("Image attachments are available only in Maple Agent Mode".to_string());
-        }
         if text.is_empty()
             && request.attachments.is_empty()
             && desktop_send == DesktopSendDisposition::StartOnly
@@ -4454,6 +5858,9 @@ impl AgentRuntimeHandle {
             return Err("Failed to create user timeline item".to_string());
         }
         let live_timelines = Arc::clone(&state.live_timelines);
+        let session_search = state.session_search.clone();
+        let permission_rules = state.permission_rules.clone();
+        let checkpoint_runs = state.checkpoint_runs.clone();
 
         // Claim the session before changing its title, provider, mode, or
         // extensions. A duplicate send must not mutate an Agent that is already
@@ -4560,6 +5967,9 @@ impl AgentRuntimeHandle {
                     mode: &effective_mode,
                     primary_model_supports_vision: request.vision_capable,
                     tool_context: &tool_context,
+                    checkpoint_runs: &state.checkpoint_runs,
+                    mcp_tool_permissions: &state.mcp_tool_permissions,
+                    mcp_sampling: McpSamplingScope::new(state, account_scope, &session.id),
                 },
             )
             .await?;
@@ -4569,10 +5979,11 @@ impl AgentRuntimeHandle {
                 tool_context,
                 should_name_from_prompt,
                 usage_before,
+                session,
             ))
         }
         .await;
-        let (agent, mcp_errors, tool_context, generate_session_title, usage_before) =
+        let (agent, mcp_errors, tool_context, generate_session_title, usage_before, session) =
             match setup_result {
                 Ok(setup) => setup,
                 Err(error) => {
@@ -4640,6 +6051,7 @@ impl AgentRuntimeHandle {
         let state_inner = Arc::clone(&state.inner);
         let session_lifecycle = Arc::clone(&state.session_lifecycle);
         let task_pending_permissions = Arc::clone(&state.pending_permissions);
+        let task_pending_elicitations = state.pending_elicitations.clone();
         let session_id = request.session_id.clone();
         let task_run_id = run_id.clone();
         let task_agent_manager = Arc::clone(&agent_manager);
@@ -4655,6 +6067,8 @@ impl AgentRuntimeHandle {
         let accepting_queue = Arc::new(AtomicBool::new(true));
         let steered_unacked = Arc::new(Mutex::new(Vec::new()));
         let task_steered_unacked = Arc::clone(&steered_unacked);
+        let deferred_usage = DeferredRunUsage::new();
+        let task_deferred_usage = deferred_usage.clone();
         let task_accepting_queue = Arc::clone(&accepting_queue);
         let task_desktop_queues = Arc::clone(&state.desktop_queues);
         let task_account_scope = account_scope.to_string();
@@ -4729,15 +6143,84 @@ impl AgentRuntimeHandle {
         } else {
             (None, None, None, None)
         };
+        let health_monitor_stop = CancellationToken::new();
+        let health_monitor_guard = health_monitor_stop.clone().drop_guard();
+        {
+            let reconnect_paths = state.host.paths.clone();
+            let reconnect_user_id = user_id.to_string();
+            let reconnect_lifecycle = Arc::clone(&session_lifecycle);
+            let reconnect_agent = Arc::clone(&agent);
+            let reconnect_session = session.clone();
+            let reconnect_sampling = McpSamplingScope::new(state, account_scope, &session_id);
+            let snapshot = goose::session::EnabledExtensionsState::from_extension_data(
+                &session.extension_data,
+            )
+            .map(|state| state.extensions)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|config| mcp_transport_label(config).is_some())
+            .collect::<Vec<_>>();
+            // Servers Maple connects are rebuilt from their settings as of
+            // this run, like the Goose extensions in the snapshot.
+            let reconnect_maple_servers = Arc::new(
+                frozen_maple_mcp_servers(&state.host.paths, user_id, &snapshot)
+                    .map_err(|error| format!("Failed to load MCP servers: {error}")),
+            );
+            tokio::spawn(
+                McpHealthMonitor {
+                    agent: Arc::clone(&agent),
+                    session_id: session_id.clone(),
+                    snapshot,
+                    registry: state.mcp_health.clone(),
+                    events: run_events.clone(),
+                    reconnect: move |config: ExtensionConfig| {
+                        let paths = reconnect_paths.clone();
+                        let user_id = reconnect_user_id.clone();
+                        let lifecycle = Arc::clone(&reconnect_lifecycle);
+                        let agent = Arc::clone(&reconnect_agent);
+                        let session = reconnect_session.clone();
+                        let sampling = reconnect_sampling.clone();
+                        let maple_server = match reconnect_maple_servers.as_ref() {
+                            Ok(servers) => Ok(servers.get(&config.key()).cloned()),
+                            Err(error) => Err(error.clone()),
+                        };
+                        async move {
+                            match maple_server? {
+                                Some(server) => {
+                                    reconnect_maple_mcp_server(
+                                        &lifecycle, &agent, &session, &server, &sampling,
+                                    )
+                                    .await
+                                }
+                                None => {
+                                    reconnect_session_mcp_server(
+                                        &paths, &user_id, &lifecycle, &agent, &session, config,
+                                    )
+                                    .await
+                                }
+                            }
+                        }
+                    },
+                    timing: McpHealthTiming::default(),
+                    stop: health_monitor_stop,
+                }
+                .run(),
+            );
+        }
         let (start_tx, start_rx) = oneshot::channel();
         let (terminal_tx, terminal_rx) = watch::channel(None);
         let (usage_tx, usage_rx) = watch::channel(None);
         let task = tokio::spawn(async move {
+            // Probing stops with the run, whichever way it ends.
+            let _health_monitor_guard = health_monitor_guard;
             let should_run = tokio::select! {
                 biased;
                 _ = task_cancel_token.cancelled() => false,
                 start = start_rx => start.is_ok(),
             };
+            if should_run {
+                checkpoint_runs.begin(&session_id, &task_run_id);
+            }
             let mut pending_user_messages = task_user_messages;
             let mut current_user_message = pending_user_messages
                 .last()
@@ -4769,6 +6252,8 @@ impl AgentRuntimeHandle {
                             session_manager: Arc::clone(&task_session_manager),
                             session_title_lifecycle: Arc::clone(&session_title_lifecycle),
                             live_timelines: live_timelines.clone(),
+                            session_search: session_search.clone(),
+                            permission_rules: permission_rules.clone(),
                             session_id: session_id.clone(),
                             user_message: current_user_message.clone(),
                             permission_modes: Arc::clone(&task_permission_modes),
@@ -4783,6 +6268,7 @@ impl AgentRuntimeHandle {
                             cancel_token: task_cancel_token.clone(),
                             session_title_start: session_title_start.take(),
                             pending_permissions: Arc::clone(&task_pending_permissions),
+                            pending_elicitations: task_pending_elicitations.clone(),
                             issued_permission_ids: Arc::clone(&task_issued_permission_ids),
                             cancelled_permission_ids: Arc::clone(&task_cancelled_permission_ids),
                             run_id: task_run_id.clone(),
@@ -4832,6 +6318,7 @@ impl AgentRuntimeHandle {
                                         emit_promoted_queue_items(
                                             &task_events,
                                             &live_timelines,
+                                            &session_search,
                                             &session_id,
                                             permission_routing,
                                             &queued,
@@ -4870,6 +6357,8 @@ impl AgentRuntimeHandle {
                 }
             }
 
+            checkpoint_runs.finish(&session_id, &task_run_id);
+
             // Keep deletion serialized until every terminal write and event for
             // this run has completed. The active-run entry stays visible while
             // the cleanup is in progress, so deletion continues to reject it.
@@ -4916,6 +6405,25 @@ impl AgentRuntimeHandle {
                     }
                 }
             }
+            for (elicitation_session_id, request_id) in cancel_pending_elicitations_for_runs(
+                &task_pending_elicitations,
+                std::slice::from_ref(&task_run_id),
+                &HashMap::from([(task_run_id.clone(), Arc::clone(&task_agent))]),
+            )
+            .await
+            {
+                if let Some(item) = update_live_elicitation_status(
+                    &live_timelines,
+                    &elicitation_session_id,
+                    permission_routing,
+                    &request_id,
+                    "cancelled",
+                )
+                .await
+                {
+                    task_events.publish(AgentRunEvent::TimelineItem(item)).await;
+                }
+            }
             let cancelled_permission_ids = task_cancelled_permission_ids.lock().await.clone();
             let result = if run_was_cancelled {
                 finalize_cancelled_agent_turn(
@@ -4971,6 +6479,9 @@ impl AgentRuntimeHandle {
                 "failed" => AgentRunTerminal::Failed,
                 _ => AgentRunTerminal::Completed,
             };
+            for usage in task_deferred_usage.settle().await {
+                record_helper_usage(&task_session_manager, &session_id, &usage).await;
+            }
             let usage = task_session_manager
                 .get_session(&session_id, false)
                 .await
@@ -5019,6 +6530,7 @@ impl AgentRuntimeHandle {
                                 cancelled_permission_ids: Arc::clone(&cancelled_permission_ids),
                                 accepting_queue: Arc::clone(&accepting_queue),
                                 steered_unacked: Arc::clone(&steered_unacked),
+                                deferred_usage: deferred_usage.clone(),
                                 task_handle: task.take().expect("task handle must be available"),
                             },
                         );
@@ -5099,6 +6611,7 @@ impl AgentRuntimeHandle {
                 record_and_emit_timeline_item(
                     &run_events,
                     &state.live_timelines,
+                    &state.session_search,
                     &request.session_id,
                     permission_routing,
                     item,
@@ -5342,12 +6855,32 @@ impl AgentRuntimeHandle {
         accepting_queue.store(false, Ordering::Release);
         tool_context.cancel_run(&cancel_token);
         let run_id = run_id.to_string();
+        let agents_by_run = HashMap::from([(run_id.clone(), agent)]);
         let cancelled_permissions = cancel_pending_permissions_for_runs(
             &state.pending_permissions,
             std::slice::from_ref(&run_id),
-            &HashMap::from([(run_id.clone(), agent)]),
+            &agents_by_run,
+        )
+        .await;
+        let cancelled_elicitations = cancel_pending_elicitations_for_runs(
+            &state.pending_elicitations,
+            std::slice::from_ref(&run_id),
+            &agents_by_run,
         )
         .await;
+        for (session_id, request_id) in cancelled_elicitations {
+            if let Some(item) = update_live_elicitation_status(
+                &state.live_timelines,
+                &session_id,
+                expected_routing,
+                &request_id,
+                "cancelled",
+            )
+            .await
+            {
+                run_events.publish(AgentRunEvent::TimelineItem(item)).await;
+            }
+        }
         cancelled_permission_ids.lock().await.extend(
             cancelled_permissions
                 .iter()
@@ -5524,7 +7057,14 @@ impl AgentRuntimeHandle {
                 request_ids
             };
             for request_id in request_ids {
-                deliver_tool_permission(&agent, request_id.clone(), Permission::AllowOnce).await;
+                if !state
+                    .mcp_sampling_approvals
+                    .resolve(&request_id, AgentPermissionDecision::AllowOnce)
+                    .await
+                {
+                    deliver_tool_permission(&agent, request_id.clone(), Permission::AllowOnce)
+                        .await;
+                }
                 if let Some(item) = update_live_permission_status(
                     &state.live_timelines,
                     &session_id,
@@ -5685,15 +7225,13 @@ impl AgentRuntimeHandle {
                 .await
                 .insert(request_id.clone());
         }
-        agent
-            .handle_confirmation(
-                request_id.clone(),
-                PermissionConfirmation {
-                    principal_type: PrincipalType::Tool,
-                    permission: decision.goose_permission(),
-                },
-            )
-            .await;
+        if !state
+            .mcp_sampling_approvals
+            .resolve(&request_id, decision)
+            .await
+        {
+            deliver_tool_permission(&agent, request_id.clone(), decision.goose_permission()).await;
+        }
         if let Some(item) = update_live_permission_status(
             &state.live_timelines,
             &session_id,
@@ -5721,6 +7259,151 @@ impl AgentRuntimeHandle {
         }
         Ok(())
     }
+
+    pub(crate) async fn elicitation_respond(
+        &self,
+        response: AgentElicitationResponse,
+    ) -> Result<(), String> {
+        let action = AgentElicitationAction::parse(&response.action)?;
+        self.resolve_elicitation(
+            response.session_id,
+            response.request_id,
+            action,
+            response.content,
+            AgentPermissionResponseScope::Desktop,
+        )
+        .await
+    }
+
+    async fn elicitation_respond_for_run(
+        &self,
+        session_id: &str,
+        run_id: &str,
+        request_id: String,
+        action: AgentElicitationAction,
+        content: Option<Value>,
+    ) -> Result<(), String> {
+        self.resolve_elicitation(
+            session_id.to_string(),
+            request_id,
+            action,
+            content,
+            AgentPermissionResponseScope::CallingSurface {
+                run_id: run_id.to_string(),
+            },
+        )
+        .await
+    }
+
+    async fn resolve_elicitation(
+        &self,
+        session_id: String,
+        request_id: String,
+        action: AgentElicitationAction,
+        content: Option<Value>,
+        scope: AgentPermissionResponseScope,
+    ) -> Result<(), String> {
+        let state = &self.service;
+        let account_scope = self.account_scope.as_ref();
+        let _runtime_lifecycle_guard = state.runtime_lifecycle.lock().await;
+        self.verify_generation().await?;
+        self.ensure_accepting_new_work()?;
+        let _session_lifecycle_guard = state.session_lifecycle.lock().await;
+        let session_id = session_id.trim().to_string();
+        if session_id.is_empty() {
+            return Err("Agent input response requires a task ID".to_string());
+        }
+        let Some(pending) = state
+            .pending_elicitations
+            .get(&session_id, &request_id)
+            .await
+        else {
+            return Err(format!(
+                "No pending Agent input request found for {request_id} in task {session_id}"
+            ));
+        };
+        let (agent, run_events) = {
+            let runtime = state.inner.lock().await;
+            let current = runtime
+                .as_ref()
+                .ok_or_else(|| "Agent runtime is not running".to_string())?;
+            ensure_runtime_account(current, account_scope)?;
+            let active_run = current
+                .active_runs
+                .get(&pending.run_id)
+                .filter(|run| run.session_id == session_id)
+                .ok_or_else(|| {
+                    format!("No running Agent task found for input request {request_id}")
+                })?;
+            let owns_request = match &scope {
+                AgentPermissionResponseScope::Desktop => {
+                    pending.routing == AgentPermissionRouting::Desktop
+                }
+                AgentPermissionResponseScope::CallingSurface { run_id } => {
+                    pending.routing == AgentPermissionRouting::CallingSurface
+                        && run_id == &pending.run_id
+                }
+            };
+            if !owns_request {
+                return Err("Agent input responder does not own this request".to_string());
+            }
+            if active_run.token.is_cancelled() {
+                return Err("Agent input request is already cancelled".to_string());
+            }
+            (Arc::clone(&active_run.agent), active_run.events.clone())
+        };
+        // Check the reply before claiming the request so the user can correct
+        // an invalid answer instead of losing the server's question.
+        let content = match action {
+            AgentElicitationAction::Accept => Some(validate_elicitation_content(
+                &pending.request.requested_schema,
+                content.as_ref(),
+            )?),
+            AgentElicitationAction::Decline | AgentElicitationAction::Cancel => None,
+        };
+        if state
+            .pending_elicitations
+            .take(&session_id, &request_id)
+            .await
+            .is_none()
+        {
+            return Err(format!(
+                "Agent input request {request_id} was already answered"
+            ));
+        }
+        if !state
+            .pending_elicitations
+            .reply(&session_id, &request_id, action, content.clone())
+            .await
+        {
+            deliver_elicitation_response(&agent, &session_id, request_id.clone(), action, content)
+                .await;
+        }
+        if let Some(item) = update_live_elicitation_status(
+            &state.live_timelines,
+            &session_id,
+            pending.routing,
+            &request_id,
+            action.status(),
+        )
+        .await
+        {
+            match scope {
+                AgentPermissionResponseScope::Desktop => emit_agent_event(
+                    &state.host.events,
+                    AgentServiceEvent::TimelineItem {
+                        session_id,
+                        run_id: None,
+                        item,
+                    },
+                ),
+                AgentPermissionResponseScope::CallingSurface { .. } => {
+                    run_events.publish(AgentRunEvent::TimelineItem(item)).await;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 fn validate_run_cancellation_scope(
@@ -5744,6 +7427,8 @@ struct AgentPromptRun {
     session_manager: Arc<SessionManager>,
     session_title_lifecycle: Arc<Mutex<()>>,
     live_timelines: LiveTimelines,
+    session_search: SessionSearchIndex,
+    permission_rules: ProjectPermissionRules,
     session_id: String,
     user_message: Message,
     permission_modes: SessionPermissionModes,
@@ -5752,6 +7437,7 @@ struct AgentPromptRun {
     cancel_token: CancellationToken,
     session_title_start: Option<oneshot::Sender<()>>,
     pending_permissions: PendingPermissions,
+    pending_elicitations: PendingElicitations,
     issued_permission_ids: IssuedPermissionIds,
     cancelled_permission_ids: CancelledPermissionIds,
     run_id: String,
@@ -5869,23 +7555,89 @@ async fn deliver_tool_permission(agent: &Agent, request_id: String, permission:
         .await;
 }
 
-async fn deliver_tool_permission_if_auto(
+/// Hand Goose the user's answer to an MCP elicitation. Goose releases the
+/// waiting server call and persists the agent-only response without starting
+/// another model turn, so the returned stream only needs draining.
+async fn deliver_elicitation_response(
     agent: &Agent,
     session_id: &str,
-    permission_modes: &SessionPermissionModes,
-    request_id: &str,
-    cancel_token: &CancellationToken,
-) -> bool {
-    // Keep the policy lock through confirmation delivery. This is the
-    // linearization point for Auto -> Read only: once the restrictive mode
-    // command returns, no permission decision based on an older Auto snapshot
-    // can still be delivered.
-    let modes = permission_modes.lock().await;
-    if modes
-        .get(session_id)
-        .copied()
-        .unwrap_or(GOOSE_PERMISSION_ROUTING_MODE)
-        != GooseMode::Auto
+    request_id: String,
+    action: AgentElicitationAction,
+    content: Option<Value>,
+) {
+    let response = Message::user()
+        .with_content(MessageContent::action_required_elicitation_response(
+            request_id.clone(),
+            content.unwrap_or_else(|| Value::Object(serde_json::Map::new())),
+            action.mcp_action(),
+        ))
+        .agent_only();
+    let session_config = SessionConfig {
+        id: session_id.to_string(),
+        schedule_id: None,
+        max_turns: None,
+        retry_config: None,
+    };
+    match agent.reply(response, session_config, None).await {
+        Ok(mut stream) => while stream.next().await.is_some() {},
+        Err(error) => {
+            log::warn!("Failed to deliver the Agent input response for {request_id}: {error}")
+        }
+    }
+}
+
+async fn cancel_pending_elicitations_for_runs(
+    pending_elicitations: &PendingElicitations,
+    run_ids: &[String],
+    agents_by_run: &HashMap<String, Arc<Agent>>,
+) -> Vec<(String, String)> {
+    let mut cancelled = Vec::new();
+    for ((session_id, request_id), elicitation) in pending_elicitations.take_for_runs(run_ids).await
+    {
+        if pending_elicitations
+            .reply(
+                &session_id,
+                &request_id,
+                AgentElicitationAction::Cancel,
+                None,
+            )
+            .await
+        {
+            cancelled.push((session_id, request_id));
+            continue;
+        }
+        if let Some(agent) = agents_by_run.get(&elicitation.run_id) {
+            deliver_elicitation_response(
+                agent,
+                &session_id,
+                request_id.clone(),
+                AgentElicitationAction::Cancel,
+                None,
+            )
+            .await;
+        }
+        cancelled.push((session_id, request_id));
+    }
+    cancelled
+}
+
+async fn deliver_tool_permission_if_auto(
+    agent: &Agent,
+    session_id: &str,
+    permission_modes: &SessionPermissionModes,
+    request_id: &str,
+    cancel_token: &CancellationToken,
+) -> bool {
+    // Keep the policy lock through confirmation delivery. This is the
+    // linearization point for Auto -> Read only: once the restrictive mode
+    // command returns, no permission decision based on an older Auto snapshot
+    // can still be delivered.
+    let modes = permission_modes.lock().await;
+    if modes
+        .get(session_id)
+        .copied()
+        .unwrap_or(GOOSE_PERMISSION_ROUTING_MODE)
+        != GooseMode::Auto
     {
         return false;
     }
@@ -5935,10 +7687,31 @@ async fn claim_pending_permission_if_auto(
     true
 }
 
+/// The answer to a tool confirmation that needs no classifier or prompt. A
+/// saved project rule decides first; otherwise Allow all approves the call.
+fn unprompted_tool_permission(
+    rule: Option<AgentPermissionRuleDecision>,
+    mode: GooseMode,
+    cancelled: bool,
+) -> Option<Permission> {
+    let permission = match rule {
+        Some(AgentPermissionRuleDecision::Allow) => Permission::AllowOnce,
+        Some(AgentPermissionRuleDecision::Deny) => Permission::DenyOnce,
+        None if mode == GooseMode::Auto => Permission::AllowOnce,
+        None => return None,
+    };
+    Some(if cancelled {
+        Permission::Cancel
+    } else {
+        permission
+    })
+}
+
 struct PermissionAutomationContext<'a> {
     permission_modes: &'a SessionPermissionModes,
     web_tool_state: &'a WebToolState,
     web_permission_context: &'a WebPermissionContext,
+    permission_rules: &'a ProjectPermissionRules,
     working_dir: &'a Path,
     cancel_token: &'a CancellationToken,
 }
@@ -5953,6 +7726,7 @@ async fn automatically_handle_permissions(
         permission_modes,
         web_tool_state,
         web_permission_context,
+        permission_rules,
         working_dir,
         cancel_token,
     } = context;
@@ -5969,19 +7743,36 @@ async fn automatically_handle_permissions(
             _ => None,
         };
         if let Some(request_id) = tool_request_id.as_ref() {
-            if deliver_tool_permission_if_auto(
-                agent,
-                session_id,
-                permission_modes,
-                request_id,
-                cancel_token,
-            )
-            .await
+            // Saved project rules answer before Allow all, any classifier or a
+            // prompt, so a matching deny rule holds in every mode.
+            let rule = permission_rules
+                .decision_for_action(working_dir, action)
+                .await;
+            // Keep the policy lock through delivery, as in
+            // `deliver_tool_permission_if_auto`.
+            let modes = permission_modes.lock().await;
+            let mode = modes
+                .get(session_id)
+                .copied()
+                .unwrap_or(GOOSE_PERMISSION_ROUTING_MODE);
+            if let Some(permission) =
+                unprompted_tool_permission(rule, mode, cancel_token.is_cancelled())
             {
-                let request_id = request_id.clone();
-                handled.insert(request_id);
+                match rule {
+                    Some(AgentPermissionRuleDecision::Allow) => {
+                        log::info!("Saved project rule allowed Agent Mode request {request_id}");
+                    }
+                    Some(AgentPermissionRuleDecision::Deny) => {
+                        log::info!("Saved project rule denied Agent Mode request {request_id}");
+                    }
+                    None => {}
+                }
+                deliver_tool_permission(agent, request_id.clone(), permission).await;
+                drop(modes);
+                handled.insert(request_id.clone());
                 continue;
             }
+            drop(modes);
         }
         let current_mode = selected_permission_mode(permission_modes, session_id)
             .await
@@ -6042,6 +7833,7 @@ async fn automatically_handle_permissions(
         }
         if let Some(request_id) = local_read_request_id(&current_mode, action)
             .or_else(|| local_read_image_request_id(&current_mode, action))
+            .or_else(|| local_search_request_id(&current_mode, action))
             .map(str::to_string)
         {
             let permission = if cancel_token.is_cancelled() {
@@ -6168,6 +7960,8 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
         session_manager,
         session_title_lifecycle,
         live_timelines,
+        session_search,
+        permission_rules,
         session_id,
         user_message,
         permission_modes,
@@ -6176,6 +7970,7 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
         cancel_token,
         session_title_start,
         pending_permissions,
+        pending_elicitations,
         issued_permission_ids,
         cancelled_permission_ids,
         run_id,
@@ -6239,6 +8034,7 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
                     break;
                 }
                 let permission_requests = extracted_permissions.requests;
+                let elicitations = elicitation_requests(&message);
                 let automatically_handled = automatically_handle_permissions(
                     &agent,
                     &session_id,
@@ -6247,6 +8043,7 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
                         permission_modes: &permission_modes,
                         web_tool_state: &web_tool_state,
                         web_permission_context: &web_permission_context,
+                        permission_rules: &permission_rules,
                         working_dir: &working_dir,
                         cancel_token: &cancel_token,
                     },
@@ -6328,11 +8125,47 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
                         }
                     }
                 }
+                let mut duplicate_elicitations = HashSet::new();
+                for item in &mut items {
+                    let Some(request_id) = pending_elicitation_request_id(item) else {
+                        continue;
+                    };
+                    if pending_elicitations
+                        .get(&session_id, &request_id)
+                        .await
+                        .is_some_and(|pending| pending.run_id == run_id)
+                    {
+                        // A repeated action-required message must neither
+                        // re-register nor cancel the request it repeats.
+                        duplicate_elicitations.insert(request_id);
+                        continue;
+                    }
+                    let registered = match elicitations.get(&request_id) {
+                        Some(request) if !cancel_token.is_cancelled() => {
+                            pending_elicitations
+                                .register(&session_id, &run_id, permission_routing, request.clone())
+                                .await
+                        }
+                        _ => false,
+                    };
+                    if !registered {
+                        deliver_elicitation_response(
+                            &agent,
+                            &session_id,
+                            request_id,
+                            AgentElicitationAction::Cancel,
+                            None,
+                        )
+                        .await;
+                        item.status = Some("cancelled".to_string());
+                    }
+                }
                 items.retain(|item| {
                     pending_permission_request_id(item).is_none_or(|request_id| {
                         !newly_auto_handled.contains(&request_id)
                             && !duplicate_permissions.contains(&request_id)
-                    })
+                    }) && pending_elicitation_request_id(item)
+                        .is_none_or(|request_id| !duplicate_elicitations.contains(&request_id))
                 });
                 // Publish a permission card while holding the same claim lock
                 // used by an Allow-all transition. If that transition already
@@ -6366,6 +8199,7 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
                         if let Some(request) = permission_requests.get(&request_id) {
                             record_timeline_item(
                                 &live_timelines,
+                                &session_search,
                                 &session_id,
                                 permission_routing,
                                 item.clone(),
@@ -6380,9 +8214,29 @@ async fn run_agent_prompt(run: AgentPromptRun) -> Result<AgentPromptOutcome, Str
                             continue;
                         }
                     }
+                    if let Some(request_id) = pending_elicitation_request_id(&item) {
+                        if let Some(request) = elicitations.get(&request_id) {
+                            record_timeline_item(
+                                &live_timelines,
+                                &session_search,
+                                &session_id,
+                                permission_routing,
+                                item.clone(),
+                            )
+                            .await;
+                            events
+                                .publish(AgentRunEvent::ElicitationRequested {
+                                    request: request.clone(),
+                                    item,
+                                })
+                                .await;
+                            continue;
+                        }
+                    }
                     record_and_emit_timeline_item(
                         &events,
                         &live_timelines,
+                        &session_search,
                         &session_id,
                         permission_routing,
                         item,
@@ -6545,6 +8399,17 @@ fn pending_permission_request_id(item: &AgentTimelineItem) -> Option<String> {
     None
 }
 
+fn pending_elicitation_request_id(item: &AgentTimelineItem) -> Option<String> {
+    if item.item_type == "permission" && item.status.as_deref() == Some("pending") {
+        return item
+            .id
+            .strip_prefix("elicitation-")
+            .filter(|request_id| !request_id.is_empty())
+            .map(ToString::to_string);
+    }
+    None
+}
+
 fn project_is_trusted(paths: &AgentPathLayout, user_id: &str, project_root: &Path) -> bool {
     match load_agent_config_inner(paths, user_id) {
         Ok(config) => project_trust_status(&config, project_root, true).decision == Some(true),
@@ -6557,6 +8422,25 @@ fn project_is_trusted(paths: &AgentPathLayout, user_id: &str, project_root: &Pat
     }
 }
 
+async fn persist_session_workspace_roots(
+    session_manager: &SessionManager,
+    session_id: &str,
+    roots: &[PathBuf],
+) -> Result<(), String> {
+    let session = session_manager
+        .get_session(session_id, false)
+        .await
+        .map_err(|error| format!("Failed to load Agent task workspace roots: {error}"))?;
+    let mut extension_data = session.extension_data;
+    set_session_workspace_roots(&mut extension_data, roots)?;
+    session_manager
+        .update(session_id)
+        .extension_data(extension_data)
+        .apply()
+        .await
+        .map_err(|error| format!("Failed to persist Agent task workspace roots: {error}"))
+}
+
 fn project_skills_root_is_available(project_root: &Path) -> bool {
     let Ok(canonical) = project_root.canonicalize() else {
         return false;
@@ -6567,6 +8451,7 @@ fn project_skills_root_is_available(project_root: &Path) -> bool {
 struct SkillsDiscoveryScope {
     working_dir: PathBuf,
     blocked_project_root: Option<PathBuf>,
+    secondary_roots: Vec<PathBuf>,
 }
 
 fn skills_discovery_scope(
@@ -6574,11 +8459,26 @@ fn skills_discovery_scope(
     user_id: &str,
     session: &Session,
 ) -> Result<SkillsDiscoveryScope, String> {
+    // Secondary workspace roots contribute skills only under their own trust,
+    // and only while they would still be admitted to the session.
+    let config = load_agent_config_inner(paths, user_id)
+        .map_err(|e| format!("Failed to load Agent config: {e}"))?;
+    let secondary_roots = admitted_session_workspace_roots(
+        &session.extension_data,
+        &session.working_dir,
+        &config.removed_project_roots,
+    )
+    .into_iter()
+    .filter(|root| {
+        project_is_trusted(paths, user_id, root) && project_skills_root_is_available(root)
+    })
+    .collect();
     if project_is_trusted(paths, user_id, &session.working_dir) {
         if project_skills_root_is_available(&session.working_dir) {
             return Ok(SkillsDiscoveryScope {
                 working_dir: session.working_dir.clone(),
                 blocked_project_root: None,
+                secondary_roots,
             });
         }
         log::warn!(
@@ -6596,6 +8496,7 @@ fn skills_discovery_scope(
     Ok(SkillsDiscoveryScope {
         working_dir: root,
         blocked_project_root: Some(session.working_dir.clone()),
+        secondary_roots,
     })
 }
 
@@ -6637,6 +8538,18 @@ fn skills_client_for_working_dir(
 struct TrustAwareSkillsClient {
     inner: SkillsClient,
     blocked_project_root: Option<PathBuf>,
+    /// Skills from trusted secondary workspace roots, consulted in order when
+    /// the primary discovery scope does not have the requested skill.
+    secondary: Vec<SkillsClient>,
+}
+
+fn skill_was_missing(result: &CallToolResult) -> bool {
+    result.is_error == Some(true)
+        && result.content.iter().any(|content| {
+            content.as_text().is_some_and(|text| {
+                text.text.starts_with("Skill '") && text.text.contains(" not found")
+            })
+        })
 }
 
 #[async_trait::async_trait]
@@ -6665,16 +8578,19 @@ impl McpClientTrait for TrustAwareSkillsClient {
             .and_then(Value::as_str)
             .unwrap_or_default()
             .to_string();
-        let result = self
+        let mut result = self
             .inner
-            .call_tool(context, name, arguments, cancel_token)
+            .call_tool(context, name, arguments.clone(), cancel_token.clone())
             .await?;
-        let skill_missing = result.is_error == Some(true)
-            && result.content.iter().any(|content| {
-                content.as_text().is_some_and(|text| {
-                    text.text.starts_with("Skill '") && text.text.contains(" not found")
-                })
-            });
+        for secondary in &self.secondary {
+            if !skill_was_missing(&result) {
+                break;
+            }
+            result = secondary
+                .call_tool(context, name, arguments.clone(), cancel_token.clone())
+                .await?;
+        }
+        let skill_missing = skill_was_missing(&result);
         let Some(project_root) = self
             .blocked_project_root
             .as_ref()
@@ -6693,7 +8609,22 @@ impl McpClientTrait for TrustAwareSkillsClient {
     }
 
     fn get_instructions(&self) -> Option<String> {
-        self.inner.get_instructions()
+        let mut combined = self.inner.get_instructions().unwrap_or_default();
+        // Every scope repeats the header and the user's global skills, so a
+        // secondary root only adds the lines the listing does not have yet.
+        for instructions in self
+            .secondary
+            .iter()
+            .filter_map(SkillsClient::get_instructions)
+        {
+            for line in instructions.lines() {
+                if !line.trim().is_empty() && !combined.lines().any(|existing| existing == line) {
+                    combined.push('\n');
+                    combined.push_str(line);
+                }
+            }
+        }
+        (!combined.trim().is_empty()).then_some(combined)
     }
 
     async fn subscribe(&self) -> mpsc::Receiver<ServerNotification> {
@@ -6711,6 +8642,11 @@ fn prepare_transient_skills_client(
     Ok(TrustAwareSkillsClient {
         inner: skills_client_for_working_dir(agent, session, scope.working_dir)?,
         blocked_project_root: scope.blocked_project_root,
+        secondary: scope
+            .secondary_roots
+            .into_iter()
+            .map(|root| skills_client_for_working_dir(agent, session, root))
+            .collect::<Result<_, _>>()?,
     })
 }
 
@@ -6740,6 +8676,9 @@ struct SessionAgentConfiguration<'a> {
     mode: &'a str,
     primary_model_supports_vision: bool,
     tool_context: &'a SharedAgentToolContext,
+    checkpoint_runs: &'a ActiveCheckpointRuns,
+    mcp_tool_permissions: &'a McpToolPermissions,
+    mcp_sampling: McpSamplingScope,
 }
 
 fn maple_model_config(
@@ -6858,6 +8797,9 @@ async fn configure_session_agent(
         mode,
         primary_model_supports_vision,
         tool_context,
+        checkpoint_runs,
+        mcp_tool_permissions,
+        mcp_sampling,
     } = configuration;
     let session_mcp_keys = session_mcp_extension_keys(session);
     let manager_result = get_or_create_session_agent(
@@ -6870,7 +8812,31 @@ async fn configure_session_agent(
     let agent = manager_result.agent;
     let skills_client =
         prepare_transient_skills_client(skills_scope.paths, skills_scope.user_id, &agent, session)?;
-    let mcp_errors = mcp_connection_errors(manager_result.extension_results, &session_mcp_keys);
+    let maple_servers = session_maple_mcp_servers(
+        skills_scope.paths,
+        skills_scope.user_id,
+        &agent,
+        session,
+        manager_result.agent_created,
+    )
+    .await?;
+    // Maple reports its own attempt for the servers it connects.
+    let goose_mcp_keys = session_mcp_keys
+        .iter()
+        .filter(|key| !maple_servers.keys.contains(*key))
+        .cloned()
+        .collect::<HashSet<_>>();
+    let mut mcp_errors = mcp_connection_errors(manager_result.extension_results, &goose_mcp_keys);
+    mcp_errors.extend(maple_servers.errors);
+    mcp_errors.extend(
+        attach_maple_mcp_servers(
+            &agent,
+            &maple_servers.pending,
+            &session.working_dir,
+            &mcp_sampling,
+        )
+        .await,
+    );
     install_maple_provider(&agent, maple_api_session, session, model, context_limit).await?;
     // All transient MCP operations are hidden behind Maple's one static
     // `external_mcp` tool, which is permanently ask-before in Maple's owned
@@ -6880,6 +8846,9 @@ async fn configure_session_agent(
         .update_goose_mode(GOOSE_PERMISSION_ROUTING_MODE, &session.id)
         .await
         .map_err(|e| format!("Failed to configure Goose permission routing: {e}"))?;
+    mcp_tool_permissions
+        .apply_to_session(&agent, &session.id, &session_mcp_keys)
+        .await;
     let developer = ExtensionConfig::Builtin {
         name: "developer".to_string(),
         description: DEFAULT_EXTENSION_DESCRIPTION.to_string(),
@@ -6900,6 +8869,15 @@ async fn configure_session_agent(
         skills_scope.paths,
         skills_scope.user_id,
     )?);
+    let config = load_agent_config_inner(skills_scope.paths, skills_scope.user_id)
+        .map_err(|e| format!("Failed to load Agent config: {e}"))?;
+    // Persisted roots were admitted when they were stored; re-check them on
+    // every attach so a since-removed project does not come back.
+    let workspace_roots = admitted_session_workspace_roots(
+        &session.extension_data,
+        &session.working_dir,
+        &config.removed_project_roots,
+    );
     let developer_client = MapleDeveloperClient::new(
         developer_context,
         primary_model_supports_vision,
@@ -6908,7 +8886,24 @@ async fn configure_session_agent(
         tool_context.clone(),
     )
     .map_err(|e| format!("Failed to create Maple developer tools: {e}"))?
-    .with_attachment_store(attachment_store);
+    .with_attachment_store(attachment_store)
+    .with_workspace_roots(workspace_roots)
+    .with_checkpoints(
+        account_checkpoint_store(skills_scope.paths, skills_scope.user_id)?,
+        checkpoint_runs.clone(),
+    );
+    let filesystem_boundary = config.filesystem_boundary;
+    let developer_client = if filesystem_boundary.enabled {
+        developer_client.with_filesystem_boundary(
+            filesystem_boundary
+                .extra_directories
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+        )
+    } else {
+        developer_client
+    };
     agent
         .extension_manager
         .add_client(
@@ -7310,9 +9305,48 @@ fn message_to_timeline_items_with_thinking(
             // bounded presentation without exposing base64 metadata.
             MessageContent::Image(_) => None,
         })
+        .flat_map(|item| {
+            let boundary = filesystem_boundary_item(&item);
+            std::iter::once(item).chain(boundary)
+        })
         .collect()
 }
 
+/// Tool results that crossed the filesystem boundary get a companion
+/// permission item so the UI can explain what was blocked and how to allow it,
+/// separately from the tool's own failure row.
+fn filesystem_boundary_item(item: &AgentTimelineItem) -> Option<AgentTimelineItem> {
+    if item.item_type != "tool" {
+        return None;
+    }
+    let violation = item
+        .output
+        .as_ref()?
+        .get("structuredContent")?
+        .get(developer_tools::FILESYSTEM_BOUNDARY_KEY)?;
+    let operation = violation.get("operation")?.as_str()?;
+    let path = violation.get("path")?.as_str()?;
+    Some(AgentTimelineItem {
+        id: format!("boundary-{}", item.id),
+        item_type: "permission".to_string(),
+        role: Some("system".to_string()),
+        title: Some(format!(
+            "Blocked {operation} outside the filesystem boundary"
+        )),
+        text: Some(bounded_timeline_text(
+            &format!(
+                "{path} is outside the folders this task may use. Add its folder to the Agent filesystem boundary to allow it."
+            ),
+            MAX_AGENT_ERROR_CHARS,
+        )),
+        status: Some("boundary_blocked".to_string()),
+        input: Some(violation.clone()),
+        output: None,
+        created_ms: item.created_ms,
+        merge: "replace".to_string(),
+    })
+}
+
 fn system_notification_item(
     base_id: &str,
     index: usize,
@@ -7768,6 +9802,97 @@ fn session_summary(session: &Session) -> AgentSessionSummary {
     }
 }
 
+fn forked_session_title(source_title: &str) -> String {
+    let suffix = " (fork)";
+    let base = source_title.trim();
+    let base = if base.is_empty() {
+        DEFAULT_AGENT_SESSION_TITLE
+    } else {
+        base
+    };
+    let limit = MAX_AGENT_SESSION_TITLE_CHARS - suffix.chars().count();
+    if base.chars().count() <= limit {
+        return format!("{base}{suffix}");
+    }
+    let truncated = base.chars().take(limit - 1).collect::<String>();
+    format!("{}…{suffix}", truncated.trim_end())
+}
+
+/// Select the persisted history a fork of `timeline_item_id` keeps.
+///
+/// Forking from a user prompt keeps everything before that prompt so it can be
+/// retried with different text. Forking from any other row keeps the message
+/// that produced it plus any later tool results its tool requests still need,
+/// so Goose resumes from a well-formed provider history.
+fn fork_conversation_prefix(
+    messages: &[Message],
+    timeline_item_id: &str,
+) -> Result<Vec<Message>, String> {
+    let (index, item) = messages
+        .iter()
+        .enumerate()
+        .find_map(|(index, message)| {
+            message_to_timeline_items(message, false)
+                .into_iter()
+                .find(|item| item.id == timeline_item_id)
+                .map(|item| (index, item))
+        })
+        .ok_or_else(|| {
+            "That timeline item is not part of this Agent task's saved history".to_string()
+        })?;
+    let mut end = if is_user_message_item(&item) {
+        index
+    } else {
+        index + 1
+    };
+
+    let mut unanswered = HashSet::new();
+    for message in &messages[..end] {
+        track_unanswered_tool_requests(message, &mut unanswered);
+    }
+    while !unanswered.is_empty() {
+        let Some(message) = messages.get(end) else {
+            return Err(
+                "Wait for the running tool call to finish before forking from this point"
+                    .to_string(),
+            );
+        };
+        track_unanswered_tool_requests(message, &mut unanswered);
+        end += 1;
+    }
+    Ok(messages[..end].to_vec())
+}
+
+fn fork_complete_conversation(messages: &[Message]) -> Result<Vec<Message>, String> {
+    let mut unanswered = HashSet::new();
+    for message in messages {
+        track_unanswered_tool_requests(message, &mut unanswered);
+    }
+    if !unanswered.is_empty() {
+        return Err(
+            "Wait for the running tool call to finish before forking this task".to_string(),
+        );
+    }
+    Ok(messages.to_vec())
+}
+
+fn track_unanswered_tool_requests(message: &Message, unanswered: &mut HashSet<String>) {
+    for content in &message.content {
+        match content {
+            MessageContent::ToolRequest(request) => {
+                unanswered.insert(request.id.clone());
+            }
+            MessageContent::FrontendToolRequest(request) => {
+                unanswered.insert(request.id.clone());
+            }
+            MessageContent::ToolResponse(response) => {
+                unanswered.remove(&response.id);
+            }
+            _ => {}
+        }
+    }
+}
+
 fn sort_sessions_newest_first(sessions: &mut [AgentSessionSummary]) {
     sessions.sort_by(|a, b| b.updated_ms.cmp(&a.updated_ms));
 }
@@ -7775,20 +9900,30 @@ fn sort_sessions_newest_first(sessions: &mut [AgentSessionSummary]) {
 async fn record_and_emit_timeline_item(
     events: &AgentRunEventPublisher,
     live_timelines: &LiveTimelines,
+    session_search: &SessionSearchIndex,
     session_id: &str,
     routing: AgentPermissionRouting,
     item: AgentTimelineItem,
 ) {
-    record_timeline_item(live_timelines, session_id, routing, item.clone()).await;
+    record_timeline_item(
+        live_timelines,
+        session_search,
+        session_id,
+        routing,
+        item.clone(),
+    )
+    .await;
     events.publish(AgentRunEvent::TimelineItem(item)).await;
 }
 
 async fn record_timeline_item(
     live_timelines: &LiveTimelines,
+    session_search: &SessionSearchIndex,
     session_id: &str,
     routing: AgentPermissionRouting,
     item: AgentTimelineItem,
 ) {
+    let item_id = item.id.clone();
     let mut timelines = live_timelines.lock().await;
     let current = match timelines.remove(session_id) {
         Some(LiveTimelineEntry {
@@ -7815,13 +9950,23 @@ async fn record_timeline_item(
         Some(_) => Vec::new(),
         None => Vec::new(),
     };
+    let merged = merge_timeline_item(current, item);
+    let indexed = merged
+        .iter()
+        .find(|candidate| candidate.id == item_id)
+        .cloned();
     timelines.insert(
         session_id.to_string(),
         LiveTimelineEntry {
             routing,
-            timeline: LiveTimeline::Streaming(merge_timeline_item(current, item)),
+            timeline: LiveTimeline::Streaming(merged),
         },
     );
+    drop(timelines);
+    // Index the merged row so streamed text is searchable as a whole.
+    if let Some(item) = indexed {
+        session_search.record_item(session_id, &item).await;
+    }
 }
 
 /// Goose replaces persisted history during compaction, so any live rows from
@@ -7954,15 +10099,48 @@ async fn update_live_permission_status(
     request_id: &str,
     decision: &str,
 ) -> Option<AgentTimelineItem> {
-    let permission_id = format!("permission-{request_id}");
+    update_live_action_status(
+        live_timelines,
+        session_id,
+        routing,
+        &format!("permission-{request_id}"),
+        decision,
+    )
+    .await
+}
+
+async fn update_live_elicitation_status(
+    live_timelines: &LiveTimelines,
+    session_id: &str,
+    routing: AgentPermissionRouting,
+    request_id: &str,
+    status: &str,
+) -> Option<AgentTimelineItem> {
+    update_live_action_status(
+        live_timelines,
+        session_id,
+        routing,
+        &format!("elicitation-{request_id}"),
+        status,
+    )
+    .await
+}
+
+async fn update_live_action_status(
+    live_timelines: &LiveTimelines,
+    session_id: &str,
+    routing: AgentPermissionRouting,
+    item_id: &str,
+    status: &str,
+) -> Option<AgentTimelineItem> {
     let mut timelines = live_timelines.lock().await;
     let entry = timelines.get_mut(session_id)?;
     if entry.routing != routing {
         return None;
     }
     let items = entry.timeline.items_mut();
-    let item = items.iter_mut().find(|item| item.id == permission_id)?;
-    item.status = Some(decision.to_string());
+    let item = items.iter_mut().find(|item| item.id == item_id)?;
+    item.status = Some(status.to_string());
     item.merge = "replace".to_string();
     Some(item.clone())
 }
@@ -8133,11 +10311,20 @@ fn normalize_mcp_servers(mut servers: Vec<AgentMcpServer>) -> Result<Vec<AgentMc
                 server.name
             ));
         }
+        normalize_mcp_tool_filter(
+            &mut server.allowed_tools,
+            &mut server.blocked_tools,
+            &server.name,
+        )?;
+        if let Some(sampling) = &mut server.sampling {
+            mcp_sampling::normalize_mcp_sampling(sampling, &server.name)?;
+        }
 
         let environment = match &mut server.transport {
             AgentMcpTransport::Stdio {
                 command,
                 environment,
+                working_directory,
             } => {
                 *command = command.trim().to_string();
                 if command.is_empty() {
@@ -8151,22 +10338,42 @@ fn normalize_mcp_servers(mut servers: Vec<AgentMcpServer>) -> Result<Vec<AgentMc
                     ));
                 }
                 validate_mcp_key_values(environment, &server.name, "environment variable", false)?;
+                normalize_mcp_working_directory(working_directory, &server.name)?;
                 environment
             }
             AgentMcpTransport::StreamableHttp {
                 url,
                 environment,
                 headers,
+            }
+            | AgentMcpTransport::Sse {
+                url,
+                environment,
+                headers,
+            } => {
+                normalize_mcp_endpoint(url, environment, headers, &server.name)?;
+                environment
+            }
+            AgentMcpTransport::UnixSocket {
+                socket_path,
+                url,
+                environment,
+                headers,
             } => {
-                *url = url.trim().to_string();
-                if url.is_empty() {
+                if cfg!(not(unix)) {
                     return Err(format!(
-                        "MCP server '{}' requires an endpoint URL",
+                        "MCP server '{}' uses a Unix socket, which this system does not support",
                         server.name
                     ));
                 }
-                validate_mcp_key_values(environment, &server.name, "environment variable", false)?;
-                validate_mcp_key_values(headers, &server.name, "HTTP header", true)?;
+                *socket_path = socket_path.trim().to_string();
+                if !Path::new(socket_path.as_str()).is_absolute() {
+                    return Err(format!(
+                        "MCP server '{}' requires an absolute Unix socket path",
+                        server.name
+                    ));
+                }
+                normalize_mcp_endpoint(url, environment, headers, &server.name)?;
                 environment
             }
         };
@@ -8187,6 +10394,136 @@ fn normalize_mcp_servers(mut servers: Vec<AgentMcpServer>) -> Result<Vec<AgentMc
     Ok(servers)
 }
 
+fn normalize_mcp_endpoint(
+    url: &mut String,
+    environment: &mut [AgentMcpKeyValue],
+    headers: &mut [AgentMcpKeyValue],
+    server_name: &str,
+) -> Result<(), String> {
+    *url = url.trim().to_string();
+    if url.is_empty() {
+        return Err(format!(
+            "MCP server '{server_name}' requires an endpoint URL"
+        ));
+    }
+    validate_mcp_key_values(environment, server_name, "environment variable", false)?;
+    validate_mcp_key_values(headers, server_name, "HTTP header", true)
+}
+
+/// Accept an absolute directory, or `${projectRoot}` optionally followed by a
+/// path that stays inside the project.
+fn normalize_mcp_working_directory(
+    working_directory: &mut Option<String>,
+    server_name: &str,
+) -> Result<(), String> {
+    let Some(directory) = working_directory.as_mut() else {
+        return Ok(());
+    };
+    *directory = directory.trim().to_string();
+    if directory.is_empty() {
+        *working_directory = None;
+        return Ok(());
+    }
+    match directory.strip_prefix(MCP_PROJECT_ROOT_PLACEHOLDER) {
+        Some(rest) => {
+            let inside_project = (rest.is_empty() || rest.starts_with(['/', '\\']))
+                && Path::new(rest.trim_start_matches(['/', '\\']))
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+            if !inside_project {
+                return Err(format!(
+                    "MCP server '{server_name}' working directory must stay inside {MCP_PROJECT_ROOT_PLACEHOLDER}"
+                ));
+            }
+        }
+        None if !Path::new(directory.as_str()).is_absolute() => {
+            return Err(format!(
+                "MCP server '{server_name}' working directory must be an absolute path or start with {MCP_PROJECT_ROOT_PLACEHOLDER}"
+            ));
+        }
+        None => {}
+    }
+    Ok(())
+}
+
+/// Replace a leading `${projectRoot}` in a stdio server's working directory.
+/// Without a project the placeholder cannot be resolved and the server cannot
+/// start.
+fn resolve_mcp_working_directory(
+    server: &AgentMcpServer,
+    project_root: Option<&Path>,
+) -> Result<AgentMcpServer, String> {
+    let mut resolved = server.clone();
+    let AgentMcpTransport::Stdio {
+        working_directory: Some(directory),
+        ..
+    } = &mut resolved.transport
+    else {
+        return Ok(resolved);
+    };
+    let Some(rest) = directory.strip_prefix(MCP_PROJECT_ROOT_PLACEHOLDER) else {
+        return Ok(resolved);
+    };
+    let project_root = project_root.ok_or_else(|| {
+        format!(
+            "MCP server '{}' starts in the project folder, but no project is open",
+            server.name
+        )
+    })?;
+    *directory = project_root
+        .join(rest.trim_start_matches(['/', '\\']))
+        .display()
+        .to_string();
+    Ok(resolved)
+}
+
+/// The directory a stdio server starts in, once any placeholder is resolved.
+fn mcp_working_directory(server: &AgentMcpServer) -> Result<Option<&str>, String> {
+    match &server.transport {
+        AgentMcpTransport::Stdio {
+            working_directory: Some(directory),
+            ..
+        } if directory.starts_with(MCP_PROJECT_ROOT_PLACEHOLDER) => Err(format!(
+            "MCP server '{}' working directory was not resolved for a project",
+            server.name
+        )),
+        AgentMcpTransport::Stdio {
+            working_directory, ..
+        } => Ok(working_directory.as_deref()),
+        _ => Ok(None),
+    }
+}
+
+fn normalize_mcp_tool_filter(
+    allowed: &mut Vec<String>,
+    blocked: &mut Vec<String>,
+    server_name: &str,
+) -> Result<(), String> {
+    for (names, label) in [(&mut *allowed, "allowed"), (&mut *blocked, "blocked")] {
+        let mut seen = HashSet::new();
+        for name in names.iter_mut() {
+            *name = name.trim().to_string();
+            if name.is_empty() {
+                return Err(format!(
+                    "MCP server '{server_name}' has an empty {label} tool name"
+                ));
+            }
+        }
+        names.retain(|name| seen.insert(name.clone()));
+        if names.len() > MAX_MCP_TOOL_FILTER_NAMES {
+            return Err(format!(
+                "MCP server '{server_name}' can list at most {MAX_MCP_TOOL_FILTER_NAMES} {label} tools"
+            ));
+        }
+    }
+    if !allowed.is_empty() && allowed.iter().all(|name| blocked.contains(name)) {
+        return Err(format!(
+            "MCP server '{server_name}' blocks every allowed tool; disable the server instead"
+        ));
+    }
+    Ok(())
+}
+
 fn maple_reserved_extension_key(key: &str) -> bool {
     matches!(key, "developer" | MAPLE_SKILLS_CLIENT_KEY)
 }
@@ -8215,72 +10552,580 @@ fn validate_mcp_key_values(
         } else {
             entry.key.clone()
         };
-        if !keys.insert(comparison_key) {
-            return Err(format!(
-                "MCP server '{server_name}' has a duplicate {label} named {}",
-                entry.key
-            ));
+        if !keys.insert(comparison_key) {
+            return Err(format!(
+                "MCP server '{server_name}' has a duplicate {label} named {}",
+                entry.key
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn mcp_environment(server: &AgentMcpServer) -> &[AgentMcpKeyValue] {
+    match &server.transport {
+        AgentMcpTransport::Stdio { environment, .. }
+        | AgentMcpTransport::StreamableHttp { environment, .. }
+        | AgentMcpTransport::Sse { environment, .. }
+        | AgentMcpTransport::UnixSocket { environment, .. } => environment,
+    }
+}
+
+fn split_mcp_command(command: &str, server_name: &str) -> Result<Vec<String>, String> {
+    goose::utils::split_command_args(command)
+        .map_err(|error| format!("MCP server '{server_name}' has an invalid command: {error}"))
+}
+
+/// Servers Maple connects itself and hands to Goose as a running client:
+/// legacy SSE servers, which Goose cannot connect, servers that may request
+/// sampling, which Goose's client cannot answer, and servers with only a
+/// block list, which Goose's `available_tools` allowlist cannot express.
+fn maple_connects_mcp_server(server: &AgentMcpServer) -> bool {
+    matches!(server.transport, AgentMcpTransport::Sse { .. })
+        || server.sampling.is_some()
+        || (server.allowed_tools.is_empty() && !server.blocked_tools.is_empty())
+}
+
+/// Build the Goose extension for a server. Goose filters extension tools only
+/// through `available_tools`; for servers Maple connects the config only
+/// records them, and [`attach_maple_mcp_server`] filters live.
+fn mcp_server_to_extension(server: &AgentMcpServer) -> Result<ExtensionConfig, String> {
+    let available_tools = server
+        .allowed_tools
+        .iter()
+        .filter(|name| !server.blocked_tools.contains(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    let envs = Envs::new(
+        mcp_environment(server)
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect(),
+    );
+    match &server.transport {
+        AgentMcpTransport::Stdio { command, .. } => {
+            let mut parts = split_mcp_command(command, &server.name)?;
+            if parts.is_empty() {
+                return Err(format!("MCP server '{}' requires a command", server.name));
+            }
+            let cmd = parts.remove(0);
+            Ok(ExtensionConfig::Stdio {
+                name: server.name.clone(),
+                description: server.description.clone(),
+                cmd,
+                args: parts,
+                envs,
+                env_keys: Vec::new(),
+                timeout: Some(server.timeout_seconds),
+                cwd: mcp_working_directory(server)?.map(str::to_string),
+                bundled: Some(false),
+                available_tools,
+            })
+        }
+        AgentMcpTransport::StreamableHttp { url, headers, .. }
+        | AgentMcpTransport::UnixSocket { url, headers, .. } => {
+            Ok(ExtensionConfig::StreamableHttp {
+                name: server.name.clone(),
+                description: server.description.clone(),
+                uri: url.clone(),
+                envs,
+                env_keys: Vec::new(),
+                headers: headers
+                    .iter()
+                    .map(|entry| (entry.key.clone(), entry.value.clone()))
+                    .collect(),
+                timeout: Some(server.timeout_seconds),
+                socket: match &server.transport {
+                    AgentMcpTransport::UnixSocket { socket_path, .. } => Some(socket_path.clone()),
+                    _ => None,
+                },
+                bundled: Some(false),
+                available_tools,
+            })
+        }
+        AgentMcpTransport::Sse { url, .. } => Ok(ExtensionConfig::Sse {
+            name: server.name.clone(),
+            description: server.description.clone(),
+            uri: Some(url.clone()),
+        }),
+    }
+}
+
+/// Build the Goose extension for a session in `project_root`.
+fn mcp_server_to_session_extension(
+    server: &AgentMcpServer,
+    project_root: &Path,
+) -> Result<ExtensionConfig, String> {
+    mcp_server_to_extension(&resolve_mcp_working_directory(server, Some(project_root))?)
+}
+
+/// Connect a server Maple owns and hand Goose the running client under the
+/// server's extension config. The caller persists the session's extension
+/// state afterwards.
+async fn attach_maple_mcp_server(
+    agent: &Agent,
+    server: &AgentMcpServer,
+    project_root: &Path,
+    sampling: &McpSamplingScope,
+) -> Result<(), String> {
+    let server = &resolve_mcp_working_directory(server, Some(project_root))?;
+    let handler = match &server.sampling {
+        Some(policy) => MapleMcpClientHandler::with_sampling(
+            server.name.clone(),
+            policy.clone(),
+            Arc::new(sampling.clone()),
+        ),
+        None => MapleMcpClientHandler::default(),
+    }
+    .with_elicitation(server.name.clone(), Arc::new(sampling.clone()));
+    let connected = match &server.transport {
+        AgentMcpTransport::Sse { url, headers, .. } => LegacySseMcpClient::connect(
+            url,
+            mcp_diagnostics::http_headers(server, headers)?,
+            std::time::Duration::from_secs(server.timeout_seconds),
+            &server.allowed_tools,
+            &server.blocked_tools,
+            handler,
+        )
+        .await
+        .map(|client| Arc::new(client) as Arc<dyn McpClientTrait>),
+        _ => MapleMcpClient::connect(server, handler)
+            .await
+            .map(|client| Arc::new(client) as Arc<dyn McpClientTrait>),
+    };
+    let client = connected
+        .map_err(|error| format!("Failed to connect MCP server '{}': {error}", server.name))?;
+    agent
+        .extension_manager
+        .add_client(
+            goose::config::extensions::name_to_key(&server.name),
+            mcp_server_to_extension(server)?,
+            client,
+            None,
+            None,
+        )
+        .await;
+    Ok(())
+}
+
+/// The configured server Maple connects for a recorded extension. A recorded
+/// SSE extension only matches an SSE server, and any other only a server of
+/// another transport.
+fn configured_maple_mcp_server<'a>(
+    configured: &'a [AgentMcpServer],
+    config: &ExtensionConfig,
+) -> Option<&'a AgentMcpServer> {
+    let key = config.key();
+    let recorded_sse = matches!(config, ExtensionConfig::Sse { .. });
+    configured.iter().find(|server| {
+        maple_connects_mcp_server(server)
+            && matches!(server.transport, AgentMcpTransport::Sse { .. }) == recorded_sse
+            && goose::config::extensions::name_to_key(&server.name) == key
+    })
+}
+
+struct SessionMapleMcpServers {
+    /// Keys of every server in the session that Maple connects.
+    keys: HashSet<String>,
+    /// Configured servers that have no Maple client yet.
+    pending: Vec<AgentMcpServer>,
+    errors: Vec<AgentMcpConnectionError>,
+}
+
+/// Find the servers a restored session still needs Maple to connect. When
+/// Goose has just restored the session's extensions itself, a sampling
+/// server it started is replaced so that sampling requests reach Maple.
+async fn session_maple_mcp_servers(
+    paths: &AgentPathLayout,
+    user_id: &str,
+    agent: &Agent,
+    session: &Session,
+    restored_by_goose: bool,
+) -> Result<SessionMapleMcpServers, String> {
+    let recorded =
+        goose::session::EnabledExtensionsState::from_extension_data(&session.extension_data)
+            .map(|state| state.extensions)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|config| mcp_transport_label(config).is_some())
+            .collect::<Vec<_>>();
+    let mut servers = SessionMapleMcpServers {
+        keys: HashSet::new(),
+        pending: Vec::new(),
+        errors: Vec::new(),
+    };
+    if recorded.is_empty() {
+        return Ok(servers);
+    }
+    let configured = normalize_mcp_servers(
+        load_agent_config_inner(paths, user_id)
+            .map_err(|error| format!("Failed to load MCP servers: {error}"))?
+            .mcp_servers,
+    )?;
+    for config in recorded {
+        let key = config.key();
+        let recorded_sse = matches!(config, ExtensionConfig::Sse { .. });
+        let Some(server) = configured_maple_mcp_server(&configured, &config) else {
+            // Goose always fails to restore SSE servers, so Maple reports them.
+            if recorded_sse {
+                servers.keys.insert(key);
+                servers.errors.push(AgentMcpConnectionError {
+                    name: config.name(),
+                    error: "This SSE server is no longer configured".to_string(),
+                });
+            }
+            continue;
+        };
+        servers.keys.insert(key.clone());
+        if agent.extension_manager.get_mcp_client(&key).await.is_some() {
+            if recorded_sse || !restored_by_goose {
+                continue;
+            }
+            if let Err(error) = agent.extension_manager.remove_extension(&key).await {
+                log::debug!("Replacing Goose's client for MCP server {key}: {error}");
+            }
+        }
+        servers.pending.push(server.clone());
+    }
+    Ok(servers)
+}
+
+/// The configured servers Maple connects for the extensions in `snapshot`,
+/// keyed by extension key, as they stand when a run starts.
+fn frozen_maple_mcp_servers(
+    paths: &AgentPathLayout,
+    user_id: &str,
+    snapshot: &[ExtensionConfig],
+) -> Result<HashMap<String, AgentMcpServer>, String> {
+    if snapshot.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let configured = normalize_mcp_servers(
+        load_agent_config_inner(paths, user_id)
+            .map_err(|error| error.to_string())?
+            .mcp_servers,
+    )?;
+    Ok(snapshot
+        .iter()
+        .filter_map(|config| {
+            configured_maple_mcp_server(&configured, config)
+                .map(|server| (config.key(), server.clone()))
+        })
+        .collect())
+}
+
+/// Replace a session's live client for one user MCP server Maple connects
+/// with a fresh connection built from the server's settings at run start.
+async fn reconnect_maple_mcp_server(
+    session_lifecycle: &Mutex<()>,
+    agent: &Agent,
+    session: &Session,
+    server: &AgentMcpServer,
+    sampling: &McpSamplingScope,
+) -> Result<(), String> {
+    let _session_lifecycle_guard = session_lifecycle.lock().await;
+    let key = goose::config::extensions::name_to_key(&server.name);
+    if let Err(error) = agent.extension_manager.remove_extension(&key).await {
+        log::debug!("Removing disconnected MCP server {key}: {error}");
+    }
+    attach_maple_mcp_server(agent, server, &session.working_dir, sampling).await
+}
+
+/// Replace a session's live client for one user MCP server with a fresh
+/// connection built from its snapshot config. The persisted snapshot already
+/// holds the same config, so a failed attempt leaves it unchanged.
+async fn reconnect_session_mcp_server(
+    paths: &AgentPathLayout,
+    user_id: &str,
+    session_lifecycle: &Mutex<()>,
+    agent: &Arc<Agent>,
+    session: &Session,
+    config: ExtensionConfig,
+) -> Result<(), String> {
+    let _session_lifecycle_guard = session_lifecycle.lock().await;
+    let key = config.key();
+    if let Err(error) = agent.extension_manager.remove_extension(&key).await {
+        log::debug!("Removing disconnected MCP server {key}: {error}");
+    }
+    if matches!(config, ExtensionConfig::Sse { .. }) {
+        return Err("This SSE server is no longer configured".to_string());
+    }
+    let skills_client = prepare_transient_skills_client(paths, user_id, agent, session)?;
+    detach_transient_skills_client(agent).await;
+    let result = agent
+        .add_extension(config, &session.id)
+        .await
+        .map_err(|error| error.to_string());
+    attach_prepared_skills_client(agent, skills_client).await;
+    result
+}
+
+/// Attach every server in `servers` that Maple connects, reporting failures
+/// the way Goose reports its own extensions.
+async fn attach_maple_mcp_servers<'a>(
+    agent: &Agent,
+    servers: impl IntoIterator<Item = &'a AgentMcpServer>,
+    project_root: &Path,
+    sampling: &McpSamplingScope,
+) -> Vec<AgentMcpConnectionError> {
+    let mut errors = Vec::new();
+    for server in servers {
+        if !maple_connects_mcp_server(server) {
+            continue;
+        }
+        if let Err(error) = attach_maple_mcp_server(agent, server, project_root, sampling).await {
+            errors.push(AgentMcpConnectionError {
+                name: server.name.clone(),
+                error,
+            });
+        }
+    }
+    errors
+}
+
+const MCP_SAMPLING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+/// The task an MCP server's sampling and elicitation requests are served for.
+/// It holds the runtime weakly: the server's client lives inside that
+/// runtime's agent.
+#[derive(Clone)]
+struct McpSamplingScope {
+    runtime: Weak<Mutex<Option<AgentRuntime>>>,
+    account_scope: Arc<str>,
+    session_id: String,
+    pending_permissions: PendingPermissions,
+    pending_elicitations: PendingElicitations,
+    live_timelines: LiveTimelines,
+    session_search: SessionSearchIndex,
+    approvals: McpSamplingApprovals,
+}
+
+/// The parts of the session's active run a sampling request needs.
+struct McpSamplingRun {
+    run_id: String,
+    routing: AgentPermissionRouting,
+    events: AgentRunEventPublisher,
+    token: CancellationToken,
+    permission_modes: SessionPermissionModes,
+    session_manager: Arc<SessionManager>,
+    maple_api_session: Arc<MapleApiSession>,
+    model: String,
+    deferred_usage: DeferredRunUsage,
+}
+
+impl McpSamplingScope {
+    fn new(service: &MapleAgentService, account_scope: &str, session_id: &str) -> Self {
+        Self {
+            runtime: Arc::downgrade(&service.inner),
+            account_scope: Arc::from(account_scope),
+            session_id: session_id.to_string(),
+            pending_permissions: Arc::clone(&service.pending_permissions),
+            pending_elicitations: service.pending_elicitations.clone(),
+            live_timelines: Arc::clone(&service.live_timelines),
+            session_search: service.session_search.clone(),
+            approvals: service.mcp_sampling_approvals.clone(),
+        }
+    }
+
+    /// Sampling and elicitation are served only while the task runs, so that
+    /// every request has a run to cancel it and a timeline to ask on.
+    async fn active_run(&self) -> Result<McpSamplingRun, String> {
+        let runtime = self
+            .runtime
+            .upgrade()
+            .ok_or_else(|| "Agent runtime is not running".to_string())?;
+        let runtime = runtime.lock().await;
+        let current = runtime
+            .as_ref()
+            .ok_or_else(|| "Agent runtime is not running".to_string())?;
+        ensure_runtime_account(current, &self.account_scope)?;
+        let (run_id, run) = current
+            .active_runs
+            .iter()
+            .find(|(_, run)| run.session_id == self.session_id)
+            .ok_or_else(|| {
+                "MCP sampling is only available while the task is running".to_string()
+            })?;
+        Ok(McpSamplingRun {
+            run_id: run_id.clone(),
+            routing: run.permission_routing,
+            events: run.events.clone(),
+            token: run.token.clone(),
+            permission_modes: Arc::clone(&current.permission_modes),
+            session_manager: Arc::clone(&current.session_manager),
+            maple_api_session: Arc::clone(&current.maple_api_session),
+            model: current.model.clone(),
+            deferred_usage: run.deferred_usage.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl McpSamplingHost for McpSamplingScope {
+    async fn task_model(&self) -> Result<String, String> {
+        let run = self.active_run().await?;
+        let session = run
+            .session_manager
+            .get_session(&self.session_id, false)
+            .await
+            .map_err(|error| format!("Failed to load Agent task: {error}"))?;
+        Ok(session
+            .model_config
+            .map(|model| model.model_name)
+            .unwrap_or(run.model))
+    }
+
+    async fn approve(&self, approval: McpSamplingApproval) -> Result<(), String> {
+        let run = self.active_run().await?;
+        if selected_permission_mode(&run.permission_modes, &self.session_id).await
+            == GooseMode::Auto
+        {
+            return Ok(());
+        }
+        let request_id = format!("mcp_sampling_{:032x}", rand::random::<u128>());
+        let request = AgentPermissionRequest {
+            request_id: request_id.clone(),
+            tool_name: format!(
+                "{}__sampling",
+                goose::config::extensions::name_to_key(&approval.server)
+            ),
+            arguments: approval.arguments(),
+            prompt: Some(approval.prompt()),
+        };
+        let item = AgentTimelineItem {
+            id: format!("permission-{request_id}"),
+            item_type: "permission".to_string(),
+            role: Some("system".to_string()),
+            title: Some(format!("Sampling request from {}", approval.server)),
+            text: request.prompt.clone(),
+            status: Some("pending".to_string()),
+            input: Some(Value::Object(request.arguments.clone())),
+            output: None,
+            created_ms: unix_ms(),
+            merge: "replace".to_string(),
+        };
+        let decision = self.approvals.register(&request_id).await;
+        let key = (self.session_id.clone(), request_id.clone());
+        self.pending_permissions.lock().await.insert(
+            key.clone(),
+            PendingAgentPermission {
+                run_id: run.run_id,
+                routing: run.routing,
+                request: request.clone(),
+            },
+        );
+        record_timeline_item(
+            &self.live_timelines,
+            &self.session_search,
+            &self.session_id,
+            run.routing,
+            item.clone(),
+        )
+        .await;
+        run.events
+            .publish(AgentRunEvent::PermissionRequested { request, item })
+            .await;
+        let decision = tokio::select! {
+            decision = decision => decision.ok(),
+            _ = run.token.cancelled() => None,
+        };
+        self.approvals.forget(&request_id).await;
+        self.pending_permissions.lock().await.remove(&key);
+        match decision {
+            Some(AgentPermissionDecision::AllowOnce) => Ok(()),
+            Some(AgentPermissionDecision::DenyOnce) => {
+                Err("The user declined the sampling request".to_string())
+            }
+            _ => Err("The sampling request was cancelled".to_string()),
         }
     }
-    Ok(())
-}
-
-fn mcp_environment(server: &AgentMcpServer) -> &[AgentMcpKeyValue] {
-    match &server.transport {
-        AgentMcpTransport::Stdio { environment, .. }
-        | AgentMcpTransport::StreamableHttp { environment, .. } => environment,
-    }
-}
-
-fn split_mcp_command(command: &str, server_name: &str) -> Result<Vec<String>, String> {
-    goose::utils::split_command_args(command)
-        .map_err(|error| format!("MCP server '{server_name}' has an invalid command: {error}"))
-}
 
-fn mcp_server_to_extension(server: &AgentMcpServer) -> Result<ExtensionConfig, String> {
-    let envs = Envs::new(
-        mcp_environment(server)
-            .iter()
-            .map(|entry| (entry.key.clone(), entry.value.clone()))
-            .collect(),
-    );
-    match &server.transport {
-        AgentMcpTransport::Stdio { command, .. } => {
-            let mut parts = split_mcp_command(command, &server.name)?;
-            if parts.is_empty() {
-                return Err(format!("MCP server '{}' requires a command", server.name));
+    async fn complete(&self, completion: McpSamplingCompletion) -> Result<String, String> {
+        let run = self.active_run().await?;
+        let model_config = maple_model_config(&completion.model, None)?
+            .with_max_tokens(Some(
+                i32::try_from(completion.max_tokens).unwrap_or(i32::MAX),
+            ))
+            .with_temperature(completion.temperature);
+        let provider = MapleProvider::new(run.maple_api_session);
+        let cancel = run.token.child_token();
+        let generation = provider::with_run_cancellation(
+            cancel.clone(),
+            goose::session_context::with_session_id(
+                Some(self.session_id.clone()),
+                provider.complete(&model_config, &completion.system, &completion.messages, &[]),
+            ),
+        );
+        tokio::pin!(generation);
+        let (response, usage) = tokio::select! {
+            result = &mut generation => result,
+            _ = tokio::time::sleep(MCP_SAMPLING_TIMEOUT) => {
+                cancel.cancel();
+                // Drain the provider so its credential task settles first.
+                let _ = generation.await;
+                return Err("The sampling request timed out".to_string());
             }
-            let cmd = parts.remove(0);
-            Ok(ExtensionConfig::Stdio {
-                name: server.name.clone(),
-                description: server.description.clone(),
-                cmd,
-                args: parts,
-                envs,
-                env_keys: Vec::new(),
-                timeout: Some(server.timeout_seconds),
-                cwd: None,
-                bundled: Some(false),
-                available_tools: Vec::new(),
-            })
         }
-        AgentMcpTransport::StreamableHttp { url, headers, .. } => {
-            Ok(ExtensionConfig::StreamableHttp {
-                name: server.name.clone(),
-                description: server.description.clone(),
-                uri: url.clone(),
-                envs,
-                env_keys: Vec::new(),
-                headers: headers
-                    .iter()
-                    .map(|entry| (entry.key.clone(), entry.value.clone()))
-                    .collect(),
-                timeout: Some(server.timeout_seconds),
-                socket: None,
-                bundled: Some(false),
-                available_tools: Vec::new(),
-            })
+        .map_err(|error| format!("Sampling failed: {error}"))?;
+        // Sampling counts toward the task's usage once the run settles.
+        if let Some(usage) = run.deferred_usage.defer(usage).await {
+            record_helper_usage(&run.session_manager, &self.session_id, &usage).await;
         }
+        Ok(response.as_concat_text())
+    }
+}
+
+#[async_trait::async_trait]
+impl McpElicitationHost for McpSamplingScope {
+    async fn elicit(
+        &self,
+        server: &str,
+        message: String,
+        requested_schema: Value,
+    ) -> Result<McpElicitationReply, String> {
+        let run = self.active_run().await?;
+        let request_id = format!("mcp_elicitation_{:032x}", rand::random::<u128>());
+        let request = AgentElicitationRequest {
+            request_id: request_id.clone(),
+            message,
+            requested_schema,
+        };
+        let item = AgentTimelineItem {
+            id: format!("elicitation-{request_id}"),
+            item_type: "permission".to_string(),
+            role: Some("system".to_string()),
+            title: Some(format!("Input requested by {server}")),
+            text: Some(request.message.clone()),
+            status: Some("pending".to_string()),
+            input: Some(request.requested_schema.clone()),
+            output: None,
+            created_ms: unix_ms(),
+            merge: "replace".to_string(),
+        };
+        let reply = self
+            .pending_elicitations
+            .register_with_reply(&self.session_id, &run.run_id, run.routing, request.clone())
+            .await
+            .ok_or_else(|| "The input request is already pending".to_string())?;
+        record_timeline_item(
+            &self.live_timelines,
+            &self.session_search,
+            &self.session_id,
+            run.routing,
+            item.clone(),
+        )
+        .await;
+        run.events
+            .publish(AgentRunEvent::ElicitationRequested { request, item })
+            .await;
+        let reply = tokio::select! {
+            reply = reply => reply.ok(),
+            _ = run.token.cancelled() => None,
+        };
+        self.pending_elicitations
+            .forget(&self.session_id, &request_id)
+            .await;
+        Ok(reply.unwrap_or((AgentElicitationAction::Cancel, None)))
     }
 }
 
@@ -8336,6 +11181,11 @@ fn normalize_transient_mcp_servers(
         server.timeout_seconds = server
             .timeout_seconds
             .min(MAX_TRANSIENT_MCP_REQUEST_TIMEOUT_SECONDS);
+        normalize_mcp_tool_filter(
+            &mut server.allowed_tools,
+            &mut server.blocked_tools,
+            &server.name,
+        )?;
 
         let AgentTransientMcpTransport::StreamableHttp { url, headers } = &mut server.transport;
         *url = url.trim().to_string();
@@ -8460,6 +11310,8 @@ async fn install_transient_mcp_router(
                     .into_iter()
                     .map(|entry| (entry.key, entry.value))
                     .collect(),
+                allowed_tools: server.allowed_tools,
+                blocked_tools: server.blocked_tools,
             }
         })
         .collect();
@@ -8579,7 +11431,11 @@ fn format_mcp_connection_errors(errors: &[AgentMcpConnectionError]) -> String {
 fn mcp_transport_label(config: &ExtensionConfig) -> Option<&'static str> {
     match config {
         ExtensionConfig::Stdio { .. } => Some("stdio"),
+        ExtensionConfig::StreamableHttp {
+            socket: Some(_), ..
+        } => Some("unix_socket"),
         ExtensionConfig::StreamableHttp { .. } => Some("streamable_http"),
+        ExtensionConfig::Sse { .. } => Some("sse"),
         _ => None,
     }
 }
@@ -8587,7 +11443,8 @@ fn mcp_transport_label(config: &ExtensionConfig) -> Option<&'static str> {
 fn mcp_extension_description(config: &ExtensionConfig) -> String {
     match config {
         ExtensionConfig::Stdio { description, .. }
-        | ExtensionConfig::StreamableHttp { description, .. } => description.clone(),
+        | ExtensionConfig::StreamableHttp { description, .. }
+        | ExtensionConfig::Sse { description, .. } => description.clone(),
         _ => String::new(),
     }
 }
@@ -8595,6 +11452,7 @@ fn mcp_extension_description(config: &ExtensionConfig) -> String {
 fn session_mcp_servers(
     configured: &[AgentMcpServer],
     session: &Session,
+    health: &HashMap<String, AgentMcpServerHealth>,
 ) -> Vec<AgentSessionMcpServer> {
     let active =
         goose::session::EnabledExtensionsState::from_extension_data(&session.extension_data)
@@ -8613,10 +11471,16 @@ fn session_mcp_servers(
             transport: match server.transport {
                 AgentMcpTransport::Stdio { .. } => "stdio",
                 AgentMcpTransport::StreamableHttp { .. } => "streamable_http",
+                AgentMcpTransport::Sse { .. } => "sse",
+                AgentMcpTransport::UnixSocket { .. } => "unix_socket",
             }
             .to_string(),
             enabled: active_keys.contains(&goose::config::extensions::name_to_key(&server.name)),
             available: true,
+            health: health
+                .get(&goose::config::extensions::name_to_key(&server.name))
+                .cloned()
+                .unwrap_or_default(),
         })
         .collect::<Vec<_>>();
     let configured_keys = configured
@@ -8631,6 +11495,7 @@ fn session_mcp_servers(
             transport: transport.to_string(),
             enabled: true,
             available: false,
+            health: health.get(&config.key()).cloned().unwrap_or_default(),
         })
     }));
     entries
@@ -8708,6 +11573,15 @@ fn account_attachment_store(
         .map_err(|error| error.to_string())
 }
 
+fn account_checkpoint_store(
+    paths: &AgentPathLayout,
+    user_id: &str,
+) -> Result<AgentCheckpointStore, String> {
+    account_local_data_dir_path(paths, user_id)
+        .map(AgentCheckpointStore::new)
+        .map_err(|error| error.to_string())
+}
+
 fn agent_config_dir(paths: &AgentPathLayout, user_id: &str) -> Result<PathBuf, anyhow::Error> {
     let path = account_config_dir_path(paths, user_id)?;
     fs::create_dir_all(&path)?;
@@ -8899,6 +11773,29 @@ fn project_trust_features(project_root: &Path) -> Vec<AgentProjectTrustFeature>
     Vec::new()
 }
 
+const MAX_FILESYSTEM_BOUNDARY_DIRECTORIES: usize = 32;
+
+fn normalize_filesystem_boundary(
+    boundary: AgentFilesystemBoundary,
+) -> Result<AgentFilesystemBoundary, String> {
+    if boundary.extra_directories.len() > MAX_FILESYSTEM_BOUNDARY_DIRECTORIES {
+        return Err(format!(
+            "The filesystem boundary supports at most {MAX_FILESYSTEM_BOUNDARY_DIRECTORIES} extra folders"
+        ));
+    }
+    let mut extra_directories = Vec::with_capacity(boundary.extra_directories.len());
+    for directory in &boundary.extra_directories {
+        let directory = path_string(&normalize_project_root(Path::new(directory.trim()))?);
+        if !extra_directories.contains(&directory) {
+            extra_directories.push(directory);
+        }
+    }
+    Ok(AgentFilesystemBoundary {
+        enabled: boundary.enabled,
+        extra_directories,
+    })
+}
+
 fn apply_project_trust(config: &mut AgentConfig, project_root: &Path, trusted: bool) {
     let path = path_string(project_root);
     if let Some(existing) = config
@@ -9425,6 +12322,7 @@ async fn steer_into_desktop_run(
         record_and_emit_timeline_item(
             &events,
             &state.live_timelines,
+            &state.session_search,
             session_id,
             permission_routing,
             item,
@@ -9618,6 +12516,7 @@ async fn persist_leading_user_messages(
 async fn emit_promoted_queue_items(
     events: &AgentRunEventPublisher,
     live_timelines: &LiveTimelines,
+    session_search: &SessionSearchIndex,
     session_id: &str,
     permission_routing: AgentPermissionRouting,
     queued: &[AgentQueuedMessage],
@@ -9632,6 +12531,7 @@ async fn emit_promoted_queue_items(
             record_and_emit_timeline_item(
                 events,
                 live_timelines,
+                session_search,
                 session_id,
                 permission_routing,
                 user_item.clone(),
@@ -10133,6 +13033,8 @@ mod tests {
                 url: server.url.clone(),
                 headers: Vec::new(),
             },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
         }
     }
 
@@ -10592,6 +13494,7 @@ mod tests {
                     cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                     accepting_queue: Arc::new(AtomicBool::new(true)),
                     steered_unacked: Arc::new(Mutex::new(Vec::new())),
+                    deferred_usage: DeferredRunUsage::new(),
                     task_handle: tokio::spawn(async {}),
                 },
             )]),
@@ -10884,6 +13787,7 @@ mod tests {
                     cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                     accepting_queue: Arc::new(AtomicBool::new(true)),
                     steered_unacked: Arc::new(Mutex::new(Vec::new())),
+                    deferred_usage: DeferredRunUsage::new(),
                     task_handle: tokio::spawn(async {}),
                 },
             )]),
@@ -11269,6 +14173,7 @@ mod tests {
                     cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                     accepting_queue: Arc::new(AtomicBool::new(true)),
                     steered_unacked: Arc::clone(&steered_unacked),
+                    deferred_usage: DeferredRunUsage::new(),
                     task_handle: tokio::spawn(async {}),
                 },
             )]),
@@ -12197,6 +15102,9 @@ mod tests {
                 environment: Vec::new(),
                 headers: Vec::new(),
             },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
         })
         .unwrap();
         let mcp_client = skills_client_for_working_dir(&agent, &session, external_root).unwrap();
@@ -12321,7 +15229,11 @@ mod tests {
                     key: "MCP_TOKEN".to_string(),
                     value: "super-secret-value".to_string(),
                 }],
+                working_directory: None,
             },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
         }
     }
 
@@ -12444,12 +15356,181 @@ mod tests {
                     },
                 ],
             },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
+        };
+        assert!(normalize_mcp_servers(vec![duplicate_headers])
+            .unwrap_err()
+            .contains("duplicate HTTP header"));
+    }
+
+    #[test]
+    fn mcp_tool_filters_are_normalized_into_goose_allowlists() {
+        let mut server = stdio_mcp("github", true);
+        server.allowed_tools = vec![
+            " create_issue ".to_string(),
+            "search_code".to_string(),
+            "create_issue".to_string(),
+        ];
+        server.blocked_tools = vec!["search_code".to_string()];
+        let server = normalize_mcp_servers(vec![server]).unwrap().remove(0);
+        assert_eq!(server.allowed_tools, ["create_issue", "search_code"]);
+        let ExtensionConfig::Stdio {
+            available_tools, ..
+        } = mcp_server_to_extension(&server).unwrap()
+        else {
+            panic!("expected stdio extension");
+        };
+        assert_eq!(available_tools, ["create_issue"]);
+
+        let mut blocks_everything = server.clone();
+        blocks_everything.blocked_tools = blocks_everything.allowed_tools.clone();
+        assert!(normalize_mcp_servers(vec![blocks_everything])
+            .unwrap_err()
+            .contains("blocks every allowed tool"));
+
+        // Goose cannot express a block list on its own, so Maple connects
+        // the server and filters its tools on the live client instead.
+        let mut block_list_only = server;
+        block_list_only.allowed_tools.clear();
+        assert!(maple_connects_mcp_server(&block_list_only));
+        let ExtensionConfig::Stdio {
+            available_tools, ..
+        } = mcp_server_to_extension(&block_list_only).unwrap()
+        else {
+            panic!("expected stdio extension");
+        };
+        assert!(available_tools.is_empty());
+    }
+
+    #[test]
+    fn sse_mcp_servers_share_http_validation_and_are_recorded_for_snapshots() {
+        let server = AgentMcpServer {
+            name: "Legacy Docs".to_string(),
+            description: "Docs over SSE".to_string(),
+            enabled: true,
+            timeout_seconds: 30,
+            transport: AgentMcpTransport::Sse {
+                url: " https://mcp.example.com/sse ".to_string(),
+                environment: Vec::new(),
+                headers: vec![AgentMcpKeyValue {
+                    key: " Authorization ".to_string(),
+                    value: "Bearer token".to_string(),
+                }],
+            },
+            allowed_tools: Vec::new(),
+            blocked_tools: vec!["delete_page".to_string()],
+            sampling: None,
+        };
+        let normalized = normalize_mcp_servers(vec![server.clone()])
+            .unwrap()
+            .remove(0);
+        let AgentMcpTransport::Sse { url, headers, .. } = &normalized.transport else {
+            panic!("expected SSE transport");
+        };
+        assert_eq!(url, "https://mcp.example.com/sse");
+        assert_eq!(headers[0].key, "Authorization");
+
+        // SSE tools are filtered by Maple's client, so a block list on its own
+        // needs no catalog resolution.
+        let extension = mcp_server_to_extension(&normalized).unwrap();
+        assert!(matches!(extension, ExtensionConfig::Sse { .. }));
+        assert_eq!(mcp_transport_label(&extension), Some("sse"));
+
+        let mut duplicate_headers = server;
+        let AgentMcpTransport::Sse { headers, .. } = &mut duplicate_headers.transport else {
+            unreachable!();
         };
+        headers.push(AgentMcpKeyValue {
+            key: "authorization".to_string(),
+            value: "other".to_string(),
+        });
         assert!(normalize_mcp_servers(vec![duplicate_headers])
             .unwrap_err()
             .contains("duplicate HTTP header"));
     }
 
+    #[test]
+    fn stdio_working_directories_resolve_the_project_root_per_task() {
+        let mut server = stdio_mcp("scoped", true);
+        let AgentMcpTransport::Stdio {
+            working_directory, ..
+        } = &mut server.transport
+        else {
+            unreachable!();
+        };
+        *working_directory = Some(" ${projectRoot}/tools ".to_string());
+        let normalized = normalize_mcp_servers(vec![server.clone()])
+            .unwrap()
+            .remove(0);
+        assert!(mcp_server_to_extension(&normalized).is_err());
+        assert!(resolve_mcp_working_directory(&normalized, None).is_err());
+
+        let project = std::env::temp_dir().join("maple-project");
+        let resolved = resolve_mcp_working_directory(&normalized, Some(&project)).unwrap();
+        let ExtensionConfig::Stdio { cwd, .. } = mcp_server_to_extension(&resolved).unwrap() else {
+            panic!("expected stdio extension");
+        };
+        assert_eq!(cwd, Some(project.join("tools").display().to_string()));
+
+        for rejected in [
+            "relative/dir",
+            "${projectRoot}/../outside",
+            "${projectRoot}tools",
+        ] {
+            let AgentMcpTransport::Stdio {
+                working_directory, ..
+            } = &mut server.transport
+            else {
+                unreachable!();
+            };
+            *working_directory = Some(rejected.to_string());
+            assert!(
+                normalize_mcp_servers(vec![server.clone()]).is_err(),
+                "{rejected} should be rejected"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_socket_mcp_servers_connect_through_the_socket() {
+        let mut server = AgentMcpServer {
+            name: "Local Index".to_string(),
+            description: String::new(),
+            enabled: true,
+            timeout_seconds: 30,
+            transport: AgentMcpTransport::UnixSocket {
+                socket_path: " /run/user/1000/index.sock ".to_string(),
+                url: default_unix_socket_mcp_url(),
+                environment: Vec::new(),
+                headers: Vec::new(),
+            },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
+        };
+        let normalized = normalize_mcp_servers(vec![server.clone()])
+            .unwrap()
+            .remove(0);
+        let extension = mcp_server_to_extension(&normalized).unwrap();
+        let ExtensionConfig::StreamableHttp { uri, socket, .. } = &extension else {
+            panic!("expected streamable HTTP extension");
+        };
+        assert_eq!(uri, "http://localhost/mcp");
+        assert_eq!(socket.as_deref(), Some("/run/user/1000/index.sock"));
+        assert_eq!(mcp_transport_label(&extension), Some("unix_socket"));
+
+        let AgentMcpTransport::UnixSocket { socket_path, .. } = &mut server.transport else {
+            unreachable!();
+        };
+        *socket_path = "index.sock".to_string();
+        assert!(normalize_mcp_servers(vec![server])
+            .unwrap_err()
+            .contains("absolute Unix socket path"));
+    }
+
     #[test]
     fn mcp_environment_values_are_independent_between_servers() {
         let first = stdio_mcp("first", true);
@@ -12635,10 +15716,13 @@ mod tests {
             default_project_root: Some(removed.clone()),
             default_model: "test-model".to_string(),
             mcp_servers: vec![stdio_mcp("kept-mcp", true)],
+            mcp_tool_permissions: Vec::new(),
             project_trust: vec![AgentProjectTrust {
                 path: removed.clone(),
                 trusted: true,
             }],
+            permission_rules: Vec::new(),
+            filesystem_boundary: AgentFilesystemBoundary::default(),
             removed_project_roots: Vec::new(),
         };
 
@@ -13121,6 +16205,49 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn saved_deny_rule_blocks_a_tool_in_allow_all_mode() {
+        let rules = ProjectPermissionRules::default();
+        rules
+            .replace(vec![AgentPermissionRule {
+                id: "deny-publish".to_string(),
+                project_root: "/work/project".to_string(),
+                tool: AgentPermissionRuleTool::Shell,
+                pattern: "cargo publish".to_string(),
+                decision: AgentPermissionRuleDecision::Deny,
+                created_ms: 0,
+            }])
+            .await;
+        let MessageContent::ActionRequired(action) = MessageContent::action_required(
+            "request-1",
+            "shell".to_string(),
+            rmcp::object!({ "command": "cargo publish" }),
+            None,
+        ) else {
+            unreachable!();
+        };
+        let rule = rules
+            .decision_for_action(Path::new("/work/project"), &action)
+            .await;
+        assert_eq!(rule, Some(AgentPermissionRuleDecision::Deny));
+        assert_eq!(
+            unprompted_tool_permission(rule, GooseMode::Auto, false),
+            Some(Permission::DenyOnce)
+        );
+        assert_eq!(
+            unprompted_tool_permission(None, GooseMode::Auto, false),
+            Some(Permission::AllowOnce)
+        );
+        assert_eq!(
+            unprompted_tool_permission(None, GooseMode::SmartApprove, false),
+            None
+        );
+        assert_eq!(
+            unprompted_tool_permission(rule, GooseMode::Auto, true),
+            Some(Permission::Cancel)
+        );
+    }
+
     #[test]
     fn agent_mode_accepts_only_one_shot_permission_decisions() {
         assert_eq!(
@@ -13158,10 +16285,14 @@ mod tests {
                 Some(goose::config::permission::PermissionLevel::AskBefore)
             );
         }
-        assert_eq!(
-            manager.get_user_permission("load_skill"),
-            Some(goose::config::permission::PermissionLevel::AlwaysAllow)
-        );
+        // Polling and stopping only touch processes an approved shell call
+        // already started.
+        for tool in ["load_skill", "shell_output", "shell_kill"] {
+            assert_eq!(
+                manager.get_user_permission(tool),
+                Some(goose::config::permission::PermissionLevel::AlwaysAllow)
+            );
+        }
         let _ = fs::remove_dir_all(root);
     }
 
@@ -13247,7 +16378,10 @@ mod tests {
             default_project_root: Some("/tmp/project".to_string()),
             default_model: LEGACY_AGENT_DEFAULT_MODEL.to_string(),
             mcp_servers: Vec::new(),
+            mcp_tool_permissions: Vec::new(),
             project_trust: Vec::new(),
+            permission_rules: Vec::new(),
+            filesystem_boundary: AgentFilesystemBoundary::default(),
             removed_project_roots: Vec::new(),
         };
 
@@ -13263,7 +16397,10 @@ mod tests {
                 default_project_root: None,
                 default_model: model.to_string(),
                 mcp_servers: Vec::new(),
+                mcp_tool_permissions: Vec::new(),
                 project_trust: Vec::new(),
+                permission_rules: Vec::new(),
+                filesystem_boundary: AgentFilesystemBoundary::default(),
                 removed_project_roots: Vec::new(),
             };
 
@@ -13677,6 +16814,7 @@ mod tests {
         .unwrap();
         record_timeline_item(
             &live_timelines,
+            &SessionSearchIndex::default(),
             session_id,
             AgentPermissionRouting::Desktop,
             next_user,
@@ -13711,6 +16849,21 @@ mod tests {
             )
     }
 
+    #[tokio::test]
+    async fn helper_usage_waits_for_the_run_to_settle() {
+        let deferred = DeferredRunUsage::new();
+        let usage = |model: &str| ProviderUsage::new(model.to_string(), Usage::default());
+        assert!(deferred.defer(usage("during")).await.is_none());
+
+        let settled = deferred.settle().await;
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].model, "during");
+        // Usage that arrives after the run settled is recorded by its caller.
+        let late = deferred.defer(usage("late")).await.unwrap();
+        assert_eq!(late.model, "late");
+        assert!(deferred.settle().await.is_empty());
+    }
+
     fn with_usage(mut message: Message) -> Message {
         message.metadata.usage = Some(Box::default());
         message
@@ -13739,6 +16892,88 @@ mod tests {
         )
     }
 
+    #[test]
+    fn fork_prefix_retries_user_prompts_and_keeps_tool_pairs_whole() {
+        let history = vec![
+            Message::user().with_id("prompt-1").with_text("List files"),
+            assistant_tool_message("assistant-1", "tool-1", "Checking", "sig"),
+            tool_response_message("response-1", "tool-1"),
+            Message::assistant().with_id("answer-1").with_text("Done"),
+            Message::user()
+                .with_id("prompt-2")
+                .with_text("Now delete them"),
+            Message::assistant()
+                .with_id("answer-2")
+                .with_text("Deleted"),
+        ];
+        let ids = |messages: Vec<Message>| {
+            messages
+                .into_iter()
+                .map(|message| message.id.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ids(fork_conversation_prefix(&history, "prompt-2-text").unwrap()),
+            ["prompt-1", "assistant-1", "response-1", "answer-1"]
+        );
+        assert!(fork_conversation_prefix(&history, "prompt-1-text")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            ids(fork_conversation_prefix(&history, "tool-1").unwrap()),
+            ["prompt-1", "assistant-1", "response-1"]
+        );
+        assert_eq!(
+            ids(fork_conversation_prefix(&history, "answer-2-text").unwrap()),
+            [
+                "prompt-1",
+                "assistant-1",
+                "response-1",
+                "answer-1",
+                "prompt-2",
+                "answer-2"
+            ]
+        );
+        assert_eq!(
+            fork_conversation_prefix(&history, "missing").unwrap_err(),
+            "That timeline item is not part of this Agent task's saved history"
+        );
+        assert_eq!(
+            fork_conversation_prefix(&history[..2], "assistant-1-thinking").unwrap_err(),
+            "Wait for the running tool call to finish before forking from this point"
+        );
+    }
+
+    #[test]
+    fn complete_fork_copies_history_only_between_tool_calls() {
+        let history = vec![
+            Message::user().with_id("prompt-1").with_text("List files"),
+            assistant_tool_message("assistant-1", "tool-1", "Checking", "sig"),
+            tool_response_message("response-1", "tool-1"),
+            Message::assistant().with_id("answer-1").with_text("Done"),
+        ];
+
+        assert_eq!(fork_complete_conversation(&history).unwrap().len(), 4);
+        assert!(fork_complete_conversation(&[]).unwrap().is_empty());
+        assert_eq!(
+            fork_complete_conversation(&history[..2]).unwrap_err(),
+            "Wait for the running tool call to finish before forking this task"
+        );
+    }
+
+    #[test]
+    fn forked_session_titles_stay_within_the_title_limit() {
+        assert_eq!(forked_session_title("Fix tests"), "Fix tests (fork)");
+        assert_eq!(
+            forked_session_title("  "),
+            format!("{DEFAULT_AGENT_SESSION_TITLE} (fork)")
+        );
+        let long = forked_session_title(&"a".repeat(200));
+        assert_eq!(long.chars().count(), MAX_AGENT_SESSION_TITLE_CHARS);
+        assert!(long.ends_with("… (fork)"));
+    }
+
     #[test]
     fn load_skill_timeline_card_uses_the_selected_skill_name() {
         let arguments = json!({"name": "release-maple"})
@@ -14337,6 +17572,43 @@ mod tests {
         assert!(!output.to_string().contains("provider-private-tool-state"));
     }
 
+    #[test]
+    fn filesystem_boundary_crossings_add_a_blocked_permission_item() {
+        let mut blocked =
+            rmcp::model::CallToolResult::error(vec![rmcp::model::ContentBlock::text(
+                "Error: /etc/hosts is outside this task's boundary",
+            )]);
+        blocked.structured_content = Some(json!({
+            developer_tools::FILESYSTEM_BOUNDARY_KEY: {
+                "operation": "write",
+                "path": "/etc/hosts",
+                "resolvedPath": "/etc/hosts",
+                "allowedRoots": ["/work/project"],
+            }
+        }));
+        let message = Message::user().with_tool_response("write-call", Ok(blocked));
+
+        let items = message_to_timeline_items(&message, false);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].item_type, "tool");
+        assert_eq!(items[0].status.as_deref(), Some("failed"));
+        let boundary = &items[1];
+        assert_eq!(boundary.id, "boundary-write-call");
+        assert_eq!(boundary.item_type, "permission");
+        assert_eq!(boundary.status.as_deref(), Some("boundary_blocked"));
+        assert_eq!(pending_permission_request_id(boundary), None);
+        assert!(boundary.text.as_deref().unwrap().contains("/etc/hosts"));
+
+        let ordinary = Message::user().with_tool_response(
+            "read-call",
+            Ok(rmcp::model::CallToolResult::success(vec![
+                rmcp::model::ContentBlock::text("contents"),
+            ])),
+        );
+        assert_eq!(message_to_timeline_items(&ordinary, false).len(), 1);
+    }
+
     #[test]
     fn hidden_usage_boundary_resets_visible_inference_state() {
         let first = "First visible thought.";
@@ -14602,6 +17874,7 @@ mod tests {
         for item in message_to_timeline_items(&live_response, true) {
             record_timeline_item(
                 &live_timelines,
+                &SessionSearchIndex::default(),
                 session_id,
                 AgentPermissionRouting::Desktop,
                 item,
@@ -16403,6 +19676,8 @@ mod tests {
                 session_manager: Arc::clone(&session_manager),
                 session_title_lifecycle,
                 live_timelines: Arc::new(Mutex::new(HashMap::new())),
+                session_search: SessionSearchIndex::default(),
+                permission_rules: ProjectPermissionRules::default(),
                 session_id: session.id.clone(),
                 user_message: Message::user().with_text(prompt).with_generated_id(),
                 permission_modes: Arc::new(Mutex::new(HashMap::new())),
@@ -16411,6 +19686,7 @@ mod tests {
                 cancel_token: CancellationToken::new(),
                 session_title_start: Some(title_start),
                 pending_permissions: Arc::new(Mutex::new(HashMap::new())),
+                pending_elicitations: PendingElicitations::default(),
                 issued_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                 cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                 run_id: "fast-reply-run".to_string(),
@@ -16529,6 +19805,8 @@ mod tests {
             session_manager: Arc::clone(&session_manager),
             session_title_lifecycle: Arc::clone(&session_title_lifecycle),
             live_timelines: Arc::new(Mutex::new(HashMap::new())),
+            session_search: SessionSearchIndex::default(),
+            permission_rules: ProjectPermissionRules::default(),
             session_id: session.id.clone(),
             user_message: Message::user().with_text(prompt).with_generated_id(),
             permission_modes: Arc::new(Mutex::new(HashMap::new())),
@@ -16537,6 +19815,7 @@ mod tests {
             cancel_token: CancellationToken::new(),
             session_title_start: None,
             pending_permissions: Arc::new(Mutex::new(HashMap::new())),
+            pending_elicitations: PendingElicitations::default(),
             issued_permission_ids: Arc::new(Mutex::new(HashSet::new())),
             cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
             run_id: "rename-during-run-summary".to_string(),
@@ -16670,6 +19949,8 @@ mod tests {
             session_manager: Arc::clone(&session_manager),
             session_title_lifecycle,
             live_timelines: Arc::new(Mutex::new(HashMap::new())),
+            session_search: SessionSearchIndex::default(),
+            permission_rules: ProjectPermissionRules::default(),
             session_id: session.id.clone(),
             user_message: Message::user().with_text(prompt).with_generated_id(),
             permission_modes: Arc::new(Mutex::new(HashMap::new())),
@@ -16678,6 +19959,7 @@ mod tests {
             cancel_token: CancellationToken::new(),
             session_title_start: None,
             pending_permissions: Arc::new(Mutex::new(HashMap::new())),
+            pending_elicitations: PendingElicitations::default(),
             issued_permission_ids: Arc::new(Mutex::new(HashSet::new())),
             cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
             run_id: "reply-poll-session-isolation".to_string(),
@@ -16766,6 +20048,7 @@ mod tests {
                     cancelled_permission_ids: Arc::new(Mutex::new(HashSet::new())),
                     accepting_queue: Arc::new(AtomicBool::new(true)),
                     steered_unacked: Arc::new(Mutex::new(Vec::new())),
+                    deferred_usage: DeferredRunUsage::new(),
                     task_handle: tokio::spawn(async {}),
                 },
             )]),
@@ -8,7 +8,7 @@ use std::path::{Path, PathBuf};
 pub(super) const MAX_AGENT_IMAGE_BYTES: usize = 10 * 1024 * 1024;
 pub(super) const MAX_AGENT_IMAGES_PER_MESSAGE: usize = 10;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub(crate) struct AgentImageUpload {
     pub name: String,
@@ -131,6 +131,107 @@ impl AgentAttachmentStore {
         ))
     }
 
+    /// Copy the named attachments into another task so copied history keeps
+    /// resolving its `maple-attachment://` sources. Attachments that no longer
+    /// exist in the source task are skipped, matching how their original
+    /// messages already render.
+    pub(super) fn copy_session(
+        &self,
+        from_session_id: &str,
+        to_session_id: &str,
+        attachment_ids: &[String],
+    ) -> Result<(), String> {
+        if attachment_ids.is_empty() {
+            return Ok(());
+        }
+        let from_dir = self.session_dir(from_session_id)?;
+        let to_dir = self.session_dir(to_session_id)?;
+        if let Some(account_dir) = self.root.parent() {
+            create_owner_only_dir(account_dir)?;
+        }
+        create_owner_only_dir(&self.root)?;
+        create_owner_only_dir(&to_dir)?;
+
+        let mut created_paths = Vec::with_capacity(attachment_ids.len());
+        for attachment_id in attachment_ids {
+            validate_attachment_id(attachment_id)?;
+            let target = to_dir.join(attachment_id);
+            let copied = match fs::symlink_metadata(from_dir.join(attachment_id)) {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                _ if target.exists() => Ok(None),
+                _ => self
+                    .read(from_session_id, attachment_id)
+                    .and_then(|bytes| write_new_attachment(&target, &bytes).map(Some)),
+            };
+            match copied {
+                Ok(Some(path)) => created_paths.push(path),
+                Ok(None) => {}
+                Err(error) => {
+                    for path in created_paths {
+                        let _ = fs::remove_file(path);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the named attachments for a task archive. Attachments that no
+    /// longer exist are skipped, matching [`Self::copy_session`].
+    pub(super) fn export_session(
+        &self,
+        session_id: &str,
+        attachments: &[AgentImageAttachment],
+    ) -> Result<Vec<(AgentImageAttachment, Vec<u8>)>, String> {
+        let session_dir = self.session_dir(session_id)?;
+        let mut exported = Vec::with_capacity(attachments.len());
+        for attachment in attachments {
+            validate_attachment_id(&attachment.id)?;
+            match fs::symlink_metadata(session_dir.join(&attachment.id)) {
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+                _ => {}
+            }
+            let bytes = self.read(session_id, &attachment.id)?;
+            exported.push((attachment.clone(), bytes));
+        }
+        Ok(exported)
+    }
+
+    /// Store attachments restored from an untrusted task archive under their
+    /// original IDs so the imported history keeps resolving its sources.
+    pub(super) fn import_session(
+        &self,
+        session_id: &str,
+        attachments: &[(AgentImageAttachment, Vec<u8>)],
+    ) -> Result<(), String> {
+        if attachments.is_empty() {
+            return Ok(());
+        }
+        let session_dir = self.session_dir(session_id)?;
+        if let Some(account_dir) = self.root.parent() {
+            create_owner_only_dir(account_dir)?;
+        }
+        create_owner_only_dir(&self.root)?;
+        create_owner_only_dir(&session_dir)?;
+
+        let mut created_paths = Vec::with_capacity(attachments.len());
+        for (attachment, bytes) in attachments {
+            let imported = validate_imported_attachment(attachment, bytes)
+                .and_then(|_| write_new_attachment(&session_dir.join(&attachment.id), bytes));
+            match imported {
+                Ok(path) => created_paths.push(path),
+                Err(error) => {
+                    for path in created_paths {
+                        let _ = fs::remove_file(path);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(super) fn delete_session(&self, session_id: &str) -> Result<(), String> {
         let path = self.session_dir(session_id)?;
         remove_exact_path(&path)
@@ -163,21 +264,7 @@ impl AgentAttachmentStore {
         }
 
         let id = format!("{:032x}", rand::random::<u128>());
-        let path = session_dir.join(&id);
-        let mut options = OpenOptions::new();
-        options.write(true).create_new(true);
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::OpenOptionsExt;
-            options.mode(0o600);
-        }
-        let mut file = options
-            .open(&path)
-            .map_err(|error| format!("Failed to store Agent image attachment: {error}"))?;
-        if let Err(error) = file.write_all(&bytes).and_then(|_| file.sync_all()) {
-            let _ = fs::remove_file(&path);
-            return Err(format!("Failed to store Agent image attachment: {error}"));
-        }
+        let path = write_new_attachment(&session_dir.join(&id), &bytes)?;
 
         let attachment = AgentImageAttachment {
             source: format!("maple-attachment://{id}"),
@@ -210,6 +297,41 @@ pub(super) fn attachment_id_from_source(source: &str) -> Option<&str> {
         .and_then(|id| (!id.is_empty() && !id.contains(['/', '\\', '?', '#'])).then_some(id))
 }
 
+fn validate_imported_attachment(
+    attachment: &AgentImageAttachment,
+    bytes: &[u8],
+) -> Result<(), String> {
+    validate_attachment_id(&attachment.id)?;
+    if attachment.source != format!("maple-attachment://{}", attachment.id) {
+        return Err("Agent image attachment source does not match its ID".to_string());
+    }
+    if bytes.len() > MAX_AGENT_IMAGE_BYTES {
+        return Err("Agent image attachment exceeds the 10MB limit".to_string());
+    }
+    if supported_image_mime(bytes)? != normalize_mime(&attachment.mime_type) {
+        return Err("Image content does not match its declared type".to_string());
+    }
+    Ok(())
+}
+
+fn write_new_attachment(path: &Path, bytes: &[u8]) -> Result<PathBuf, String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|error| format!("Failed to store Agent image attachment: {error}"))?;
+    if let Err(error) = file.write_all(bytes).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(path);
+        return Err(format!("Failed to store Agent image attachment: {error}"));
+    }
+    Ok(path.to_path_buf())
+}
+
 fn parse_data_url(data_url: &str) -> Result<(&str, &str), String> {
     let (header, data) = data_url
         .split_once(',')
@@ -337,6 +459,91 @@ mod tests {
         assert!(store.read("session-a", &prepared[0].attachment.id).is_err());
     }
 
+    #[test]
+    fn copies_referenced_images_into_another_session() {
+        let temp = tempdir().unwrap();
+        let store = AgentAttachmentStore::new(temp.path().to_path_buf());
+        let prepared = store
+            .store_uploads(
+                "session-a",
+                &[AgentImageUpload {
+                    name: "screen.png".to_string(),
+                    data_url: format!("data:image/png;base64,{PNG_1X1}"),
+                }],
+            )
+            .unwrap();
+        let id = prepared[0].attachment.id.clone();
+        let missing = "0".repeat(32);
+
+        store
+            .copy_session("session-a", "session-b", &[id.clone(), missing.clone()])
+            .unwrap();
+
+        assert_eq!(
+            store.read("session-b", &id).unwrap(),
+            store.read("session-a", &id).unwrap()
+        );
+        assert!(store.read("session-b", &missing).is_err());
+        store.delete_session("session-a").unwrap();
+        assert!(store.read("session-b", &id).is_ok());
+        assert_eq!(
+            store
+                .copy_session("session-a", "session-b", &["../escape".to_string()])
+                .unwrap_err(),
+            "Invalid Agent image attachment ID"
+        );
+    }
+
+    #[test]
+    fn exports_and_imports_images_under_their_original_ids() {
+        let temp = tempdir().unwrap();
+        let store = AgentAttachmentStore::new(temp.path().to_path_buf());
+        let prepared = store
+            .store_uploads(
+                "session-a",
+                &[AgentImageUpload {
+                    name: "screen.png".to_string(),
+                    data_url: format!("data:image/png;base64,{PNG_1X1}"),
+                }],
+            )
+            .unwrap();
+        let attachment = prepared[0].attachment.clone();
+        let missing = AgentImageAttachment {
+            id: "0".repeat(32),
+            source: format!("maple-attachment://{}", "0".repeat(32)),
+            ..attachment.clone()
+        };
+
+        let exported = store
+            .export_session("session-a", &[attachment.clone(), missing])
+            .unwrap();
+        assert_eq!(exported.len(), 1);
+
+        store.import_session("session-b", &exported).unwrap();
+        assert_eq!(
+            store.read("session-b", &attachment.id).unwrap(),
+            exported[0].1
+        );
+
+        let relabeled = AgentImageAttachment {
+            mime_type: "image/webp".to_string(),
+            ..attachment.clone()
+        };
+        assert_eq!(
+            store
+                .import_session("session-c", &[(relabeled, exported[0].1.clone())])
+                .unwrap_err(),
+            "Image content does not match its declared type"
+        );
+        let redirected = AgentImageAttachment {
+            source: "https://example.com/screen.png".to_string(),
+            ..attachment
+        };
+        assert!(store
+            .import_session("session-c", &[(redirected, exported[0].1.clone())])
+            .is_err());
+    }
+
     #[test]
     fn rejects_mime_mismatches_and_unsupported_images() {
         let temp = tempdir().unwrap();
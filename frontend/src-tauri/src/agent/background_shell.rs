@@ -0,0 +1,460 @@
+//! Shell commands that keep running after the `shell` tool returns.
+//!
+//! The registry lives on the session's `SharedAgentToolContext`, and every
+//! supervisor also watches the context's revocation token, so revoking the
+//! context or shutting the runtime down stops each command's whole process
+//! group. Output is retained only until it is read, up to a fixed bound.
+
+use rmcp::model::{Tool, ToolAnnotations};
+use rmcp::object;
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+pub(crate) const SHELL_OUTPUT_TOOL_NAME: &str = "shell_output";
+pub(crate) const SHELL_KILL_TOOL_NAME: &str = "shell_kill";
+/// Argument Maple adds to the delegated `shell` tool.
+pub(super) const BACKGROUND_ARGUMENT: &str = "background";
+const MAX_BACKGROUND_SHELLS: usize = 8;
+const MAX_RETAINED_OUTPUT_BYTES: usize = 1024 * 1024;
+/// Matches the foreground shell's output bound.
+const MAX_OUTPUT_READ_BYTES: usize = 50_000;
+const MAX_OUTPUT_WAIT_SECS: u64 = 30;
+const KILL_WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct ShellOutputParams {
+    id: String,
+    wait_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ShellKillParams {
+    id: String,
+}
+
+pub(crate) fn shell_output_tool() -> Tool {
+    Tool::new(
+        SHELL_OUTPUT_TOOL_NAME.to_string(),
+        format!(
+            "Read new output from a shell command started with background set to true. Returns the interleaved stdout and stderr produced since the last read (at most {}KB per call) and whether the process is still running. Set waitSecs to wait for new output or exit.",
+            MAX_OUTPUT_READ_BYTES / 1000
+        ),
+        object!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "Background process id returned by shell"
+                },
+                "waitSecs": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": MAX_OUTPUT_WAIT_SECS,
+                    "default": 0,
+                    "description": "Seconds to wait for new output or exit before returning"
+                }
+            },
+            "required": ["id"]
+        }),
+    )
+    .annotate(ToolAnnotations::from_raw(
+        Some("Shell Output".to_string()),
+        Some(true),
+        Some(false),
+        Some(false),
+        Some(false),
+    ))
+}
+
+pub(crate) fn shell_kill_tool() -> Tool {
+    Tool::new(
+        SHELL_KILL_TOOL_NAME.to_string(),
+        "Stop a background shell command and every process it started, returning any output that was not read yet."
+            .to_string(),
+        object!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "Background process id returned by shell"
+                }
+            },
+            "required": ["id"]
+        }),
+    )
+    .annotate(ToolAnnotations::from_raw(
+        Some("Stop Shell Process".to_string()),
+        Some(false),
+        Some(true),
+        Some(true),
+        Some(false),
+    ))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BackgroundShellExit {
+    Exited(Option<i32>),
+    Stopped,
+}
+
+/// Background shell commands owned by one session's tool context.
+#[derive(Clone, Default)]
+pub(crate) struct BackgroundShells {
+    state: Arc<StdMutex<BackgroundShellsState>>,
+}
+
+#[derive(Default)]
+struct BackgroundShellsState {
+    next_id: u64,
+    processes: BTreeMap<String, Arc<BackgroundShell>>,
+}
+
+impl BackgroundShells {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BackgroundShellsState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Reserve an id before the command is spawned so a full registry never
+    /// starts a process it cannot track.
+    pub(super) fn register(&self, command: &str) -> Result<Arc<BackgroundShell>, String> {
+        let mut state = self.lock();
+        if state.processes.len() >= MAX_BACKGROUND_SHELLS {
+            let tracked = state
+                .processes
+                .values()
+                .map(|shell| format!("{}: {}", shell.id, shell.command))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(format!(
+                "At most {MAX_BACKGROUND_SHELLS} background processes can be tracked per task. Collect a finished one with shell_output or stop one with shell_kill first.\n\n{tracked}"
+            ));
+        }
+        state.next_id += 1;
+        let id = format!("shell-{}", state.next_id);
+        let shell = Arc::new(BackgroundShell::new(id.clone(), command));
+        state.processes.insert(id, Arc::clone(&shell));
+        Ok(shell)
+    }
+
+    pub(super) fn remove(&self, id: &str) {
+        self.lock().processes.remove(id);
+    }
+
+    /// Stop and forget every process. Called when the owning context is
+    /// revoked.
+    pub(crate) fn clear(&self) {
+        let processes = std::mem::take(&mut self.lock().processes);
+        for shell in processes.into_values() {
+            shell.kill.cancel();
+        }
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<BackgroundShell>, String> {
+        self.lock()
+            .processes
+            .get(id.trim())
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "No background process {id}. It may have finished and been collected already."
+                )
+            })
+    }
+
+    pub(super) async fn read(
+        &self,
+        params: ShellOutputParams,
+        cancel_token: CancellationToken,
+    ) -> Result<String, String> {
+        let shell = self.get(&params.id)?;
+        let wait = Duration::from_secs(params.wait_secs.unwrap_or(0).min(MAX_OUTPUT_WAIT_SECS));
+        shell
+            .wait_until(wait, &cancel_token, |output| {
+                !output.unread.is_empty() || output.exit.is_some()
+            })
+            .await;
+        let read = shell.take_output();
+        if read.exit.is_some() && read.remaining == 0 {
+            self.remove(&shell.id);
+        }
+        Ok(read.render(&shell, false))
+    }
+
+    pub(super) async fn kill(
+        &self,
+        params: ShellKillParams,
+        cancel_token: CancellationToken,
+    ) -> Result<String, String> {
+        let shell = self.get(&params.id)?;
+        shell.kill.cancel();
+        shell
+            .wait_until(KILL_WAIT, &cancel_token, |output| output.exit.is_some())
+            .await;
+        // The supervisor keeps its own handle and finishes the kill even if
+        // the wait above gave up first.
+        self.remove(&shell.id);
+        Ok(shell.take_output().render(&shell, true))
+    }
+
+    #[cfg(test)]
+    pub(super) fn len(&self) -> usize {
+        self.lock().processes.len()
+    }
+}
+
+pub(super) struct BackgroundShell {
+    id: String,
+    command: String,
+    started: Instant,
+    kill: CancellationToken,
+    output: StdMutex<BackgroundShellOutput>,
+    updates: watch::Sender<()>,
+}
+
+#[derive(Default)]
+struct BackgroundShellOutput {
+    unread: VecDeque<u8>,
+    dropped: u64,
+    collection_error: Option<String>,
+    exit: Option<BackgroundShellExit>,
+}
+
+impl BackgroundShell {
+    fn new(id: String, command: &str) -> Self {
+        Self {
+            id,
+            command: command.trim().to_string(),
+            started: Instant::now(),
+            kill: CancellationToken::new(),
+            output: StdMutex::new(BackgroundShellOutput::default()),
+            updates: watch::channel(()).0,
+        }
+    }
+
+    pub(super) fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub(super) fn kill_token(&self) -> &CancellationToken {
+        &self.kill
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut BackgroundShellOutput)) {
+        apply(
+            &mut self
+                .output
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        );
+        self.updates.send_replace(());
+    }
+
+    pub(super) fn record_output(&self, bytes: &[u8]) {
+        self.update(|output| {
+            output.unread.extend(bytes);
+            let excess = output
+                .unread
+                .len()
+                .saturating_sub(MAX_RETAINED_OUTPUT_BYTES);
+            if excess > 0 {
+                output.unread.drain(..excess);
+                output.dropped += excess as u64;
+            }
+        });
+    }
+
+    pub(super) fn record_error(&self, error: String) {
+        self.update(|output| output.collection_error = Some(error));
+    }
+
+    pub(super) fn finish(&self, exit: BackgroundShellExit) {
+        self.update(|output| output.exit = Some(exit));
+    }
+
+    async fn wait_until(
+        &self,
+        wait: Duration,
+        cancel_token: &CancellationToken,
+        ready: impl Fn(&BackgroundShellOutput) -> bool,
+    ) {
+        let mut updates = self.updates.subscribe();
+        let deadline = tokio::time::sleep(wait);
+        tokio::pin!(deadline);
+        loop {
+            if ready(
+                &self
+                    .output
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner),
+            ) {
+                return;
+            }
+            tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => return,
+                _ = &mut deadline => return,
+                changed = updates.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn take_output(&self) -> BackgroundShellRead {
+        let mut output = self
+            .output
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let mut length = output.unread.len().min(MAX_OUTPUT_READ_BYTES);
+        // Leave a split UTF-8 sequence for the next read.
+        while length < output.unread.len()
+            && length > 0
+            && output.unread[length] & 0b1100_0000 == 0b1000_0000
+        {
+            length -= 1;
+        }
+        let bytes = output.unread.drain(..length).collect::<Vec<_>>();
+        BackgroundShellRead {
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+            dropped: std::mem::take(&mut output.dropped),
+            remaining: output.unread.len(),
+            collection_error: output.collection_error.take(),
+            exit: output.exit,
+        }
+    }
+}
+
+struct BackgroundShellRead {
+    text: String,
+    dropped: u64,
+    remaining: usize,
+    collection_error: Option<String>,
+    exit: Option<BackgroundShellExit>,
+}
+
+impl BackgroundShellRead {
+    fn render(self, shell: &BackgroundShell, last_read: bool) -> String {
+        let mut rendered = String::new();
+        if self.dropped > 0 {
+            rendered.push_str(&format!(
+                "[{} earlier bytes were dropped because the output was not read in time]\n",
+                self.dropped
+            ));
+        }
+        if self.text.is_empty() {
+            rendered.push_str("(no new output)");
+        } else {
+            rendered.push_str(&self.text);
+        }
+        if self.remaining > 0 {
+            rendered.push_str(&if last_read {
+                format!("\n\n[{} more bytes were discarded]", self.remaining)
+            } else {
+                format!(
+                    "\n\n[{} more bytes are buffered; call shell_output again to read them]",
+                    self.remaining
+                )
+            });
+        }
+        if let Some(error) = self.collection_error {
+            rendered.push_str(&format!("\n\nOutput collection error: {error}"));
+        }
+        rendered.push_str(&match self.exit {
+            None if last_read => format!("\n\nProcess {} is being stopped.", shell.id),
+            None => format!(
+                "\n\nProcess {} is still running ({}s).",
+                shell.id,
+                shell.started.elapsed().as_secs()
+            ),
+            Some(BackgroundShellExit::Exited(Some(code))) => {
+                format!("\n\nProcess {} exited with code {code}.", shell.id)
+            }
+            Some(BackgroundShellExit::Exited(None)) => {
+                format!("\n\nProcess {} exited.", shell.id)
+            }
+            Some(BackgroundShellExit::Stopped) => format!("\n\nProcess {} was stopped.", shell.id),
+        });
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unread_output_is_bounded_and_collected_once_the_process_exits() {
+        let shells = BackgroundShells::default();
+        let shell = shells.register("make watch").unwrap();
+        shell.record_output(&vec![b'a'; MAX_RETAINED_OUTPUT_BYTES]);
+        shell.record_output("ü tail".as_bytes());
+
+        let first = shells
+            .read(
+                ShellOutputParams {
+                    id: shell.id().to_string(),
+                    wait_secs: None,
+                },
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        assert!(first.starts_with(&format!("[{} earlier bytes were dropped", "ü tail".len())));
+        assert!(first.contains("more bytes are buffered"));
+        assert!(first.contains("is still running"));
+
+        shell.finish(BackgroundShellExit::Exited(Some(0)));
+        let mut last = String::new();
+        while shells.len() == 1 {
+            last = shells
+                .read(
+                    ShellOutputParams {
+                        id: shell.id().to_string(),
+                        wait_secs: Some(0),
+                    },
+                    CancellationToken::new(),
+                )
+                .await
+                .unwrap();
+        }
+        assert!(last.contains("ü tail"));
+        assert!(last.ends_with("exited with code 0."));
+        assert!(shells
+            .read(
+                ShellOutputParams {
+                    id: shell.id().to_string(),
+                    wait_secs: None,
+                },
+                CancellationToken::new(),
+            )
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn registry_is_bounded_and_clearing_requests_every_kill() {
+        let shells = BackgroundShells::default();
+        let registered = (0..MAX_BACKGROUND_SHELLS)
+            .map(|_| shells.register("sleep 60").unwrap())
+            .collect::<Vec<_>>();
+        assert!(shells.register("sleep 60").is_err());
+
+        shells.clear();
+        assert_eq!(shells.len(), 0);
+        assert!(registered.iter().all(|shell| shell.kill.is_cancelled()));
+    }
+}
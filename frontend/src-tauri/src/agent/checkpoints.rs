@@ -0,0 +1,823 @@
+//! Per-run checkpoints for the developer `edit` and `write` tools.
+//!
+//! Before either tool changes a file, its previous contents are stored once
+//! under the account's local data directory, addressed by SHA-256, and the
+//! change is appended to a per-task manifest together with the run that made
+//! it. A revert only restores a file whose current contents still match what
+//! the agent wrote, so edits made afterwards by the user or another tool are
+//! never overwritten.
+
+use super::unified_diff::{unified_diff, MAX_DIFF_INPUT_BYTES};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Oldest changes are dropped, and their snapshots collected, past this count.
+const MAX_CHECKPOINT_CHANGES_PER_SESSION: usize = 1_000;
+const MAX_CHANGE_DIFF_CHARS: usize = 100_000;
+/// Largest file whose previous contents are snapshotted, and so the largest
+/// file a revert will read back to check it is unchanged.
+pub(super) const MAX_SNAPSHOT_BYTES: usize = 20 * 1024 * 1024;
+const MANIFEST_VERSION: u32 = 1;
+
+/// Manifests are read-modify-written by concurrent tool calls on different
+/// paths, so every manifest update in the process is serialized here.
+static MANIFEST_LOCK: StdMutex<()> = StdMutex::new(());
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum AgentFileSnapshot {
+    /// The file did not exist before the change.
+    Missing,
+    Stored {
+        sha256: String,
+    },
+    /// The file was too large or unreadable, so the change cannot be undone.
+    Unavailable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFileChange {
+    pub id: String,
+    pub run_id: String,
+    pub path: String,
+    pub tool: String,
+    pub before: AgentFileSnapshot,
+    pub after_sha256: String,
+    pub recorded_ms: u128,
+    pub reverted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentFileChangeDiff {
+    #[serde(flatten)]
+    pub change: AgentFileChange,
+    /// `None` when either side is unavailable, too large, or not UTF-8 text.
+    pub diff: Option<String>,
+    pub diff_truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentRunChanges {
+    pub session_id: String,
+    /// The run the changes belong to, or `None` when the task has none.
+    pub run_id: Option<String>,
+    pub changes: Vec<AgentFileChangeDiff>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckpointManifest {
+    version: u32,
+    changes: Vec<AgentFileChange>,
+    /// Set by an update that fails after changing files, so its partial
+    /// progress is still persisted.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// The contents a tool observed before changing a file.
+pub(super) enum FilePreImage {
+    Missing,
+    Contents(Vec<u8>),
+    Unavailable,
+}
+
+/// Which run currently owns each task. Tool calls outside a run, such as
+/// ones made through an attached external surface, are not checkpointed.
+#[derive(Clone, Default)]
+pub(super) struct ActiveCheckpointRuns {
+    runs: Arc<StdMutex<HashMap<String, String>>>,
+}
+
+impl ActiveCheckpointRuns {
+    pub(super) fn begin(&self, session_id: &str, run_id: &str) {
+        self.lock()
+            .insert(session_id.to_string(), run_id.to_string());
+    }
+
+    pub(super) fn finish(&self, session_id: &str, run_id: &str) {
+        let mut runs = self.lock();
+        if runs.get(session_id).is_some_and(|active| active == run_id) {
+            runs.remove(session_id);
+        }
+    }
+
+    pub(super) fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn run_for(&self, session_id: &str) -> Option<String> {
+        self.lock().get(session_id).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        self.runs
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct AgentCheckpointStore {
+    root: PathBuf,
+}
+
+/// Records the changes one run makes to one task's files.
+#[derive(Debug, Clone)]
+pub(super) struct FileCheckpointRecorder {
+    store: AgentCheckpointStore,
+    session_id: String,
+    run_id: String,
+}
+
+impl FileCheckpointRecorder {
+    pub(super) fn record(
+        &self,
+        tool: &str,
+        path: &Path,
+        before: FilePreImage,
+        after: &[u8],
+    ) -> Result<(), String> {
+        let store = &self.store;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        store
+            .update_manifest(&self.session_id, |manifest| {
+                // Snapshots are stored under the manifest lock so a concurrent
+                // garbage collection cannot remove them before they are listed.
+                let before = match before {
+                    FilePreImage::Missing => AgentFileSnapshot::Missing,
+                    FilePreImage::Contents(bytes) => AgentFileSnapshot::Stored {
+                        sha256: store.store_blob(&bytes)?,
+                    },
+                    FilePreImage::Unavailable => AgentFileSnapshot::Unavailable,
+                };
+                let change = AgentFileChange {
+                    id: format!("change_{:032x}", rand::random::<u128>()),
+                    run_id: self.run_id.clone(),
+                    path: path.to_string_lossy().to_string(),
+                    tool: tool.to_string(),
+                    before,
+                    after_sha256: store.store_blob(after)?,
+                    recorded_ms: unix_ms(),
+                    reverted: false,
+                };
+                manifest.changes.push(change);
+                let overflow = manifest
+                    .changes
+                    .len()
+                    .saturating_sub(MAX_CHECKPOINT_CHANGES_PER_SESSION);
+                manifest.changes.drain(..overflow);
+                Ok(overflow > 0)
+            })
+            .map(|dropped| {
+                if dropped {
+                    store.collect_garbage();
+                }
+            })
+    }
+}
+
+impl AgentCheckpointStore {
+    pub(super) fn new(account_local_data_dir: PathBuf) -> Self {
+        Self {
+            root: account_local_data_dir.join("checkpoints"),
+        }
+    }
+
+    pub(super) fn recorder(
+        &self,
+        active_runs: &ActiveCheckpointRuns,
+        session_id: &str,
+    ) -> Option<FileCheckpointRecorder> {
+        Some(FileCheckpointRecorder {
+            store: self.clone(),
+            session_id: session_id.to_string(),
+            run_id: active_runs.run_for(session_id)?,
+        })
+    }
+
+    /// Changes made by `run_id`, or by the task's most recent run when no run
+    /// is named, each with a bounded unified diff.
+    pub(super) fn run_changes(
+        &self,
+        session_id: &str,
+        run_id: Option<&str>,
+    ) -> Result<AgentRunChanges, String> {
+        let manifest = self.load_manifest(session_id)?;
+        let run_id = match run_id {
+            Some(run_id) => Some(run_id.to_string()),
+            None => manifest.changes.last().map(|change| change.run_id.clone()),
+        };
+        let changes = manifest
+            .changes
+            .into_iter()
+            .filter(|change| Some(&change.run_id) == run_id.as_ref())
+            .map(|change| self.change_diff(change))
+            .collect();
+        Ok(AgentRunChanges {
+            session_id: session_id.to_string(),
+            run_id,
+            changes,
+        })
+    }
+
+    pub(super) fn revert_change(
+        &self,
+        session_id: &str,
+        change_id: &str,
+    ) -> Result<AgentFileChange, String> {
+        self.update_manifest(session_id, |manifest| {
+            let index = manifest
+                .changes
+                .iter()
+                .position(|change| change.id == change_id)
+                .ok_or_else(|| format!("Agent file change {change_id} was not found"))?;
+            let change = &manifest.changes[index];
+            if change.reverted {
+                return Err(format!("{} was already reverted", change.path));
+            }
+            if manifest.changes[index + 1..]
+                .iter()
+                .any(|later| later.path == change.path && !later.reverted)
+            {
+                return Err(format!(
+                    "Revert the later agent changes to {} first",
+                    change.path
+                ));
+            }
+            self.ensure_unmodified(change)?;
+            self.ensure_restorable(change)?;
+            self.restore(change)?;
+            manifest.changes[index].reverted = true;
+            Ok(manifest.changes[index].clone())
+        })
+    }
+
+    /// Restore every file the run changed to its state before the run. Each
+    /// file is checked before any is written, so a conflict leaves the whole
+    /// run untouched.
+    pub(super) fn revert_run(
+        &self,
+        session_id: &str,
+        run_id: &str,
+    ) -> Result<Vec<AgentFileChange>, String> {
+        self.update_manifest(session_id, |manifest| {
+            let indexes = manifest
+                .changes
+                .iter()
+                .enumerate()
+                .filter(|(_, change)| change.run_id == run_id && !change.reverted)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            if indexes.is_empty() {
+                return Err("This run has no file changes to revert".to_string());
+            }
+
+            // For each path, the earliest change holds the pre-run contents
+            // and the latest holds what the file should still contain now.
+            let mut by_path: Vec<(usize, usize)> = Vec::new();
+            let mut seen = HashMap::new();
+            for &index in &indexes {
+                let path = &manifest.changes[index].path;
+                match seen.get(path) {
+                    Some(&slot) => by_path[slot].1 = index,
+                    None => {
+                        seen.insert(path.clone(), by_path.len());
+                        by_path.push((index, index));
+                    }
+                }
+            }
+            let last_index = *indexes.last().expect("indexes checked above");
+            for &(first, last) in &by_path {
+                let path = &manifest.changes[first].path;
+                if manifest.changes[last_index + 1..]
+                    .iter()
+                    .any(|later| &later.path == path && !later.reverted)
+                {
+                    return Err(format!(
+                        "{path} was changed again by a later run; revert that run first"
+                    ));
+                }
+                self.ensure_unmodified(&manifest.changes[last])?;
+                self.ensure_restorable(&manifest.changes[first])?;
+            }
+            for (restored, &(first, _)) in by_path.iter().enumerate() {
+                if let Err(error) = self.restore(&manifest.changes[first]) {
+                    // Keep the manifest truthful about the files already put
+                    // back before this one failed.
+                    let restored_paths = by_path[..restored]
+                        .iter()
+                        .map(|&(first, _)| manifest.changes[first].path.clone())
+                        .collect::<HashSet<_>>();
+                    for &index in &indexes {
+                        if restored_paths.contains(&manifest.changes[index].path) {
+                            manifest.changes[index].reverted = true;
+                        }
+                    }
+                    manifest.dirty = true;
+                    return Err(error);
+                }
+            }
+            for &index in &indexes {
+                manifest.changes[index].reverted = true;
+            }
+            Ok(indexes
+                .into_iter()
+                .map(|index| manifest.changes[index].clone())
+                .collect())
+        })
+    }
+
+    pub(super) fn delete_session(&self, session_id: &str) -> Result<(), String> {
+        let guard = manifest_lock();
+        match fs::remove_file(self.manifest_path(session_id)?) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(format!("Failed to delete Agent checkpoints: {error}")),
+        }
+        drop(guard);
+        self.collect_garbage();
+        Ok(())
+    }
+
+    fn change_diff(&self, change: AgentFileChange) -> AgentFileChangeDiff {
+        let before = match &change.before {
+            AgentFileSnapshot::Missing => Some(Vec::new()),
+            AgentFileSnapshot::Stored { sha256 } => self.read_blob(sha256).ok(),
+            AgentFileSnapshot::Unavailable => None,
+        };
+        let after = self.read_blob(&change.after_sha256).ok();
+        let texts = before
+            .zip(after)
+            .filter(|(before, after)| before.len() + after.len() <= MAX_DIFF_INPUT_BYTES)
+            .and_then(|(before, after)| Some((String::from_utf8(before).ok()?, after)))
+            .and_then(|(before, after)| Some((before, String::from_utf8(after).ok()?)));
+        let Some((before, after)) = texts else {
+            return AgentFileChangeDiff {
+                change,
+                diff: None,
+                diff_truncated: false,
+            };
+        };
+        let old_label = match change.before {
+            AgentFileSnapshot::Missing => "/dev/null".to_string(),
+            _ => format!("a{}", display_path(&change.path)),
+        };
+        let new_label = format!("b{}", display_path(&change.path));
+        let diff = unified_diff(
+            &before,
+            &after,
+            &old_label,
+            &new_label,
+            MAX_CHANGE_DIFF_CHARS,
+        );
+        AgentFileChangeDiff {
+            change,
+            diff: Some(diff.text),
+            diff_truncated: diff.truncated,
+        }
+    }
+
+    fn ensure_unmodified(&self, change: &AgentFileChange) -> Result<(), String> {
+        let current = match fs::symlink_metadata(&change.path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(format!(
+                    "{} was replaced by a symbolic link; it was left untouched",
+                    change.path
+                ));
+            }
+            Ok(_) => Some(read_bounded_sha256(&change.path)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(format!("Failed to read {}: {error}", change.path)),
+        };
+        if current.as_deref() != Some(change.after_sha256.as_str()) {
+            return Err(format!(
+                "{} was modified after the agent changed it; it was left untouched",
+                change.path
+            ));
+        }
+        Ok(())
+    }
+
+    fn ensure_restorable(&self, change: &AgentFileChange) -> Result<(), String> {
+        match &change.before {
+            AgentFileSnapshot::Missing => Ok(()),
+            AgentFileSnapshot::Stored { sha256 } => {
+                if self.blob_path(sha256)?.is_file() {
+                    Ok(())
+                } else {
+                    Err(format!("The snapshot of {} is missing", change.path))
+                }
+            }
+            AgentFileSnapshot::Unavailable => Err(format!(
+                "{} was not snapshotted before the change and cannot be reverted",
+                change.path
+            )),
+        }
+    }
+
+    fn restore(&self, change: &AgentFileChange) -> Result<(), String> {
+        let result = match &change.before {
+            AgentFileSnapshot::Missing => fs::remove_file(&change.path),
+            AgentFileSnapshot::Stored { sha256 } => {
+                let bytes = self.read_blob(sha256)?;
+                restore_replacing(Path::new(&change.path), &bytes)
+            }
+            AgentFileSnapshot::Unavailable => return self.ensure_restorable(change),
+        };
+        result.map_err(|error| format!("Failed to restore {}: {error}", change.path))
+    }
+
+    fn store_blob(&self, bytes: &[u8]) -> Result<String, String> {
+        let sha256 = sha256_hex(bytes);
+        let path = self.blob_path(&sha256)?;
+        if path.is_file() {
+            return Ok(sha256);
+        }
+        create_owner_only_dir(path.parent().expect("blob paths have a parent"))?;
+        write_replacing(&path, bytes)?;
+        Ok(sha256)
+    }
+
+    fn read_blob(&self, sha256: &str) -> Result<Vec<u8>, String> {
+        fs::read(self.blob_path(sha256)?)
+            .map_err(|error| format!("Failed to read Agent checkpoint: {error}"))
+    }
+
+    fn blob_path(&self, sha256: &str) -> Result<PathBuf, String> {
+        if sha256.len() != 64 || !sha256.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err("Agent checkpoint ID is invalid".to_string());
+        }
+        Ok(self.root.join("blobs").join(sha256))
+    }
+
+    fn manifest_path(&self, session_id: &str) -> Result<PathBuf, String> {
+        let session_id = session_id.trim();
+        if session_id.is_empty() {
+            return Err("Agent task ID cannot be empty".to_string());
+        }
+        let digest = Sha256::digest(session_id.as_bytes());
+        Ok(self.root.join("sessions").join(format!("{digest:x}.json")))
+    }
+
+    fn load_manifest(&self, session_id: &str) -> Result<CheckpointManifest, String> {
+        read_manifest(&self.manifest_path(session_id)?)
+    }
+
+    fn update_manifest<T>(
+        &self,
+        session_id: &str,
+        update: impl FnOnce(&mut CheckpointManifest) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let path = self.manifest_path(session_id)?;
+        let _guard = manifest_lock();
+        let mut manifest = read_manifest(&path)?;
+        let result = update(&mut manifest);
+        if result.is_ok() || manifest.dirty {
+            manifest.version = MANIFEST_VERSION;
+            create_owner_only_dir(path.parent().expect("manifest paths have a parent"))?;
+            let bytes = serde_json::to_vec(&manifest)
+                .map_err(|error| format!("Failed to encode Agent checkpoints: {error}"))?;
+            write_replacing(&path, &bytes)?;
+        }
+        result
+    }
+
+    /// Remove snapshots no manifest references. Failures only leave unused
+    /// files behind, so they are logged rather than surfaced.
+    fn collect_garbage(&self) {
+        let _guard = manifest_lock();
+        let mut referenced = HashSet::new();
+        let sessions = match fs::read_dir(self.root.join("sessions")) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return,
+            Err(error) => {
+                log::warn!("Failed to list Agent checkpoints: {error}");
+                return;
+            }
+        };
+        for entry in sessions.flatten() {
+            match read_manifest(&entry.path()) {
+                Ok(manifest) => {
+                    for change in manifest.changes {
+                        if let AgentFileSnapshot::Stored { sha256 } = change.before {
+                            referenced.insert(sha256);
+                        }
+                        referenced.insert(change.after_sha256);
+                    }
+                }
+                Err(error) => {
+                    // An unreadable manifest may still reference any blob.
+                    log::warn!("Skipped Agent checkpoint cleanup: {error}");
+                    return;
+                }
+            }
+        }
+        let Ok(blobs) = fs::read_dir(self.root.join("blobs")) else {
+            return;
+        };
+        for entry in blobs.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !referenced.contains(&name) {
+                if let Err(error) = fs::remove_file(entry.path()) {
+                    log::warn!("Failed to remove Agent checkpoint {name}: {error}");
+                }
+            }
+        }
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<CheckpointManifest, String> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(CheckpointManifest::default());
+        }
+        Err(error) => return Err(format!("Failed to read Agent checkpoints: {error}")),
+    };
+    let manifest: CheckpointManifest = serde_json::from_slice(&bytes)
+        .map_err(|error| format!("Failed to parse Agent checkpoints: {error}"))?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err("Agent checkpoints were written by a newer version of Maple".to_string());
+    }
+    Ok(manifest)
+}
+
+fn manifest_lock() -> std::sync::MutexGuard<'static, ()> {
+    MANIFEST_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Write through a sibling temporary file so a crash never leaves a torn
+/// snapshot or manifest behind.
+fn write_replacing(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let temporary = path.with_extension(format!("tmp-{:016x}", rand::random::<u64>()));
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options
+        .open(&temporary)
+        .and_then(|mut file| file.write_all(bytes).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&temporary, path));
+    if let Err(error) = written {
+        let _ = fs::remove_file(&temporary);
+        return Err(format!("Failed to store Agent checkpoint: {error}"));
+    }
+    Ok(())
+}
+
+/// Hash a file the agent changed, refusing to read past the snapshot limit.
+fn read_bounded_sha256(path: &str) -> Result<String, String> {
+    let file = fs::File::open(path).map_err(|error| format!("Failed to read {path}: {error}"))?;
+    let mut bytes = Vec::new();
+    file.take(MAX_SNAPSHOT_BYTES as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|error| format!("Failed to read {path}: {error}"))?;
+    if bytes.len() > MAX_SNAPSHOT_BYTES {
+        return Err(format!(
+            "{path} is larger than the {MAX_SNAPSHOT_BYTES} byte checkpoint limit; it was left untouched"
+        ));
+    }
+    Ok(sha256_hex(&bytes))
+}
+
+/// Put a file's previous contents back through a sibling temporary file. The
+/// rename replaces whatever sits at `path` instead of following a symbolic
+/// link planted there, and the file keeps its current permissions.
+fn restore_replacing(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let permissions = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the path is a symbolic link",
+            ));
+        }
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+        Err(error) => return Err(error),
+    };
+    let temporary = path.with_extension(format!("maple-restore-{:016x}", rand::random::<u64>()));
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temporary)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temporary, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    written
+}
+
+fn create_owner_only_dir(path: &Path) -> Result<(), String> {
+    fs::create_dir_all(path)
+        .map_err(|error| format!("Failed to create Agent checkpoint store: {error}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+            .map_err(|error| format!("Failed to secure Agent checkpoint store: {error}"))?;
+    }
+    Ok(())
+}
+
+fn display_path(path: &str) -> String {
+    if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{path}")
+    }
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn unix_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn recorder(store: &AgentCheckpointStore, run_id: &str) -> FileCheckpointRecorder {
+        let runs = ActiveCheckpointRuns::default();
+        runs.begin("session", run_id);
+        store.recorder(&runs, "session").expect("run is active")
+    }
+
+    #[test]
+    fn lists_and_reverts_a_runs_changes_in_order() {
+        let data = tempdir().unwrap();
+        let project = tempdir().unwrap();
+        let store = AgentCheckpointStore::new(data.path().to_path_buf());
+        let edited = project.path().join("notes.txt");
+        let created = project.path().join("new.txt");
+        fs::write(&edited, "one\n").unwrap();
+
+        let run = recorder(&store, "run-1");
+        fs::write(&edited, "two\n").unwrap();
+        run.record(
+            "edit",
+            &edited,
+            FilePreImage::Contents(b"one\n".to_vec()),
+            b"two\n",
+        )
+        .unwrap();
+        fs::write(&edited, "three\n").unwrap();
+        run.record(
+            "write",
+            &edited,
+            FilePreImage::Contents(b"two\n".to_vec()),
+            b"three\n",
+        )
+        .unwrap();
+        fs::write(&created, "fresh\n").unwrap();
+        run.record("write", &created, FilePreImage::Missing, b"fresh\n")
+            .unwrap();
+
+        let listed = store.run_changes("session", None).unwrap();
+        assert_eq!(listed.run_id.as_deref(), Some("run-1"));
+        assert_eq!(listed.changes.len(), 3);
+        let first_diff = listed.changes[0].diff.as_deref().unwrap();
+        assert!(first_diff.ends_with("@@ -1 +1 @@\n-one\n+two\n"));
+        assert!(listed.changes[2]
+            .diff
+            .as_deref()
+            .unwrap()
+            .starts_with("--- /dev/null\n"));
+
+        let reverted = store.revert_run("session", "run-1").unwrap();
+        assert_eq!(reverted.len(), 3);
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "one\n");
+        assert!(!created.exists());
+        assert!(store.revert_run("session", "run-1").is_err());
+    }
+
+    #[test]
+    fn refuses_to_revert_files_modified_after_the_change() {
+        let data = tempdir().unwrap();
+        let project = tempdir().unwrap();
+        let store = AgentCheckpointStore::new(data.path().to_path_buf());
+        let path = project.path().join("main.rs");
+        fs::write(&path, "after\n").unwrap();
+        recorder(&store, "run-1")
+            .record(
+                "edit",
+                &path,
+                FilePreImage::Contents(b"before\n".to_vec()),
+                b"after\n",
+            )
+            .unwrap();
+        let change_id = store.run_changes("session", None).unwrap().changes[0]
+            .change
+            .id
+            .clone();
+
+        fs::write(&path, "user edit\n").unwrap();
+        let error = store.revert_change("session", &change_id).unwrap_err();
+        assert!(error.contains("modified after the agent changed it"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "user edit\n");
+
+        fs::write(&path, "after\n").unwrap();
+        let change = store.revert_change("session", &change_id).unwrap();
+        assert!(change.reverted);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "before\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_to_restore_through_a_symlink_planted_after_the_change() {
+        let data = tempdir().unwrap();
+        let project = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let store = AgentCheckpointStore::new(data.path().to_path_buf());
+        let path = project.path().join("main.rs");
+        fs::write(&path, "after\n").unwrap();
+        recorder(&store, "run-1")
+            .record(
+                "write",
+                &path,
+                FilePreImage::Contents(b"before\n".to_vec()),
+                b"after\n",
+            )
+            .unwrap();
+
+        let target = outside.path().join("target.txt");
+        fs::write(&target, "after\n").unwrap();
+        fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        let error = store.revert_run("session", "run-1").unwrap_err();
+        assert!(error.contains("symbolic link"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "after\n");
+    }
+
+    #[test]
+    fn records_only_during_a_run_and_collects_snapshots_on_delete() {
+        let data = tempdir().unwrap();
+        let project = tempdir().unwrap();
+        let store = AgentCheckpointStore::new(data.path().to_path_buf());
+        let runs = ActiveCheckpointRuns::default();
+        assert!(store.recorder(&runs, "session").is_none());
+        runs.begin("session", "run-1");
+        runs.finish("session", "run-other");
+        let run = store.recorder(&runs, "session").unwrap();
+        runs.finish("session", "run-1");
+        assert!(store.recorder(&runs, "session").is_none());
+
+        let path = project.path().join("big.bin");
+        fs::write(&path, [0u8, 159, 146, 150]).unwrap();
+        run.record(
+            "write",
+            &path,
+            FilePreImage::Unavailable,
+            &[0, 159, 146, 150],
+        )
+        .unwrap();
+        let listed = store.run_changes("session", Some("run-1")).unwrap();
+        assert_eq!(listed.changes[0].diff, None);
+        assert!(store
+            .revert_change("session", &listed.changes[0].change.id)
+            .unwrap_err()
+            .contains("cannot be reverted"));
+
+        store.delete_session("session").unwrap();
+        assert!(store
+            .run_changes("session", None)
+            .unwrap()
+            .changes
+            .is_empty());
+        assert_eq!(
+            fs::read_dir(data.path().join("checkpoints/blobs"))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
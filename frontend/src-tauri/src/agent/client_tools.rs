@@ -0,0 +1,85 @@
+//! File and terminal operations delegated to the surface that owns a task.
+//!
+//! An ACP client that advertises file or terminal capabilities receives
+//! Maple's `read`, `write`, `edit`, and foreground `shell` operations instead
+//! of the local filesystem and process tree. Delegation is per capability
+//! (`edit` needs both read and write) and ends when the tool context is
+//! revoked. Background shells, and shells in an ephemeral tool context, still
+//! run locally so caller credentials are never sent back to the surface.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Developer-tool operations an external Agent surface performs itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct AgentClientToolCapabilities {
+    pub(crate) read_text_file: bool,
+    pub(crate) write_text_file: bool,
+    pub(crate) terminal: bool,
+}
+
+impl AgentClientToolCapabilities {
+    pub(crate) fn is_empty(self) -> bool {
+        !(self.read_text_file || self.write_text_file || self.terminal)
+    }
+}
+
+/// One foreground shell command for the surface's own terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AgentClientCommand {
+    pub(crate) command: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) cwd: Option<PathBuf>,
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) output_byte_limit: usize,
+    pub(crate) timeout: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct AgentClientCommandOutput {
+    pub(crate) output: String,
+    pub(crate) truncated: bool,
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) signal: Option<String>,
+    pub(crate) timed_out: bool,
+}
+
+/// File and terminal operations that an external Agent surface performs on
+/// Maple's behalf, so reads see the caller's unsaved buffers and writes land
+/// in them.
+///
+/// Calls reach an implementation only from Maple's developer tools, after the
+/// usual permission routing has allowed the tool call. Implementations only
+/// transport the operation; they must not add a second approval step.
+#[async_trait::async_trait]
+pub(crate) trait AgentClientTools: Send + Sync {
+    fn capabilities(&self) -> AgentClientToolCapabilities;
+
+    /// Read a text file, optionally starting at a 1-based `line` and
+    /// returning at most `limit` lines.
+    async fn read_text_file(
+        &self,
+        session_id: &str,
+        path: &Path,
+        line: Option<u32>,
+        limit: Option<u32>,
+        cancel: CancellationToken,
+    ) -> Result<String, String>;
+
+    async fn write_text_file(
+        &self,
+        session_id: &str,
+        path: &Path,
+        content: &str,
+        cancel: CancellationToken,
+    ) -> Result<(), String>;
+
+    /// Run a command to completion, killing it after `command.timeout`.
+    async fn run_command(
+        &self,
+        session_id: &str,
+        command: AgentClientCommand,
+        cancel: CancellationToken,
+    ) -> Result<AgentClientCommandOutput, String>;
+}
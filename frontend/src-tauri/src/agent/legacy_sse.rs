@@ -0,0 +1,461 @@
+//! Client half of MCP's legacy HTTP+SSE transport (protocol 2024-11-05).
+//!
+//! The server keeps one GET response open as an event stream. Its first
+//! `endpoint` event names the URL that client messages are POSTed to, and each
+//! later `message` event carries one JSON-RPC message from the server. Neither
+//! rmcp nor Goose ships this transport any more, so Maple connects these
+//! servers itself and hands Goose the running client.
+
+use super::maple_mcp::filtered_tool_result;
+use super::mcp_sampling::MapleMcpClientHandler;
+use super::transient_mcp::TransientMcpClient;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use goose::agents::mcp_client::{Error as McpError, McpClientTrait};
+use goose::agents::ToolCallContext;
+use reqwest::header::{HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use rmcp::model::{
+    CallToolResult, GetPromptResult, InitializeResult, JsonObject, ListPromptsResult,
+    ListResourcesResult, ListToolsResult, ReadResourceResult, ServerNotification,
+};
+use rmcp::service::{RoleClient, RxJsonRpcMessage, TxJsonRpcMessage};
+use rmcp::transport::Transport;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+const MAX_SSE_EVENT_BYTES: usize = 4 * 1024 * 1024;
+const INCOMING_MESSAGE_BUFFER: usize = 64;
+
+type ByteStream = BoxStream<'static, reqwest::Result<Vec<u8>>>;
+
+#[derive(Debug)]
+pub(crate) struct LegacySseError(String);
+
+impl fmt::Display for LegacySseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for LegacySseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SseEvent {
+    name: String,
+    data: String,
+}
+
+/// Incremental `text/event-stream` parser. Only the `event` and `data`
+/// fields matter to MCP; ids and retry hints are ignored.
+#[derive(Default)]
+struct SseParser {
+    line: Vec<u8>,
+    name: Option<String>,
+    data: Vec<String>,
+    data_bytes: usize,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Result<Vec<SseEvent>, LegacySseError> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if byte != b'\n' {
+                self.line.push(byte);
+                if self.line.len() + self.data_bytes > MAX_SSE_EVENT_BYTES {
+                    return Err(LegacySseError(
+                        "the server sent an event larger than Maple accepts".to_string(),
+                    ));
+                }
+                continue;
+            }
+            let mut line = std::mem::take(&mut self.line);
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            let line = String::from_utf8(line).map_err(|_| {
+                LegacySseError("the server sent an event that is not UTF-8".to_string())
+            })?;
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        name: self.name.take().unwrap_or_else(|| "message".to_string()),
+                        data: std::mem::take(&mut self.data).join("\n"),
+                    });
+                }
+                self.name = None;
+                self.data_bytes = 0;
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line.as_str(), ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.name = Some(value.to_string()),
+                "data" => {
+                    self.data_bytes += value.len() + 1;
+                    self.data.push(value.to_string());
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// rmcp transport over one legacy SSE connection.
+pub(crate) struct LegacySseTransport {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    request_timeout: Duration,
+    incoming: mpsc::Receiver<RxJsonRpcMessage<RoleClient>>,
+    reader: CancellationToken,
+}
+
+impl LegacySseTransport {
+    /// Open the event stream and wait for the server to name its message
+    /// endpoint. The endpoint must share the stream's origin so configured
+    /// headers are never sent to another host.
+    pub(crate) async fn connect(
+        url: &str,
+        headers: HashMap<HeaderName, HeaderValue>,
+        request_timeout: Duration,
+    ) -> Result<Self, LegacySseError> {
+        let url = reqwest::Url::parse(url)
+            .map_err(|_| LegacySseError("the endpoint URL is invalid".to_string()))?;
+        let client = reqwest::Client::builder()
+            .default_headers(headers.into_iter().collect())
+            .connect_timeout(request_timeout)
+            .build()
+            .map_err(|error| LegacySseError(format!("could not create an HTTP client: {error}")))?;
+        let handshake = async {
+            let response = client
+                .get(url.clone())
+                .header(ACCEPT, "text/event-stream")
+                .send()
+                .await
+                .map_err(|error| {
+                    LegacySseError(format!("could not open the event stream: {error}"))
+                })?;
+            if !response.status().is_success() {
+                return Err(LegacySseError(format!(
+                    "the event stream returned HTTP {}",
+                    response.status()
+                )));
+            }
+            let mut stream: ByteStream = response
+                .bytes_stream()
+                .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+                .boxed();
+            let mut parser = SseParser::default();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk
+                    .map_err(|error| LegacySseError(format!("the event stream failed: {error}")))?;
+                let mut events = parser.push(&chunk)?.into_iter();
+                if let Some(event) = events.by_ref().find(|event| event.name == "endpoint") {
+                    let endpoint = resolve_endpoint(&url, &event.data)?;
+                    return Ok((endpoint, stream, parser, events.collect::<Vec<_>>()));
+                }
+            }
+            Err(LegacySseError(
+                "the event stream closed before naming a message endpoint".to_string(),
+            ))
+        };
+        let (endpoint, stream, parser, pending) = tokio::time::timeout(request_timeout, handshake)
+            .await
+            .map_err(|_| {
+                LegacySseError("timed out waiting for the message endpoint".to_string())
+            })??;
+
+        let (sender, incoming) = mpsc::channel(INCOMING_MESSAGE_BUFFER);
+        let reader = CancellationToken::new();
+        tokio::spawn(read_messages(
+            stream,
+            parser,
+            pending,
+            sender,
+            reader.clone(),
+        ));
+        Ok(Self {
+            client,
+            endpoint,
+            request_timeout,
+            incoming,
+            reader,
+        })
+    }
+}
+
+fn resolve_endpoint(url: &reqwest::Url, data: &str) -> Result<reqwest::Url, LegacySseError> {
+    let endpoint = url
+        .join(data.trim())
+        .map_err(|_| LegacySseError("the server named an invalid message endpoint".to_string()))?;
+    if endpoint.origin() != url.origin() {
+        return Err(LegacySseError(
+            "the server named a message endpoint on another origin".to_string(),
+        ));
+    }
+    Ok(endpoint)
+}
+
+async fn read_messages(
+    mut stream: ByteStream,
+    mut parser: SseParser,
+    pending: Vec<SseEvent>,
+    sender: mpsc::Sender<RxJsonRpcMessage<RoleClient>>,
+    reader: CancellationToken,
+) {
+    let mut events = pending;
+    loop {
+        for event in events.drain(..) {
+            if event.name != "message" {
+                continue;
+            }
+            match serde_json::from_str(&event.data) {
+                Ok(message) => {
+                    if sender.send(message).await.is_err() {
+                        return;
+                    }
+                }
+                Err(error) => log::warn!("Ignoring malformed legacy SSE MCP message: {error}"),
+            }
+        }
+        let chunk = tokio::select! {
+            _ = reader.cancelled() => return,
+            chunk = stream.next() => chunk,
+        };
+        // Dropping the sender ends `receive`, which rmcp treats as a closed
+        // transport.
+        let Some(Ok(chunk)) = chunk else {
+            return;
+        };
+        match parser.push(&chunk) {
+            Ok(parsed) => events = parsed,
+            Err(error) => {
+                log::warn!("Closing legacy SSE MCP stream: {error}");
+                return;
+            }
+        }
+    }
+}
+
+impl Transport<RoleClient> for LegacySseTransport {
+    type Error = LegacySseError;
+
+    fn send(
+        &mut self,
+        item: TxJsonRpcMessage<RoleClient>,
+    ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send + 'static {
+        let client = self.client.clone();
+        let endpoint = self.endpoint.clone();
+        let request_timeout = self.request_timeout;
+        async move {
+            let body = serde_json::to_vec(&item)
+                .map_err(|error| LegacySseError(format!("could not encode a message: {error}")))?;
+            let response = client
+                .post(endpoint)
+                .header(CONTENT_TYPE, "application/json")
+                .timeout(request_timeout)
+                .body(body)
+                .send()
+                .await
+                .map_err(|error| LegacySseError(format!("could not send a message: {error}")))?;
+            if !response.status().is_success() {
+                return Err(LegacySseError(format!(
+                    "the message endpoint returned HTTP {}",
+                    response.status()
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    fn receive(
+        &mut self,
+    ) -> impl std::future::Future<Output = Option<RxJsonRpcMessage<RoleClient>>> + Send {
+        self.incoming.recv()
+    }
+
+    async fn close(&mut self) -> Result<(), Self::Error> {
+        self.reader.cancel();
+        Ok(())
+    }
+}
+
+impl Drop for LegacySseTransport {
+    fn drop(&mut self) {
+        self.reader.cancel();
+    }
+}
+
+/// A connected legacy SSE server with the user's tool filter applied.
+/// Goose only filters through `available_tools`, which its SSE extension
+/// config cannot carry, so the filter lives on the client instead.
+pub(crate) struct LegacySseMcpClient {
+    inner: Arc<TransientMcpClient>,
+    allowed: HashSet<String>,
+    blocked: HashSet<String>,
+}
+
+impl LegacySseMcpClient {
+    pub(crate) async fn connect(
+        url: &str,
+        headers: HashMap<HeaderName, HeaderValue>,
+        request_timeout: Duration,
+        allowed_tools: &[String],
+        blocked_tools: &[String],
+        handler: MapleMcpClientHandler,
+    ) -> Result<Self, String> {
+        let transport = LegacySseTransport::connect(url, headers, request_timeout)
+            .await
+            .map_err(|error| error.to_string())?;
+        let inner = TransientMcpClient::serve(
+            transport,
+            handler,
+            request_timeout,
+            CancellationToken::new(),
+        )
+        .await
+        .map_err(|error| error.to_string())?;
+        Ok(Self {
+            inner,
+            allowed: allowed_tools.iter().cloned().collect(),
+            blocked: blocked_tools.iter().cloned().collect(),
+        })
+    }
+
+    fn permits(&self, name: &str) -> bool {
+        !self.blocked.contains(name) && (self.allowed.is_empty() || self.allowed.contains(name))
+    }
+}
+
+#[async_trait::async_trait]
+impl McpClientTrait for LegacySseMcpClient {
+    async fn list_tools(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListToolsResult, McpError> {
+        let mut result = self
+            .inner
+            .list_tools(session_id, next_cursor, cancel_token)
+            .await?;
+        result.tools.retain(|tool| self.permits(&tool.name));
+        Ok(result)
+    }
+
+    async fn call_tool(
+        &self,
+        context: &ToolCallContext,
+        name: &str,
+        arguments: Option<JsonObject>,
+        cancel_token: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        if !self.permits(name) {
+            return Ok(filtered_tool_result(name));
+        }
+        self.inner
+            .call_tool(context, name, arguments, cancel_token)
+            .await
+    }
+
+    async fn list_resources(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListResourcesResult, McpError> {
+        self.inner
+            .list_resources(session_id, next_cursor, cancel_token)
+            .await
+    }
+
+    async fn read_resource(
+        &self,
+        session_id: &str,
+        uri: &str,
+        cancel_token: CancellationToken,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.inner
+            .read_resource(session_id, uri, cancel_token)
+            .await
+    }
+
+    async fn list_prompts(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListPromptsResult, McpError> {
+        self.inner
+            .list_prompts(session_id, next_cursor, cancel_token)
+            .await
+    }
+
+    async fn get_prompt(
+        &self,
+        session_id: &str,
+        name: &str,
+        arguments: Value,
+        cancel_token: CancellationToken,
+    ) -> Result<GetPromptResult, McpError> {
+        self.inner
+            .get_prompt(session_id, name, arguments, cancel_token)
+            .await
+    }
+
+    fn get_info(&self) -> Option<&InitializeResult> {
+        self.inner.get_info()
+    }
+
+    async fn subscribe(&self) -> mpsc::Receiver<ServerNotification> {
+        self.inner.subscribe().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_parsed_across_chunks_and_line_endings() {
+        let mut parser = SseParser::default();
+        assert!(parser
+            .push(b": keep-alive\r\nevent: endpoint\r\ndata: /messages?session=1")
+            .unwrap()
+            .is_empty());
+        let events = parser.push(b"\r\n\r\ndata: {\"a\":\ndata: 1}\n\n").unwrap();
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    name: "endpoint".to_string(),
+                    data: "/messages?session=1".to_string(),
+                },
+                SseEvent {
+                    name: "message".to_string(),
+                    data: "{\"a\":\n1}".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn message_endpoints_must_share_the_stream_origin() {
+        let url = reqwest::Url::parse("https://mcp.example.com/sse").unwrap();
+        assert_eq!(
+            resolve_endpoint(&url, "/messages?session=1")
+                .unwrap()
+                .as_str(),
+            "https://mcp.example.com/messages?session=1"
+        );
+        assert!(resolve_endpoint(&url, "https://elsewhere.example.com/messages").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! User MCP servers that Maple connects instead of Goose.
+//!
+//! Goose's own MCP client cannot answer sampling or elicitation requests, so
+//! Maple connects those servers itself and hands Goose the running client
+//! under the server's extension config. Goose only filters tools through
+//! `available_tools`, which cannot express a block list, so the user's tool
+//! filter lives on the client instead. Legacy SSE servers connect through
+//! [`super::legacy_sse::LegacySseMcpClient`].
+
+use super::mcp_diagnostics::{http_transport, stdio_transport};
+use super::mcp_sampling::MapleMcpClientHandler;
+use super::transient_mcp::TransientMcpClient;
+use super::{AgentMcpServer, AgentMcpTransport};
+use goose::agents::mcp_client::{Error as McpError, McpClientTrait};
+use goose::agents::ToolCallContext;
+use rmcp::model::{
+    CallToolResult, ContentBlock, GetPromptResult, InitializeResult, JsonObject, ListPromptsResult,
+    ListResourcesResult, ListToolsResult, ReadResourceResult, ServerNotification,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// A connected user MCP server with its tool filter applied.
+pub(crate) struct MapleMcpClient {
+    inner: Arc<TransientMcpClient>,
+    allowed: HashSet<String>,
+    blocked: HashSet<String>,
+}
+
+impl MapleMcpClient {
+    /// Connect `server`, whose working directory must already be resolved.
+    pub(crate) async fn connect(
+        server: &AgentMcpServer,
+        handler: MapleMcpClientHandler,
+    ) -> Result<Self, String> {
+        let timeout = Duration::from_secs(server.timeout_seconds);
+        let cancel = CancellationToken::new();
+        let inner = match &server.transport {
+            AgentMcpTransport::Stdio { command, .. } => {
+                let (transport, stderr) = stdio_transport(server, command)?;
+                if let Some(mut stderr) = stderr {
+                    // Nobody reads a session server's stderr, but a full pipe
+                    // would stall the server.
+                    tokio::spawn(async move {
+                        let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+                    });
+                }
+                TransientMcpClient::serve(transport, handler, timeout, cancel).await
+            }
+            AgentMcpTransport::StreamableHttp { url, headers, .. } => {
+                let transport = http_transport(server, url, headers, None, timeout, None)?;
+                TransientMcpClient::serve(transport, handler, timeout, cancel).await
+            }
+            AgentMcpTransport::UnixSocket {
+                socket_path,
+                url,
+                headers,
+                ..
+            } => {
+                let transport =
+                    http_transport(server, url, headers, Some(socket_path), timeout, None)?;
+                TransientMcpClient::serve(transport, handler, timeout, cancel).await
+            }
+            AgentMcpTransport::Sse { .. } => {
+                return Err("Legacy SSE servers connect through their own client".to_string());
+            }
+        }
+        .map_err(|error| error.to_string())?;
+        Ok(Self {
+            inner,
+            allowed: server.allowed_tools.iter().cloned().collect(),
+            blocked: server.blocked_tools.iter().cloned().collect(),
+        })
+    }
+
+    fn permits(&self, name: &str) -> bool {
+        !self.blocked.contains(name) && (self.allowed.is_empty() || self.allowed.contains(name))
+    }
+}
+
+/// The result of calling a tool the user's filter hides. The model sees why
+/// the call failed instead of an opaque transport error.
+pub(super) fn filtered_tool_result(name: &str) -> CallToolResult {
+    CallToolResult::error(vec![ContentBlock::text(format!(
+        "The MCP tool '{name}' is blocked by this server's tool settings in Maple"
+    ))])
+}
+
+#[async_trait::async_trait]
+impl McpClientTrait for MapleMcpClient {
+    async fn list_tools(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListToolsResult, McpError> {
+        let mut result = self
+            .inner
+            .list_tools(session_id, next_cursor, cancel_token)
+            .await?;
+        result.tools.retain(|tool| self.permits(&tool.name));
+        Ok(result)
+    }
+
+    async fn call_tool(
+        &self,
+        context: &ToolCallContext,
+        name: &str,
+        arguments: Option<JsonObject>,
+        cancel_token: CancellationToken,
+    ) -> Result<CallToolResult, McpError> {
+        if !self.permits(name) {
+            return Ok(filtered_tool_result(name));
+        }
+        self.inner
+            .call_tool(context, name, arguments, cancel_token)
+            .await
+    }
+
+    async fn list_resources(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListResourcesResult, McpError> {
+        self.inner
+            .list_resources(session_id, next_cursor, cancel_token)
+            .await
+    }
+
+    async fn read_resource(
+        &self,
+        session_id: &str,
+        uri: &str,
+        cancel_token: CancellationToken,
+    ) -> Result<ReadResourceResult, McpError> {
+        self.inner
+            .read_resource(session_id, uri, cancel_token)
+            .await
+    }
+
+    async fn list_prompts(
+        &self,
+        session_id: &str,
+        next_cursor: Option<String>,
+        cancel_token: CancellationToken,
+    ) -> Result<ListPromptsResult, McpError> {
+        self.inner
+            .list_prompts(session_id, next_cursor, cancel_token)
+            .await
+    }
+
+    async fn get_prompt(
+        &self,
+        session_id: &str,
+        name: &str,
+        arguments: Value,
+        cancel_token: CancellationToken,
+    ) -> Result<GetPromptResult, McpError> {
+        self.inner
+            .get_prompt(session_id, name, arguments, cancel_token)
+            .await
+    }
+
+    fn get_info(&self) -> Option<&InitializeResult> {
+        self.inner.get_info()
+    }
+
+    async fn subscribe(&self) -> mpsc::Receiver<ServerNotification> {
+        self.inner.subscribe().await
+    }
+}
@@ -0,0 +1,439 @@
+//! One-off connection checks for configured MCP servers.
+//!
+//! A check starts the server's transport the way a session would, but in
+//! isolation: no Goose extension manager, session, or snapshot is involved,
+//! and the transport is torn down before the report is returned. Errors are
+//! reported in full rather than through the truncated session summary.
+
+use super::legacy_sse::LegacySseTransport;
+use super::{
+    mcp_environment, mcp_working_directory, split_mcp_command, AgentMcpKeyValue, AgentMcpServer,
+    AgentMcpToolPermissionLevel, AgentMcpTransport,
+};
+use goose::agents::extension::Envs;
+use goose::config::search_path::SearchPaths;
+#[cfg(unix)]
+use goose::subprocess::configure_subprocess;
+use reqwest::header::{HeaderName, HeaderValue};
+use rmcp::model::PaginatedRequestParams;
+use rmcp::service::{RoleClient, RunningService};
+use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
+use rmcp::transport::{StreamableHttpClientTransport, TokioChildProcess};
+use rmcp::ServiceExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(windows)]
+use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+const MAX_TEST_TOOL_PAGES: usize = 16;
+const MAX_TEST_TOOLS: usize = 256;
+const MAX_TEST_STDERR_BYTES: usize = 64 * 1024;
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+const TEST_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentMcpTestStage {
+    Start,
+    Initialize,
+    ListTools,
+    Complete,
+}
+
+/// Outcome of connecting to one MCP server outside any session.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpServerTest {
+    pub name: String,
+    pub success: bool,
+    /// The last stage reached; on failure, the stage that failed.
+    pub stage: AgentMcpTestStage,
+    pub protocol_version: Option<String>,
+    pub server_name: Option<String>,
+    pub server_version: Option<String>,
+    pub tool_count: usize,
+    pub tools: Vec<AgentMcpTestTool>,
+    pub tools_truncated: bool,
+    pub initialize_ms: Option<u64>,
+    pub list_tools_ms: Option<u64>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+    /// Standard error captured from a stdio server.
+    pub stderr: Option<String>,
+}
+
+/// One tool reported by a connection check.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpTestTool {
+    pub name: String,
+    /// The permission the tool gets until the user chooses one.
+    pub default_permission: AgentMcpToolPermissionLevel,
+}
+
+impl AgentMcpServerTest {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            success: false,
+            stage: AgentMcpTestStage::Start,
+            protocol_version: None,
+            server_name: None,
+            server_version: None,
+            tool_count: 0,
+            tools: Vec::new(),
+            tools_truncated: false,
+            initialize_ms: None,
+            list_tools_ms: None,
+            latency_ms: 0,
+            error: None,
+            stderr: None,
+        }
+    }
+}
+
+/// Connect, initialize, and list tools. `server` must already be normalized.
+pub(super) async fn test_mcp_server(server: &AgentMcpServer) -> AgentMcpServerTest {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(server.timeout_seconds);
+    let mut report = AgentMcpServerTest::new(&server.name);
+    let mut stderr_task = None;
+
+    let running = match &server.transport {
+        AgentMcpTransport::Stdio { command, .. } => match stdio_transport(server, command) {
+            Ok((transport, stderr)) => {
+                stderr_task = stderr.map(|stderr| tokio::spawn(collect_stderr(stderr)));
+                report.stage = AgentMcpTestStage::Initialize;
+                within(timeout, ().serve(transport)).await
+            }
+            Err(error) => Err(error),
+        },
+        AgentMcpTransport::StreamableHttp { url, headers, .. } => {
+            match http_transport(server, url, headers, None, timeout, Some(timeout)) {
+                Ok(transport) => {
+                    report.stage = AgentMcpTestStage::Initialize;
+                    within(timeout, ().serve(transport)).await
+                }
+                Err(error) => Err(error),
+            }
+        }
+        AgentMcpTransport::UnixSocket {
+            socket_path,
+            url,
+            headers,
+            ..
+        } => match http_transport(
+            server,
+            url,
+            headers,
+            Some(socket_path),
+            timeout,
+            Some(timeout),
+        ) {
+            Ok(transport) => {
+                report.stage = AgentMcpTestStage::Initialize;
+                within(timeout, ().serve(transport)).await
+            }
+            Err(error) => Err(error),
+        },
+        AgentMcpTransport::Sse { url, headers, .. } => match http_headers(server, headers) {
+            Ok(headers) => {
+                report.stage = AgentMcpTestStage::Initialize;
+                match LegacySseTransport::connect(url, headers, timeout).await {
+                    Ok(transport) => within(timeout, ().serve(transport)).await,
+                    Err(error) => Err(error.to_string()),
+                }
+            }
+            Err(error) => Err(error),
+        },
+    };
+    match running {
+        Ok(running) => {
+            report.initialize_ms = Some(elapsed_ms(started));
+            record_server_info(&mut report, &running);
+            report.stage = AgentMcpTestStage::ListTools;
+            let listing_started = Instant::now();
+            match list_tools(&mut report, &running, timeout).await {
+                Ok(()) => {
+                    report.list_tools_ms = Some(elapsed_ms(listing_started));
+                    report.stage = AgentMcpTestStage::Complete;
+                    report.success = true;
+                }
+                Err(error) => report.error = Some(error),
+            }
+            // Dropping the cancellation future on timeout also drops the
+            // RunningService, whose guard cancels the transport.
+            let _ = tokio::time::timeout(TEST_SHUTDOWN_TIMEOUT, running.cancel()).await;
+        }
+        Err(error) => report.error = Some(error),
+    }
+
+    if let Some(mut task) = stderr_task {
+        match tokio::time::timeout(STDERR_DRAIN_TIMEOUT, &mut task).await {
+            Ok(Ok(stderr)) => report.stderr = Some(stderr).filter(|stderr| !stderr.is_empty()),
+            Ok(Err(_)) => {}
+            Err(_) => task.abort(),
+        }
+    }
+    report.latency_ms = elapsed_ms(started);
+    report
+}
+
+pub(super) fn stdio_transport(
+    server: &AgentMcpServer,
+    command: &str,
+) -> Result<(TokioChildProcess, Option<tokio::process::ChildStderr>), String> {
+    let mut parts = split_mcp_command(command, &server.name)?;
+    if parts.is_empty() {
+        return Err(format!("MCP server '{}' requires a command", server.name));
+    }
+    let executable = parts.remove(0);
+    // Resolve and launch the way Goose does for session extensions so a
+    // passing check means the session can find the same executable.
+    let resolved = SearchPaths::builder()
+        .with_npm()
+        .resolve(&executable)
+        .map_err(|error| format!("Could not find {executable}: {error}"))?;
+    let mut child = tokio::process::Command::new(resolved);
+    child.args(parts).envs(environment(mcp_environment(server)));
+    if let Some(directory) = mcp_working_directory(server)? {
+        child.current_dir(directory);
+    }
+    if let Ok(path) = SearchPaths::builder().path() {
+        child.env("PATH", path);
+    }
+    #[cfg(unix)]
+    configure_subprocess(&mut child);
+    #[cfg(windows)]
+    child.creation_flags(CREATE_NO_WINDOW.0);
+    TokioChildProcess::builder(child)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Failed to start {executable}: {error}"))
+}
+
+/// `request_timeout` bounds each whole HTTP exchange, so only a one-shot probe
+/// sets it. A session keeps its server-to-client stream open indefinitely.
+pub(super) fn http_transport(
+    server: &AgentMcpServer,
+    url: &str,
+    headers: &[AgentMcpKeyValue],
+    socket_path: Option<&str>,
+    connect_timeout: Duration,
+    request_timeout: Option<Duration>,
+) -> Result<StreamableHttpClientTransport<reqwest::Client>, String> {
+    let custom_headers = http_headers(server, headers)?;
+    let builder = reqwest::Client::builder().connect_timeout(connect_timeout);
+    let builder = match request_timeout {
+        Some(request_timeout) => builder.timeout(request_timeout),
+        None => builder,
+    };
+    let builder = match socket_path {
+        #[cfg(unix)]
+        Some(socket_path) => builder.unix_socket(std::path::PathBuf::from(socket_path)),
+        #[cfg(not(unix))]
+        Some(_) => {
+            return Err(format!(
+                "MCP server '{}' uses a Unix socket, which this system does not support",
+                server.name
+            ))
+        }
+        None => builder,
+    };
+    let client = builder
+        .build()
+        .map_err(|error| format!("Could not create an HTTP client: {}", error_chain(&error)))?;
+    Ok(StreamableHttpClientTransport::with_client(
+        client,
+        StreamableHttpClientTransportConfig::with_uri(url.to_string())
+            .custom_headers(custom_headers),
+    ))
+}
+
+pub(super) fn http_headers(
+    server: &AgentMcpServer,
+    headers: &[AgentMcpKeyValue],
+) -> Result<HashMap<HeaderName, HeaderValue>, String> {
+    let mut custom_headers = HashMap::with_capacity(headers.len());
+    for header in headers {
+        let name = HeaderName::from_bytes(header.key.as_bytes()).map_err(|_| {
+            format!(
+                "MCP server '{}' has an invalid HTTP header name {}",
+                server.name, header.key
+            )
+        })?;
+        let value = HeaderValue::from_str(&header.value).map_err(|_| {
+            format!(
+                "MCP server '{}' has an invalid value for HTTP header {}",
+                server.name, header.key
+            )
+        })?;
+        custom_headers.insert(name, value);
+    }
+    Ok(custom_headers)
+}
+
+fn environment(entries: &[AgentMcpKeyValue]) -> HashMap<String, String> {
+    Envs::new(
+        entries
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect(),
+    )
+    .get_env()
+}
+
+fn record_server_info(report: &mut AgentMcpServerTest, running: &RunningService<RoleClient, ()>) {
+    let Some(info) = running.peer_info() else {
+        return;
+    };
+    report.protocol_version = serde_json::to_value(&info.protocol_version)
+        .ok()
+        .and_then(|version| version.as_str().map(str::to_string));
+    if let Some(implementation) = &info.server_info {
+        report.server_name = Some(implementation.name.clone());
+        report.server_version = Some(implementation.version.clone());
+    }
+}
+
+async fn list_tools(
+    report: &mut AgentMcpServerTest,
+    running: &RunningService<RoleClient, ()>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let mut cursor = None;
+    for _ in 0..MAX_TEST_TOOL_PAGES {
+        let page = within(
+            timeout,
+            running
+                .peer()
+                .list_tools(Some(PaginatedRequestParams::default().with_cursor(cursor))),
+        )
+        .await?;
+        report.tool_count += page.tools.len();
+        for tool in page.tools {
+            if report.tools.len() < MAX_TEST_TOOLS {
+                report.tools.push(AgentMcpTestTool {
+                    name: tool.name.to_string(),
+                    default_permission: AgentMcpToolPermissionLevel::default_for(
+                        tool.annotations.as_ref(),
+                    ),
+                });
+            } else {
+                report.tools_truncated = true;
+            }
+        }
+        match page.next_cursor.filter(|cursor| !cursor.is_empty()) {
+            Some(next) => cursor = Some(next),
+            None => return Ok(()),
+        }
+    }
+    report.tools_truncated = true;
+    Ok(())
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+async fn within<T, E>(
+    timeout: Duration,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, String>
+where
+    E: std::error::Error,
+{
+    match tokio::time::timeout(timeout, future).await {
+        Ok(result) => result.map_err(|error| error_chain(&error)),
+        Err(_) => Err(format!("Timed out after {} seconds", timeout.as_secs())),
+    }
+}
+
+/// Render an error with every source, since transport errors usually keep
+/// the useful detail (DNS, TLS, HTTP status) in the chain.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut rendered = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let cause_text = cause.to_string();
+        if !rendered.contains(&cause_text) {
+            rendered.push_str(": ");
+            rendered.push_str(&cause_text);
+        }
+        source = cause.source();
+    }
+    rendered
+}
+
+async fn collect_stderr(mut stderr: impl AsyncRead + Unpin) -> String {
+    let mut captured = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut truncated = false;
+    while let Ok(read) = stderr.read(&mut chunk).await {
+        if read == 0 {
+            break;
+        }
+        let retained = read.min(MAX_TEST_STDERR_BYTES.saturating_sub(captured.len()));
+        captured.extend_from_slice(&chunk[..retained]);
+        truncated |= retained < read;
+    }
+    let mut rendered = String::from_utf8_lossy(&captured).trim_end().to_string();
+    if truncated {
+        rendered.push_str("\n[stderr truncated]");
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_chain_keeps_distinct_sources() {
+        #[derive(Debug)]
+        struct Wrapped(std::io::Error);
+        impl std::fmt::Display for Wrapped {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("connect failed")
+            }
+        }
+        impl std::error::Error for Wrapped {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+        let wrapped = Wrapped(std::io::Error::other("dns lookup failed"));
+        assert_eq!(error_chain(&wrapped), "connect failed: dns lookup failed");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failed_stdio_start_reports_the_stage_and_full_stderr() {
+        let server = AgentMcpServer {
+            name: "Broken".to_string(),
+            description: String::new(),
+            enabled: true,
+            timeout_seconds: 5,
+            transport: AgentMcpTransport::Stdio {
+                command: "sh -c 'echo \"missing API key\" >&2; exit 3'".to_string(),
+                environment: Vec::new(),
+                working_directory: None,
+            },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
+        };
+
+        let report = test_mcp_server(&server).await;
+
+        assert!(!report.success);
+        assert_eq!(report.stage, AgentMcpTestStage::Initialize);
+        assert!(report.error.is_some());
+        assert_eq!(report.stderr.as_deref(), Some("missing API key"));
+        assert!(report.tools.is_empty());
+    }
+}
@@ -0,0 +1,528 @@
+//! MCP elicitation answered by the task's user.
+//!
+//! Goose surfaces a server's `elicitation/create` as an action-required
+//! message and waits for an elicitation response before the server's tool call
+//! continues. Maple registers each request for the run that owns it, shows it
+//! as a form in Desktop or forwards it to an ACP client, checks an accepted
+//! reply against the server's schema, and hands Goose the user's answer.
+//! Requests still pending when the run ends are cancelled so the server never
+//! waits on a form nobody can submit.
+//!
+//! Servers Maple connects itself elicit through its own client handler
+//! instead. Their requests join the same pending set and card flow, and the
+//! answer goes back to the waiting handler rather than to Goose.
+
+use super::AgentPermissionRouting;
+use goose::conversation::message::{ActionRequiredData, Message, MessageContent};
+use rmcp::model::ElicitationAction;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AgentElicitationRequest {
+    pub request_id: String,
+    pub message: String,
+    pub requested_schema: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentElicitationResponse {
+    pub session_id: String,
+    pub request_id: String,
+    pub action: String,
+    #[serde(default)]
+    pub content: Option<Value>,
+}
+
+/// The user's answer to an elicitation, with the validated content of an
+/// accepted reply.
+pub(super) type McpElicitationReply = (AgentElicitationAction, Option<Value>);
+
+/// The task an elicitation from a Maple-connected server is asked on.
+#[async_trait::async_trait]
+pub(super) trait McpElicitationHost: Send + Sync {
+    /// Ask the task's user and wait for the answer.
+    async fn elicit(
+        &self,
+        server: &str,
+        message: String,
+        requested_schema: Value,
+    ) -> Result<McpElicitationReply, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AgentElicitationAction {
+    Accept,
+    Decline,
+    Cancel,
+}
+
+impl AgentElicitationAction {
+    pub(super) fn parse(action: &str) -> Result<Self, String> {
+        match action {
+            "accept" => Ok(Self::Accept),
+            "decline" => Ok(Self::Decline),
+            "cancel" => Ok(Self::Cancel),
+            other => Err(format!("Unknown Agent input response: {other}")),
+        }
+    }
+
+    pub(super) fn status(self) -> &'static str {
+        match self {
+            Self::Accept => "accepted",
+            Self::Decline => "declined",
+            Self::Cancel => "cancelled",
+        }
+    }
+
+    pub(super) fn mcp_action(self) -> ElicitationAction {
+        match self {
+            Self::Accept => ElicitationAction::Accept,
+            Self::Decline => ElicitationAction::Decline,
+            Self::Cancel => ElicitationAction::Cancel,
+        }
+    }
+}
+
+/// Elicitation requests carried by one Goose message, keyed by request ID.
+/// Ambiguous or empty IDs are dropped; their cards are cancelled instead.
+pub(super) fn elicitation_requests(message: &Message) -> HashMap<String, AgentElicitationRequest> {
+    let mut requests = HashMap::new();
+    let mut duplicates = Vec::new();
+    for content in &message.content {
+        let MessageContent::ActionRequired(action) = content else {
+            continue;
+        };
+        let ActionRequiredData::Elicitation {
+            id,
+            message,
+            requested_schema,
+        } = &action.data
+        else {
+            continue;
+        };
+        if id.trim().is_empty() {
+            continue;
+        }
+        let request = AgentElicitationRequest {
+            request_id: id.clone(),
+            message: message.clone(),
+            requested_schema: requested_schema.clone(),
+        };
+        if requests.insert(id.clone(), request).is_some() {
+            duplicates.push(id.clone());
+        }
+    }
+    for id in duplicates {
+        requests.remove(&id);
+    }
+    requests
+}
+
+/// Check an accepted reply against the server's requested schema.
+///
+/// MCP restricts elicitation schemas to a flat object of primitive
+/// properties, so anything else is rejected rather than passed through.
+pub(super) fn validate_elicitation_content(
+    schema: &Value,
+    content: Option<&Value>,
+) -> Result<Value, String> {
+    let schema = schema
+        .as_object()
+        .ok_or_else(|| "The requested input schema is not an object".to_string())?;
+    if schema
+        .get("type")
+        .is_some_and(|kind| kind != &Value::String("object".to_string()))
+    {
+        return Err("The requested input schema must describe an object".to_string());
+    }
+    let empty = Map::new();
+    let properties = match schema.get("properties") {
+        Some(Value::Object(properties)) => properties,
+        Some(_) => return Err("The requested input schema has invalid properties".to_string()),
+        None => &empty,
+    };
+    let content = match content {
+        None | Some(Value::Null) => &empty,
+        Some(Value::Object(content)) => content,
+        Some(_) => return Err("The input response must be an object".to_string()),
+    };
+    if let Some(name) = content.keys().find(|name| !properties.contains_key(*name)) {
+        return Err(format!("'{name}' is not a requested input field"));
+    }
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !content.contains_key(name) {
+                return Err(format!("'{}' is required", field_label(properties, name)));
+            }
+        }
+    }
+    for (name, value) in content {
+        validate_field(field_label(properties, name), &properties[name], value)?;
+    }
+    Ok(Value::Object(content.clone()))
+}
+
+fn field_label<'a>(properties: &'a Map<String, Value>, name: &'a str) -> &'a str {
+    properties
+        .get(name)
+        .and_then(|property| property.get("title"))
+        .and_then(Value::as_str)
+        .unwrap_or(name)
+}
+
+fn validate_field(label: &str, property: &Value, value: &Value) -> Result<(), String> {
+    let bound = |key: &str| property.get(key).and_then(Value::as_f64);
+    match property.get("type").and_then(Value::as_str) {
+        Some("string") => {
+            let text = value
+                .as_str()
+                .ok_or_else(|| format!("'{label}' must be text"))?;
+            if let Some(Value::Array(options)) = property.get("enum") {
+                if !options.iter().any(|option| option.as_str() == Some(text)) {
+                    return Err(format!("'{label}' must be one of the offered choices"));
+                }
+            }
+            let length = text.chars().count() as f64;
+            if bound("minLength").is_some_and(|min| length < min) {
+                return Err(format!("'{label}' is too short"));
+            }
+            if bound("maxLength").is_some_and(|max| length > max) {
+                return Err(format!("'{label}' is too long"));
+            }
+            let valid_format = match property.get("format").and_then(Value::as_str) {
+                Some("email") => text
+                    .split_once('@')
+                    .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.')),
+                Some("uri") => reqwest::Url::parse(text).is_ok(),
+                Some("date") => chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+                Some("date-time") => chrono::DateTime::parse_from_rfc3339(text).is_ok(),
+                _ => true,
+            };
+            if !valid_format {
+                return Err(format!("'{label}' is not in the requested format"));
+            }
+        }
+        Some(kind @ ("number" | "integer")) => {
+            let number = value
+                .as_f64()
+                .filter(|number| kind == "number" || number.fract() == 0.0)
+                .ok_or_else(|| {
+                    if kind == "integer" {
+                        format!("'{label}' must be a whole number")
+                    } else {
+                        format!("'{label}' must be a number")
+                    }
+                })?;
+            if bound("minimum").is_some_and(|min| number < min) {
+                return Err(format!("'{label}' is below the minimum"));
+            }
+            if bound("maximum").is_some_and(|max| number > max) {
+                return Err(format!("'{label}' is above the maximum"));
+            }
+        }
+        Some("boolean") => {
+            if !value.is_boolean() {
+                return Err(format!("'{label}' must be true or false"));
+            }
+        }
+        _ => return Err(format!("'{label}' has an unsupported input type")),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct PendingAgentElicitation {
+    pub(super) run_id: String,
+    pub(super) routing: AgentPermissionRouting,
+    pub(super) request: AgentElicitationRequest,
+}
+
+type PendingElicitationKey = (String, String);
+
+/// Elicitations waiting on a user, keyed by session and request ID.
+#[derive(Clone, Default)]
+pub(super) struct PendingElicitations {
+    inner: Arc<Mutex<HashMap<PendingElicitationKey, PendingAgentElicitation>>>,
+    /// Answer channels for requests from servers Maple connects itself.
+    replies: Arc<Mutex<HashMap<PendingElicitationKey, oneshot::Sender<McpElicitationReply>>>>,
+}
+
+impl PendingElicitations {
+    /// Returns false when the request ID is already pending, in which case
+    /// the earlier registration keeps ownership.
+    pub(super) async fn register(
+        &self,
+        session_id: &str,
+        run_id: &str,
+        routing: AgentPermissionRouting,
+        request: AgentElicitationRequest,
+    ) -> bool {
+        let mut pending = self.inner.lock().await;
+        let key = (session_id.to_string(), request.request_id.clone());
+        if pending.contains_key(&key) {
+            return false;
+        }
+        pending.insert(
+            key,
+            PendingAgentElicitation {
+                run_id: run_id.to_string(),
+                routing,
+                request,
+            },
+        );
+        true
+    }
+
+    /// Register a request whose answer goes back to a Maple client handler.
+    /// Returns `None` when the request ID is already pending.
+    pub(super) async fn register_with_reply(
+        &self,
+        session_id: &str,
+        run_id: &str,
+        routing: AgentPermissionRouting,
+        request: AgentElicitationRequest,
+    ) -> Option<oneshot::Receiver<McpElicitationReply>> {
+        let key = (session_id.to_string(), request.request_id.clone());
+        let (sender, receiver) = oneshot::channel();
+        // Hold the reply map first so an answer cannot arrive in between.
+        let mut replies = self.replies.lock().await;
+        if !self.register(session_id, run_id, routing, request).await {
+            return None;
+        }
+        replies.insert(key, sender);
+        Some(receiver)
+    }
+
+    /// Hand an answer to the Maple client handler waiting on a request.
+    /// Returns false when the request came from Goose, which the caller then
+    /// answers instead.
+    pub(super) async fn reply(
+        &self,
+        session_id: &str,
+        request_id: &str,
+        action: AgentElicitationAction,
+        content: Option<Value>,
+    ) -> bool {
+        match self
+            .replies
+            .lock()
+            .await
+            .remove(&(session_id.to_string(), request_id.to_string()))
+        {
+            Some(sender) => {
+                let _ = sender.send((action, content));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop a Maple client handler's request once it stops waiting.
+    pub(super) async fn forget(&self, session_id: &str, request_id: &str) {
+        let key = (session_id.to_string(), request_id.to_string());
+        self.replies.lock().await.remove(&key);
+        self.inner.lock().await.remove(&key);
+    }
+
+    pub(super) async fn get(
+        &self,
+        session_id: &str,
+        request_id: &str,
+    ) -> Option<PendingAgentElicitation> {
+        self.inner
+            .lock()
+            .await
+            .get(&(session_id.to_string(), request_id.to_string()))
+            .cloned()
+    }
+
+    pub(super) async fn take(
+        &self,
+        session_id: &str,
+        request_id: &str,
+    ) -> Option<PendingAgentElicitation> {
+        self.inner
+            .lock()
+            .await
+            .remove(&(session_id.to_string(), request_id.to_string()))
+    }
+
+    pub(super) async fn take_for_runs(
+        &self,
+        run_ids: &[String],
+    ) -> Vec<(PendingElicitationKey, PendingAgentElicitation)> {
+        let mut pending = self.inner.lock().await;
+        let keys = pending
+            .iter()
+            .filter(|(_, elicitation)| run_ids.contains(&elicitation.run_id))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|key| pending.remove(&key).map(|elicitation| (key, elicitation)))
+            .collect()
+    }
+
+    /// Surface routing for each pending request in `session_id`.
+    pub(super) async fn routes(&self, session_id: &str) -> Vec<(String, AgentPermissionRouting)> {
+        self.inner
+            .lock()
+            .await
+            .iter()
+            .filter(|((pending_session_id, _), _)| pending_session_id == session_id)
+            .map(|((_, request_id), elicitation)| (request_id.clone(), elicitation.routing))
+            .collect()
+    }
+
+    /// Drop every request. Waiting Maple client handlers see a cancellation.
+    pub(super) async fn clear(&self) {
+        self.replies.lock().await.clear();
+        self.inner.lock().await.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "title": "Name", "minLength": 1, "maxLength": 8 },
+                "plan": { "type": "string", "enum": ["free", "pro"] },
+                "seats": { "type": "integer", "minimum": 1, "maximum": 10 },
+                "email": { "type": "string", "format": "email" },
+                "notify": { "type": "boolean" }
+            },
+            "required": ["name"]
+        })
+    }
+
+    #[test]
+    fn accepted_replies_must_match_the_requested_schema() {
+        let accepted = json!({ "name": "Ada", "plan": "pro", "seats": 3, "notify": true });
+        assert_eq!(
+            validate_elicitation_content(&schema(), Some(&accepted)),
+            Ok(accepted)
+        );
+
+        for (reply, error) in [
+            (json!({}), "'Name' is required"),
+            (json!({ "name": "" }), "'Name' is too short"),
+            (json!({ "name": "Ada Lovelace" }), "'Name' is too long"),
+            (
+                json!({ "name": "Ada", "plan": "team" }),
+                "'plan' must be one of the offered choices",
+            ),
+            (
+                json!({ "name": "Ada", "seats": 2.5 }),
+                "'seats' must be a whole number",
+            ),
+            (
+                json!({ "name": "Ada", "seats": 11 }),
+                "'seats' is above the maximum",
+            ),
+            (
+                json!({ "name": "Ada", "email": "ada" }),
+                "'email' is not in the requested format",
+            ),
+            (
+                json!({ "name": "Ada", "notify": "yes" }),
+                "'notify' must be true or false",
+            ),
+            (
+                json!({ "name": "Ada", "role": "admin" }),
+                "'role' is not a requested input field",
+            ),
+        ] {
+            assert_eq!(
+                validate_elicitation_content(&schema(), Some(&reply)),
+                Err(error.to_string())
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pending_elicitations_belong_to_their_run() {
+        let pending = PendingElicitations::default();
+        let request = AgentElicitationRequest {
+            request_id: "input-1".to_string(),
+            message: "Which plan?".to_string(),
+            requested_schema: schema(),
+        };
+        assert!(
+            pending
+                .register(
+                    "task",
+                    "run-1",
+                    AgentPermissionRouting::Desktop,
+                    request.clone()
+                )
+                .await
+        );
+        assert!(
+            !pending
+                .register("task", "run-2", AgentPermissionRouting::Desktop, request)
+                .await
+        );
+        assert!(pending
+            .take_for_runs(&["run-2".to_string()])
+            .await
+            .is_empty());
+        let taken = pending.take_for_runs(&["run-1".to_string()]).await;
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].1.run_id, "run-1");
+        assert!(pending.routes("task").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn maple_client_requests_are_answered_through_their_handler() {
+        let pending = PendingElicitations::default();
+        let request = AgentElicitationRequest {
+            request_id: "input-1".to_string(),
+            message: "Which plan?".to_string(),
+            requested_schema: schema(),
+        };
+        let reply = pending
+            .register_with_reply(
+                "task",
+                "run-1",
+                AgentPermissionRouting::Desktop,
+                request.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(pending
+            .register_with_reply("task", "run-1", AgentPermissionRouting::Desktop, request)
+            .await
+            .is_none());
+        assert!(
+            !pending
+                .reply("task", "goose-input", AgentElicitationAction::Cancel, None)
+                .await
+        );
+
+        let content = json!({ "name": "Ada" });
+        assert!(pending.take("task", "input-1").await.is_some());
+        assert!(
+            pending
+                .reply(
+                    "task",
+                    "input-1",
+                    AgentElicitationAction::Accept,
+                    Some(content.clone())
+                )
+                .await
+        );
+        assert_eq!(
+            reply.await.unwrap(),
+            (AgentElicitationAction::Accept, Some(content))
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Liveness checks and reconnection for a session's user MCP servers.
+//!
+//! While a run is active Maple periodically lists each connected server's
+//! tools. A server whose transport has closed, such as a stdio process that
+//! exited, is reconnected from the session's frozen extension snapshot with
+//! bounded exponential backoff. A probe that times out or gets a protocol
+//! error leaves the server alone: it may be busy with a long tool call, and
+//! replacing it would kill that call. Outcomes are recorded per session for
+//! the task's MCP server list and announced on the run's timeline.
+
+use super::{bounded_timeline_text, AgentRunEvent, AgentRunEventPublisher, MAX_AGENT_ERROR_CHARS};
+use goose::agents::mcp_client::Error as McpError;
+use goose::agents::{Agent, ExtensionConfig};
+use rmcp::ServiceError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+const MCP_HEALTH_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+const MCP_HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+const MCP_RECONNECT_ATTEMPTS: u32 = 5;
+const MCP_RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const MCP_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentMcpHealthStatus {
+    Connected,
+    Reconnecting,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpServerHealth {
+    pub status: AgentMcpHealthStatus,
+    /// Reconnection attempts made since the server last stopped responding.
+    pub attempts: u32,
+    /// Why the server last stopped responding or failed to reconnect.
+    pub last_error: Option<String>,
+}
+
+impl Default for AgentMcpServerHealth {
+    fn default() -> Self {
+        Self {
+            status: AgentMcpHealthStatus::Connected,
+            attempts: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Health of the servers that have had trouble, keyed by session and then by
+/// Goose extension key. A server without an entry is reported as connected.
+#[derive(Clone, Default)]
+pub(super) struct McpHealthRegistry {
+    inner: Arc<Mutex<HashMap<String, HashMap<String, AgentMcpServerHealth>>>>,
+}
+
+impl McpHealthRegistry {
+    pub(super) async fn session(&self, session_id: &str) -> HashMap<String, AgentMcpServerHealth> {
+        self.inner
+            .lock()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    async fn record(&self, session_id: &str, key: &str, health: AgentMcpServerHealth) {
+        self.inner
+            .lock()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .insert(key.to_string(), health);
+    }
+
+    /// Forget a server after the user reconnects or disconnects it.
+    pub(super) async fn forget(&self, session_id: &str, key: &str) {
+        let mut sessions = self.inner.lock().await;
+        if let Some(servers) = sessions.get_mut(session_id) {
+            servers.remove(key);
+            if servers.is_empty() {
+                sessions.remove(session_id);
+            }
+        }
+    }
+
+    pub(super) async fn forget_session(&self, session_id: &str) {
+        self.inner.lock().await.remove(session_id);
+    }
+
+    pub(super) async fn clear(&self) {
+        self.inner.lock().await.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct McpHealthTiming {
+    probe_interval: Duration,
+    probe_timeout: Duration,
+    reconnect_base_delay: Duration,
+    reconnect_max_delay: Duration,
+}
+
+impl Default for McpHealthTiming {
+    fn default() -> Self {
+        Self {
+            probe_interval: MCP_HEALTH_PROBE_INTERVAL,
+            probe_timeout: MCP_HEALTH_PROBE_TIMEOUT,
+            reconnect_base_delay: MCP_RECONNECT_BASE_DELAY,
+            reconnect_max_delay: MCP_RECONNECT_MAX_DELAY,
+        }
+    }
+}
+
+impl McpHealthTiming {
+    /// Delay before reconnection attempt `attempt`, counting from one.
+    fn reconnect_delay(&self, attempt: u32) -> Duration {
+        self.reconnect_base_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.reconnect_max_delay)
+    }
+}
+
+/// Whether a failed probe means the server's connection is gone.
+fn is_disconnected(error: &McpError) -> bool {
+    matches!(
+        error,
+        ServiceError::TransportClosed | ServiceError::TransportSend(_)
+    )
+}
+
+pub(super) struct McpHealthMonitor<R> {
+    pub(super) agent: Arc<Agent>,
+    pub(super) session_id: String,
+    /// The session's user MCP extensions as frozen when the run started.
+    pub(super) snapshot: Vec<ExtensionConfig>,
+    pub(super) registry: McpHealthRegistry,
+    pub(super) events: AgentRunEventPublisher,
+    /// Replaces the live client for one extension from its snapshot config.
+    pub(super) reconnect: R,
+    pub(super) timing: McpHealthTiming,
+    pub(super) stop: CancellationToken,
+}
+
+impl<R, F> McpHealthMonitor<R>
+where
+    R: Fn(ExtensionConfig) -> F + Send,
+    F: Future<Output = Result<(), String>> + Send,
+{
+    pub(super) async fn run(self) {
+        if self.snapshot.is_empty() {
+            return;
+        }
+        loop {
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = tokio::time::sleep(self.timing.probe_interval) => {}
+            }
+            for config in &self.snapshot {
+                if self.stop.is_cancelled() {
+                    return;
+                }
+                let key = config.key();
+                // A server that is not live either failed to restore, which
+                // the run already reported, or already exhausted its retries.
+                let Some(client) = self.agent.extension_manager.get_mcp_client(&key).await else {
+                    continue;
+                };
+                let probe = tokio::time::timeout(
+                    self.timing.probe_timeout,
+                    client.list_tools(&self.session_id, None, self.stop.child_token()),
+                )
+                .await;
+                // A slow answer usually means a serial server is still busy
+                // with a tool call, so only a closed connection is recovered.
+                let error = match probe {
+                    Ok(Err(error)) if is_disconnected(&error) => error.to_string(),
+                    Ok(_) | Err(_) => continue,
+                };
+                drop(client);
+                self.recover(config, &key, error).await;
+            }
+        }
+    }
+
+    async fn recover(&self, config: &ExtensionConfig, key: &str, error: String) {
+        let name = config.name();
+        self.warn(format!(
+            "MCP server '{name}' disconnected ({error}). Reconnecting."
+        ))
+        .await;
+        let mut last_error = error;
+        for attempt in 1..=MCP_RECONNECT_ATTEMPTS {
+            self.registry
+                .record(
+                    &self.session_id,
+                    key,
+                    AgentMcpServerHealth {
+                        status: AgentMcpHealthStatus::Reconnecting,
+                        attempts: attempt,
+                        last_error: Some(last_error.clone()),
+                    },
+                )
+                .await;
+            tokio::select! {
+                _ = self.stop.cancelled() => return,
+                _ = tokio::time::sleep(self.timing.reconnect_delay(attempt)) => {}
+            }
+            match (self.reconnect)(config.clone()).await {
+                Ok(()) => {
+                    self.registry
+                        .record(
+                            &self.session_id,
+                            key,
+                            AgentMcpServerHealth {
+                                status: AgentMcpHealthStatus::Connected,
+                                attempts: attempt,
+                                last_error: Some(last_error),
+                            },
+                        )
+                        .await;
+                    self.warn(format!("MCP server '{name}' reconnected.")).await;
+                    return;
+                }
+                Err(error) => last_error = error,
+            }
+        }
+        self.registry
+            .record(
+                &self.session_id,
+                key,
+                AgentMcpServerHealth {
+                    status: AgentMcpHealthStatus::Failed,
+                    attempts: MCP_RECONNECT_ATTEMPTS,
+                    last_error: Some(last_error.clone()),
+                },
+            )
+            .await;
+        self.warn(format!(
+            "MCP server '{name}' could not be reconnected after {MCP_RECONNECT_ATTEMPTS} attempts \
+             ({last_error}). Turn it off and on again to retry."
+        ))
+        .await;
+    }
+
+    async fn warn(&self, message: String) {
+        self.events
+            .publish(AgentRunEvent::SetupWarning(bounded_timeline_text(
+                &message,
+                MAX_AGENT_ERROR_CHARS,
+            )))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        mcp_server_to_extension, session_manager_for_account_dir, AgentEventDispatcher,
+        AgentEventSink, AgentHostEventPolicy, AgentMcpServer, AgentMcpTransport, AgentServiceEvent,
+    };
+    use super::*;
+    use goose::agents::mcp_client::McpClientTrait;
+    use goose::agents::{AgentConfig as GooseAgentConfig, GoosePlatform, ToolCallContext};
+    use goose::config::{GooseMode, PermissionManager};
+    use rmcp::model::{
+        CallToolResult, InitializeResult, JsonObject, ListToolsResult, ServerNotification,
+    };
+    use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+    use tokio::sync::mpsc;
+
+    const PROBE_HEALTHY: u8 = 0;
+    const PROBE_BUSY: u8 = 1;
+    const PROBE_REFUSED: u8 = 2;
+    const PROBE_CLOSED: u8 = 3;
+
+    struct ProbedClient {
+        probe: Arc<AtomicU8>,
+    }
+
+    #[async_trait::async_trait]
+    impl McpClientTrait for ProbedClient {
+        async fn list_tools(
+            &self,
+            _session_id: &str,
+            _next_cursor: Option<String>,
+            _cancel_token: CancellationToken,
+        ) -> Result<ListToolsResult, McpError> {
+            match self.probe.load(Ordering::SeqCst) {
+                PROBE_BUSY => {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(ListToolsResult::default())
+                }
+                PROBE_REFUSED => Err(ServiceError::UnexpectedResponse),
+                PROBE_CLOSED => Err(ServiceError::TransportClosed),
+                _ => Ok(ListToolsResult::default()),
+            }
+        }
+
+        async fn call_tool(
+            &self,
+            _context: &ToolCallContext,
+            _name: &str,
+            _arguments: Option<JsonObject>,
+            _cancel_token: CancellationToken,
+        ) -> Result<CallToolResult, McpError> {
+            Err(ServiceError::UnexpectedResponse)
+        }
+
+        fn get_info(&self) -> Option<&InitializeResult> {
+            None
+        }
+
+        async fn subscribe(&self) -> mpsc::Receiver<ServerNotification> {
+            mpsc::channel(1).1
+        }
+    }
+
+    struct NoopSink;
+
+    impl AgentEventSink for NoopSink {
+        fn emit(&self, _event: &AgentServiceEvent) {}
+    }
+
+    async fn wait_for(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("condition was not met in time");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn only_a_closed_connection_is_reconnected() {
+        let temp = tempfile::tempdir().unwrap();
+        let session_manager = session_manager_for_account_dir(temp.path()).unwrap();
+        let permission_manager = Arc::new(PermissionManager::new(temp.path().join("permissions")));
+        let agent = Arc::new(Agent::with_config(GooseAgentConfig::new(
+            session_manager,
+            permission_manager,
+            None,
+            GooseMode::SmartApprove,
+            true,
+            GoosePlatform::GooseDesktop,
+        )));
+        let config = mcp_server_to_extension(&AgentMcpServer {
+            name: "slow".to_string(),
+            description: "Serial server".to_string(),
+            enabled: true,
+            timeout_seconds: 30,
+            transport: AgentMcpTransport::Stdio {
+                command: "slow-server".to_string(),
+                environment: Vec::new(),
+                working_directory: None,
+            },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
+        })
+        .unwrap();
+        let probe = Arc::new(AtomicU8::new(PROBE_BUSY));
+        agent
+            .extension_manager
+            .add_client(
+                config.key(),
+                config.clone(),
+                Arc::new(ProbedClient {
+                    probe: Arc::clone(&probe),
+                }),
+                None,
+                None,
+            )
+            .await;
+        let (events, _events_rx) = AgentRunEventPublisher::new(
+            AgentEventDispatcher::new(Arc::new(NoopSink)),
+            "task".to_string(),
+            "run".to_string(),
+            AgentHostEventPolicy::Suppress,
+        );
+        let registry = McpHealthRegistry::default();
+        let reconnects = Arc::new(AtomicU32::new(0));
+        let stop = CancellationToken::new();
+        let monitor = McpHealthMonitor {
+            agent,
+            session_id: "task".to_string(),
+            snapshot: vec![config.clone()],
+            registry: registry.clone(),
+            events,
+            reconnect: {
+                let reconnects = Arc::clone(&reconnects);
+                let probe = Arc::clone(&probe);
+                move |reconnected: ExtensionConfig| {
+                    assert_eq!(reconnected.key(), "slow");
+                    reconnects.fetch_add(1, Ordering::SeqCst);
+                    probe.store(PROBE_HEALTHY, Ordering::SeqCst);
+                    async { Ok(()) }
+                }
+            },
+            timing: McpHealthTiming {
+                probe_interval: Duration::from_millis(10),
+                probe_timeout: Duration::from_millis(20),
+                reconnect_base_delay: Duration::from_millis(1),
+                reconnect_max_delay: Duration::from_millis(1),
+            },
+            stop: stop.clone(),
+        };
+        let task = tokio::spawn(monitor.run());
+
+        // Busy and refusing servers sit through several probe rounds untouched.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        probe.store(PROBE_REFUSED, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(reconnects.load(Ordering::SeqCst), 0);
+        assert!(registry.session("task").await.is_empty());
+
+        probe.store(PROBE_CLOSED, Ordering::SeqCst);
+        wait_for(|| reconnects.load(Ordering::SeqCst) == 1).await;
+        stop.cancel();
+        task.await.unwrap();
+        let health = registry.session("task").await.remove("slow").unwrap();
+        assert_eq!(health.status, AgentMcpHealthStatus::Connected);
+        assert_eq!(health.attempts, 1);
+        assert!(health.last_error.unwrap().to_lowercase().contains("closed"));
+    }
+
+    #[test]
+    fn reconnect_delays_double_up_to_the_cap() {
+        let timing = McpHealthTiming::default();
+        let delays = (1..=7)
+            .map(|attempt| timing.reconnect_delay(attempt))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 16, 30, 30].map(Duration::from_secs).to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn registry_reports_only_sessions_with_trouble() {
+        let registry = McpHealthRegistry::default();
+        assert!(registry.session("task").await.is_empty());
+        registry
+            .record(
+                "task",
+                "github",
+                AgentMcpServerHealth {
+                    status: AgentMcpHealthStatus::Failed,
+                    attempts: MCP_RECONNECT_ATTEMPTS,
+                    last_error: Some("Transport closed".to_string()),
+                },
+            )
+            .await;
+        assert_eq!(
+            registry.session("task").await["github"].status,
+            AgentMcpHealthStatus::Failed
+        );
+        registry.forget("task", "github").await;
+        assert!(registry.inner.lock().await.is_empty());
+    }
+}
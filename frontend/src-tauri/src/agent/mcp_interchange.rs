@@ -0,0 +1,505 @@
+//! Import and export of MCP servers in the `mcpServers` JSON shape shared by
+//! Claude Desktop, Cursor and similar clients.
+//!
+//! Each entry is either a launched command (`command`, `args`, `env`, `cwd`)
+//! or a remote endpoint (`url`, `headers`, optional `type`, and `socket` for
+//! one served on a Unix domain socket). An import is always
+//! planned first: the report says what would be added, replaced or refused,
+//! and only a non-dry-run import with something to add is saved.
+
+use super::{
+    maple_reserved_extension_key, normalize_mcp_servers, split_mcp_command, AgentMcpKeyValue,
+    AgentMcpServer, AgentMcpTransport, DEFAULT_MCP_TIMEOUT_SECONDS,
+};
+use goose::agents::extension::Envs;
+use goose::config::extensions::name_to_key;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const MAX_MCP_IMPORT_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpImportRequest {
+    pub json: String,
+    /// Plan the import and report it without saving anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Replace a configured server that has the same name instead of
+    /// skipping the imported one.
+    #[serde(default)]
+    pub replace_existing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentMcpImportStatus {
+    Added,
+    Replaced,
+    Collision,
+    Reserved,
+    RejectedEnvironment,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpImportEntry {
+    pub name: String,
+    pub status: AgentMcpImportStatus,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpImportReport {
+    pub entries: Vec<AgentMcpImportEntry>,
+    /// The server list after the import; for a dry run, the list that would
+    /// be saved.
+    pub servers: Vec<AgentMcpServer>,
+    pub saved: bool,
+}
+
+impl AgentMcpImportReport {
+    pub(super) fn changes_servers(&self) -> bool {
+        self.entries.iter().any(|entry| {
+            matches!(
+                entry.status,
+                AgentMcpImportStatus::Added | AgentMcpImportStatus::Replaced
+            )
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct McpServerEntry {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    socket: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    disabled: bool,
+}
+
+/// Render `servers` as an `mcpServers` document. Tool allowlists and block
+/// lists have no place in the shared shape and are left out.
+pub(super) fn export_mcp_servers(servers: &[AgentMcpServer]) -> Result<String, String> {
+    let mut entries = Map::new();
+    for server in servers {
+        let env = key_values_to_map(match &server.transport {
+            AgentMcpTransport::Stdio { environment, .. }
+            | AgentMcpTransport::StreamableHttp { environment, .. }
+            | AgentMcpTransport::Sse { environment, .. }
+            | AgentMcpTransport::UnixSocket { environment, .. } => environment,
+        });
+        let entry = match &server.transport {
+            AgentMcpTransport::Stdio {
+                command,
+                working_directory,
+                ..
+            } => {
+                let mut parts = split_mcp_command(command, &server.name)?;
+                if parts.is_empty() {
+                    return Err(format!("MCP server '{}' requires a command", server.name));
+                }
+                McpServerEntry {
+                    command: Some(parts.remove(0)),
+                    args: parts,
+                    env,
+                    cwd: working_directory.clone(),
+                    disabled: !server.enabled,
+                    ..McpServerEntry::default()
+                }
+            }
+            AgentMcpTransport::StreamableHttp { url, headers, .. }
+            | AgentMcpTransport::Sse { url, headers, .. } => McpServerEntry {
+                kind: Some(
+                    match server.transport {
+                        AgentMcpTransport::Sse { .. } => "sse",
+                        _ => "http",
+                    }
+                    .to_string(),
+                ),
+                url: Some(url.clone()),
+                headers: key_values_to_map(headers),
+                env,
+                disabled: !server.enabled,
+                ..McpServerEntry::default()
+            },
+            AgentMcpTransport::UnixSocket {
+                socket_path,
+                url,
+                headers,
+                ..
+            } => McpServerEntry {
+                kind: Some("http".to_string()),
+                url: Some(url.clone()),
+                socket: Some(socket_path.clone()),
+                headers: key_values_to_map(headers),
+                env,
+                disabled: !server.enabled,
+                ..McpServerEntry::default()
+            },
+        };
+        entries.insert(
+            server.name.clone(),
+            serde_json::to_value(entry).map_err(|error| error.to_string())?,
+        );
+    }
+    serde_json::to_string_pretty(&serde_json::json!({ "mcpServers": entries }))
+        .map_err(|error| format!("Failed to export MCP servers: {error}"))
+}
+
+/// Work out what importing `json` into `existing` would do.
+pub(super) fn plan_mcp_import(
+    json: &str,
+    existing: &[AgentMcpServer],
+    replace_existing: bool,
+) -> Result<AgentMcpImportReport, String> {
+    if json.len() > MAX_MCP_IMPORT_BYTES {
+        return Err("The MCP configuration is larger than 1 MB".to_string());
+    }
+    let document: Value = serde_json::from_str(json)
+        .map_err(|error| format!("The MCP configuration is not valid JSON: {error}"))?;
+    let Some(Value::Object(imported)) = document.get("mcpServers") else {
+        return Err("The MCP configuration has no \"mcpServers\" object".to_string());
+    };
+
+    let mut servers = existing.to_vec();
+    let existing_keys = existing
+        .iter()
+        .map(|server| name_to_key(&server.name))
+        .collect::<HashSet<_>>();
+    let mut imported_keys = HashSet::new();
+    let mut entries = Vec::with_capacity(imported.len());
+    for (name, value) in imported {
+        let name = name.trim().to_string();
+        let key = name_to_key(&name);
+        let (status, detail) = if maple_reserved_extension_key(&key) {
+            (
+                AgentMcpImportStatus::Reserved,
+                Some("This name is reserved by Maple".to_string()),
+            )
+        } else if !key.is_empty() && !imported_keys.insert(key.clone()) {
+            (
+                AgentMcpImportStatus::Collision,
+                Some("Another imported server has the same name".to_string()),
+            )
+        } else if existing_keys.contains(&key) && !replace_existing {
+            (
+                AgentMcpImportStatus::Collision,
+                Some("A configured server already has this name".to_string()),
+            )
+        } else {
+            match import_entry(&name, value) {
+                Ok(server) => {
+                    match servers
+                        .iter_mut()
+                        .find(|existing| name_to_key(&existing.name) == key)
+                    {
+                        Some(slot) => {
+                            *slot = server;
+                            (AgentMcpImportStatus::Replaced, None)
+                        }
+                        None => {
+                            servers.push(server);
+                            (AgentMcpImportStatus::Added, None)
+                        }
+                    }
+                }
+                Err(rejection) => rejection,
+            }
+        };
+        entries.push(AgentMcpImportEntry {
+            name,
+            status,
+            detail,
+        });
+    }
+
+    Ok(AgentMcpImportReport {
+        entries,
+        servers: normalize_mcp_servers(servers)?,
+        saved: false,
+    })
+}
+
+type ImportRejection = (AgentMcpImportStatus, Option<String>);
+
+fn import_entry(name: &str, value: &Value) -> Result<AgentMcpServer, ImportRejection> {
+    let invalid = |detail: String| (AgentMcpImportStatus::Invalid, Some(detail));
+    let entry = McpServerEntry::deserialize(value)
+        .map_err(|error| invalid(format!("Unrecognized server entry: {error}")))?;
+
+    let rejected = entry
+        .env
+        .iter()
+        .filter(|(key, value)| {
+            !Envs::new(HashMap::from([((*key).clone(), (*value).clone())]))
+                .get_env()
+                .contains_key(*key)
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    if !rejected.is_empty() {
+        return Err((
+            AgentMcpImportStatus::RejectedEnvironment,
+            Some(format!(
+                "Maple does not let MCP servers override {}",
+                rejected.join(", ")
+            )),
+        ));
+    }
+
+    let environment = map_to_key_values(&entry.env);
+    let transport = match (&entry.command, &entry.url) {
+        (Some(_), Some(_)) => {
+            return Err(invalid(
+                "A server cannot have both a command and a URL".to_string(),
+            ))
+        }
+        (None, None) => return Err(invalid("A server needs a command or a URL".to_string())),
+        (Some(command), None) => {
+            if !matches!(entry.kind.as_deref(), None | Some("stdio")) {
+                return Err(invalid(format!(
+                    "A command cannot use the {} transport",
+                    entry.kind.as_deref().unwrap_or_default()
+                )));
+            }
+            let line = command_line(command, &entry.args);
+            // The saved form is one command line; refuse arguments that would
+            // not split back into exactly what was imported.
+            let expected = std::iter::once(command.clone())
+                .chain(entry.args.iter().cloned())
+                .collect::<Vec<_>>();
+            if split_mcp_command(&line, name).ok() != Some(expected) {
+                return Err(invalid(
+                    "The command arguments cannot be represented as one command line".to_string(),
+                ));
+            }
+            if entry.socket.is_some() {
+                return Err(invalid("A command cannot use a Unix socket".to_string()));
+            }
+            AgentMcpTransport::Stdio {
+                command: line,
+                environment,
+                working_directory: entry.cwd.clone(),
+            }
+        }
+        (None, Some(url)) => {
+            if entry.cwd.is_some() {
+                return Err(invalid(
+                    "A working directory only applies to a command".to_string(),
+                ));
+            }
+            let headers = map_to_key_values(&entry.headers);
+            match entry.kind.as_deref() {
+                None | Some("http" | "streamable-http" | "streamable_http" | "streamableHttp") => {
+                    match &entry.socket {
+                        Some(socket_path) => AgentMcpTransport::UnixSocket {
+                            socket_path: socket_path.clone(),
+                            url: url.clone(),
+                            environment,
+                            headers,
+                        },
+                        None => AgentMcpTransport::StreamableHttp {
+                            url: url.clone(),
+                            environment,
+                            headers,
+                        },
+                    }
+                }
+                Some("sse") if entry.socket.is_some() => {
+                    return Err(invalid(
+                        "Only streamable HTTP servers can use a Unix socket".to_string(),
+                    ));
+                }
+                Some("sse") => AgentMcpTransport::Sse {
+                    url: url.clone(),
+                    environment,
+                    headers,
+                },
+                Some(other) => {
+                    return Err(invalid(format!("Unsupported transport type '{other}'")));
+                }
+            }
+        }
+    };
+    let server = AgentMcpServer {
+        name: name.to_string(),
+        description: String::new(),
+        enabled: !entry.disabled,
+        timeout_seconds: DEFAULT_MCP_TIMEOUT_SECONDS,
+        transport,
+        allowed_tools: Vec::new(),
+        blocked_tools: Vec::new(),
+        sampling: None,
+    };
+    normalize_mcp_servers(vec![server])
+        .map(|mut servers| servers.remove(0))
+        .map_err(invalid)
+}
+
+fn command_line(command: &str, args: &[String]) -> String {
+    std::iter::once(command)
+        .chain(args.iter().map(String::as_str))
+        .map(quote_argument)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote_argument(argument: &str) -> String {
+    let plain = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|character| character.is_ascii_alphanumeric() || "-_./:=@%+,".contains(character));
+    if plain {
+        argument.to_string()
+    } else if !argument.contains('\'') {
+        format!("'{argument}'")
+    } else {
+        format!(
+            "\"{}\"",
+            argument.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    }
+}
+
+fn key_values_to_map(entries: &[AgentMcpKeyValue]) -> BTreeMap<String, String> {
+    entries
+        .iter()
+        .map(|entry| (entry.key.clone(), entry.value.clone()))
+        .collect()
+}
+
+fn map_to_key_values(entries: &BTreeMap<String, String>) -> Vec<AgentMcpKeyValue> {
+    entries
+        .iter()
+        .map(|(key, value)| AgentMcpKeyValue {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn existing_server() -> AgentMcpServer {
+        AgentMcpServer {
+            name: "github".to_string(),
+            description: "Issues".to_string(),
+            enabled: true,
+            timeout_seconds: 30,
+            transport: AgentMcpTransport::StreamableHttp {
+                url: "https://mcp.example.com/github".to_string(),
+                environment: Vec::new(),
+                headers: Vec::new(),
+            },
+            allowed_tools: Vec::new(),
+            blocked_tools: Vec::new(),
+            sampling: None,
+        }
+    }
+
+    #[test]
+    fn import_reports_conflicts_before_anything_is_saved() {
+        let json = r#"{
+            "mcpServers": {
+                "filesystem": {
+                    "command": "npx",
+                    "args": ["-y", "@modelcontextprotocol/server-filesystem", "/Users/me/My Notes"],
+                    "env": { "LOG_LEVEL": "debug" }
+                },
+                "github": { "url": "https://other.example.com/mcp" },
+                "developer": { "command": "dev-server" },
+                "legacy": { "type": "sse", "url": "https://mcp.example.com/sse", "disabled": true },
+                "broken": { "args": ["--flag"] }
+            }
+        }"#;
+        let report = plan_mcp_import(json, &[existing_server()], false).unwrap();
+        let status = |name: &str| {
+            report
+                .entries
+                .iter()
+                .find(|entry| entry.name == name)
+                .map(|entry| entry.status)
+                .unwrap()
+        };
+        assert_eq!(status("filesystem"), AgentMcpImportStatus::Added);
+        assert_eq!(status("github"), AgentMcpImportStatus::Collision);
+        assert_eq!(status("developer"), AgentMcpImportStatus::Reserved);
+        assert_eq!(status("legacy"), AgentMcpImportStatus::Added);
+        assert_eq!(status("broken"), AgentMcpImportStatus::Invalid);
+        assert!(!report.saved);
+        assert_eq!(report.servers.len(), 3);
+        assert_eq!(report.servers[0], existing_server());
+
+        let filesystem = report
+            .servers
+            .iter()
+            .find(|server| server.name == "filesystem")
+            .unwrap();
+        let AgentMcpTransport::Stdio { command, .. } = &filesystem.transport else {
+            panic!("expected a stdio server");
+        };
+        assert_eq!(
+            split_mcp_command(command, "filesystem").unwrap(),
+            [
+                "npx",
+                "-y",
+                "@modelcontextprotocol/server-filesystem",
+                "/Users/me/My Notes"
+            ]
+        );
+
+        let replaced = plan_mcp_import(json, &[existing_server()], true).unwrap();
+        assert!(replaced
+            .entries
+            .iter()
+            .any(|entry| entry.name == "github" && entry.status == AgentMcpImportStatus::Replaced));
+
+        // Exported servers import back unchanged apart from Maple-only fields.
+        let exported = export_mcp_servers(&report.servers).unwrap();
+        let round_trip = plan_mcp_import(&exported, &[], false).unwrap();
+        assert_eq!(round_trip.servers.len(), report.servers.len());
+        assert!(round_trip
+            .servers
+            .iter()
+            .all(|server| report.servers.iter().any(|original| {
+                original.name == server.name
+                    && original.transport == server.transport
+                    && original.enabled == server.enabled
+            })));
+    }
+
+    #[test]
+    fn import_rejects_environment_overrides_maple_refuses() {
+        let json = r#"{ "mcpServers": { "tool": { "command": "tool", "env": { "LD_PRELOAD": "/tmp/x.so" } } } }"#;
+        let report = plan_mcp_import(json, &[], false).unwrap();
+        assert_eq!(
+            report.entries[0].status,
+            AgentMcpImportStatus::RejectedEnvironment
+        );
+        assert!(report.entries[0]
+            .detail
+            .as_deref()
+            .unwrap()
+            .contains("LD_PRELOAD"));
+        assert!(report.servers.is_empty());
+    }
+}
@@ -0,0 +1,482 @@
+//! MCP sampling served by the task's Maple model.
+//!
+//! A server opts in per configuration. Maple then connects it itself with a
+//! client handler that answers `sampling/createMessage`: the model comes from
+//! the server's allowlist, or is the task's own model when the list is empty,
+//! the completion is capped at the server's token limit, and a task outside
+//! Allow all mode asks the user first. Completions run through the session's
+//! authenticated Maple provider and count toward the task's usage.
+//!
+//! The same handler forwards a server's `elicitation/create` to the task's
+//! user, so servers Maple connects can ask for input like Goose's own.
+
+use super::mcp_elicitation::McpElicitationHost;
+use super::{bounded_timeline_text, selectable_agent_model_id, AgentPermissionDecision};
+use goose::conversation::message::Message;
+use rmcp::model::{
+    ClientCapabilities, ClientInfo, Content, CreateElicitationRequestParams,
+    CreateElicitationResult, CreateMessageRequestParams, CreateMessageResult, ErrorCode, ErrorData,
+    ModelPreferences, RawContent, Role, SamplingMessage,
+};
+use rmcp::service::{RequestContext, RoleClient};
+use rmcp::ClientHandler;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+
+const DEFAULT_MCP_SAMPLING_MAX_TOKENS: u32 = 1_024;
+const MAX_MCP_SAMPLING_MAX_TOKENS: u32 = 8_192;
+const MAX_MCP_SAMPLING_MODELS: usize = 16;
+const MAX_MCP_SAMPLING_INPUT_BYTES: usize = 256 * 1024;
+const MAX_MCP_SAMPLING_PREVIEW_CHARS: usize = 2_000;
+
+/// A server's permission to request completions from Maple.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpSampling {
+    /// Models the server may request. Empty means the task's own model.
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// Upper bound on each completion, whatever the server asks for.
+    #[serde(default = "default_mcp_sampling_max_tokens")]
+    pub max_tokens: u32,
+}
+
+fn default_mcp_sampling_max_tokens() -> u32 {
+    DEFAULT_MCP_SAMPLING_MAX_TOKENS
+}
+
+pub(super) fn normalize_mcp_sampling(
+    sampling: &mut AgentMcpSampling,
+    server_name: &str,
+) -> Result<(), String> {
+    if !(1..=MAX_MCP_SAMPLING_MAX_TOKENS).contains(&sampling.max_tokens) {
+        return Err(format!(
+            "MCP server '{server_name}' sampling token limit must be between 1 and {MAX_MCP_SAMPLING_MAX_TOKENS}"
+        ));
+    }
+    let mut seen = HashSet::new();
+    for model in sampling.allowed_models.iter_mut() {
+        *model = model.trim().to_string();
+        if model.is_empty() || !selectable_agent_model_id(model) {
+            return Err(format!(
+                "MCP server '{server_name}' cannot sample from the model '{model}'"
+            ));
+        }
+    }
+    sampling
+        .allowed_models
+        .retain(|model| seen.insert(model.clone()));
+    if sampling.allowed_models.len() > MAX_MCP_SAMPLING_MODELS {
+        return Err(format!(
+            "MCP server '{server_name}' can list at most {MAX_MCP_SAMPLING_MODELS} sampling models"
+        ));
+    }
+    Ok(())
+}
+
+/// What the user is asked to approve before a completion runs.
+pub(super) struct McpSamplingApproval {
+    pub(super) server: String,
+    pub(super) model: String,
+    pub(super) max_tokens: u32,
+    pub(super) system_prompt: Option<String>,
+    /// The request's messages, cut to a readable length.
+    pub(super) preview: String,
+}
+
+impl McpSamplingApproval {
+    pub(super) fn prompt(&self) -> String {
+        format!(
+            "MCP server '{}' wants {} to write a reply of up to {} tokens.",
+            self.server, self.model, self.max_tokens
+        )
+    }
+
+    pub(super) fn arguments(&self) -> Map<String, Value> {
+        let mut arguments = Map::new();
+        arguments.insert("server".to_string(), json!(self.server));
+        arguments.insert("model".to_string(), json!(self.model));
+        arguments.insert("maxTokens".to_string(), json!(self.max_tokens));
+        if let Some(system_prompt) = &self.system_prompt {
+            arguments.insert(
+                "systemPrompt".to_string(),
+                json!(bounded_timeline_text(
+                    system_prompt,
+                    MAX_MCP_SAMPLING_PREVIEW_CHARS
+                )),
+            );
+        }
+        arguments.insert("messages".to_string(), json!(self.preview));
+        arguments
+    }
+}
+
+pub(super) struct McpSamplingCompletion {
+    pub(super) model: String,
+    pub(super) system: String,
+    pub(super) messages: Vec<Message>,
+    pub(super) max_tokens: u32,
+    pub(super) temperature: Option<f32>,
+}
+
+/// The task a sampling server is attached to.
+#[async_trait::async_trait]
+pub(super) trait McpSamplingHost: Send + Sync {
+    /// The model the task itself runs on.
+    async fn task_model(&self) -> Result<String, String>;
+    /// Return once the request may run, or why it may not.
+    async fn approve(&self, approval: McpSamplingApproval) -> Result<(), String>;
+    /// Run the completion and return its text.
+    async fn complete(&self, completion: McpSamplingCompletion) -> Result<String, String>;
+}
+
+/// Sampling requests waiting for the user, keyed by permission request ID.
+/// They share the permission card flow with Goose's tool confirmations.
+#[derive(Clone, Default)]
+pub(super) struct McpSamplingApprovals {
+    inner: Arc<Mutex<HashMap<String, oneshot::Sender<AgentPermissionDecision>>>>,
+}
+
+impl McpSamplingApprovals {
+    pub(super) async fn register(
+        &self,
+        request_id: &str,
+    ) -> oneshot::Receiver<AgentPermissionDecision> {
+        let (sender, receiver) = oneshot::channel();
+        self.inner
+            .lock()
+            .await
+            .insert(request_id.to_string(), sender);
+        receiver
+    }
+
+    /// Deliver the user's decision. Returns false when `request_id` is not a
+    /// sampling request, so the caller hands it to Goose instead.
+    pub(super) async fn resolve(
+        &self,
+        request_id: &str,
+        decision: AgentPermissionDecision,
+    ) -> bool {
+        match self.inner.lock().await.remove(request_id) {
+            Some(sender) => {
+                let _ = sender.send(decision);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(super) async fn forget(&self, request_id: &str) {
+        self.inner.lock().await.remove(request_id);
+    }
+
+    /// Drop every waiting request, which declines it.
+    pub(super) async fn clear(&self) {
+        self.inner.lock().await.clear();
+    }
+}
+
+struct McpSampler {
+    server: String,
+    policy: AgentMcpSampling,
+    host: Arc<dyn McpSamplingHost>,
+}
+
+impl McpSampler {
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParams,
+    ) -> Result<CreateMessageResult, String> {
+        let model = match select_sampling_model(
+            &self.policy.allowed_models,
+            params.model_preferences.as_ref(),
+        ) {
+            Some(model) => model.to_string(),
+            None => self.host.task_model().await?,
+        };
+        let max_tokens = params.max_tokens.clamp(1, self.policy.max_tokens);
+        let messages = sampling_messages(&params.messages)?;
+        self.host
+            .approve(McpSamplingApproval {
+                server: self.server.clone(),
+                model: model.clone(),
+                max_tokens,
+                system_prompt: params.system_prompt.clone(),
+                preview: sampling_preview(&params.messages),
+            })
+            .await?;
+        let text = self
+            .host
+            .complete(McpSamplingCompletion {
+                model: model.clone(),
+                system: params.system_prompt.unwrap_or_default(),
+                messages,
+                max_tokens,
+                temperature: params.temperature,
+            })
+            .await?;
+        Ok(CreateMessageResult {
+            model,
+            stop_reason: Some(CreateMessageResult::STOP_REASON_END_TURN.to_string()),
+            message: SamplingMessage {
+                role: Role::Assistant,
+                content: Content::text(text),
+            },
+        })
+    }
+}
+
+/// The first allowed model named by one of the server's hints, in hint order,
+/// or else the first allowed model. `None` means the task's own model.
+fn select_sampling_model<'a>(
+    allowed: &'a [String],
+    preferences: Option<&ModelPreferences>,
+) -> Option<&'a str> {
+    let hints = preferences
+        .and_then(|preferences| preferences.hints.as_deref())
+        .unwrap_or_default();
+    hints
+        .iter()
+        .filter_map(|hint| hint.name.as_deref())
+        .map(str::to_ascii_lowercase)
+        .find_map(|hint| {
+            allowed
+                .iter()
+                .find(|model| model.to_ascii_lowercase().contains(&hint))
+        })
+        .or_else(|| allowed.first())
+        .map(String::as_str)
+}
+
+fn sampling_messages(messages: &[SamplingMessage]) -> Result<Vec<Message>, String> {
+    if messages.is_empty() {
+        return Err("A sampling request needs at least one message".to_string());
+    }
+    let mut bytes = 0;
+    let mut converted = Vec::with_capacity(messages.len());
+    for message in messages {
+        let base = match message.role {
+            Role::User => Message::user(),
+            Role::Assistant => Message::assistant(),
+        };
+        converted.push(match &message.content.raw {
+            RawContent::Text(text) => {
+                bytes += text.text.len();
+                base.with_text(text.text.clone())
+            }
+            RawContent::Image(image) => {
+                bytes += image.data.len();
+                base.with_image(image.data.clone(), image.mime_type.clone())
+            }
+            _ => return Err("Maple can only sample from text and image messages".to_string()),
+        });
+        if bytes > MAX_MCP_SAMPLING_INPUT_BYTES {
+            return Err(format!(
+                "A sampling request can include at most {} KiB of messages",
+                MAX_MCP_SAMPLING_INPUT_BYTES / 1024
+            ));
+        }
+    }
+    Ok(converted)
+}
+
+fn sampling_preview(messages: &[SamplingMessage]) -> String {
+    let rendered = messages
+        .iter()
+        .map(|message| {
+            let role = match message.role {
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            match &message.content.raw {
+                RawContent::Text(text) => format!("{role}: {}", text.text),
+                RawContent::Image(image) => format!("{role}: [image: {}]", image.mime_type),
+                _ => format!("{role}: [unsupported content]"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    bounded_timeline_text(&rendered, MAX_MCP_SAMPLING_PREVIEW_CHARS)
+}
+
+struct McpElicitor {
+    server: String,
+    host: Arc<dyn McpElicitationHost>,
+}
+
+/// Maple's side of an MCP session it connects itself. Without a sampling
+/// policy or an elicitation host it behaves like rmcp's default client and
+/// refuses both.
+#[derive(Clone, Default)]
+pub(crate) struct MapleMcpClientHandler {
+    sampling: Option<Arc<McpSampler>>,
+    elicitation: Option<Arc<McpElicitor>>,
+}
+
+impl MapleMcpClientHandler {
+    pub(super) fn with_sampling(
+        server: String,
+        policy: AgentMcpSampling,
+        host: Arc<dyn McpSamplingHost>,
+    ) -> Self {
+        Self {
+            sampling: Some(Arc::new(McpSampler {
+                server,
+                policy,
+                host,
+            })),
+            elicitation: None,
+        }
+    }
+
+    /// Let the server ask the task's user for input.
+    pub(super) fn with_elicitation(
+        mut self,
+        server: String,
+        host: Arc<dyn McpElicitationHost>,
+    ) -> Self {
+        self.elicitation = Some(Arc::new(McpElicitor { server, host }));
+        self
+    }
+}
+
+impl ClientHandler for MapleMcpClientHandler {
+    async fn create_message(
+        &self,
+        params: CreateMessageRequestParams,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateMessageResult, ErrorData> {
+        let Some(sampler) = &self.sampling else {
+            return Err(ErrorData::new(
+                ErrorCode::METHOD_NOT_FOUND,
+                "Sampling is not enabled for this MCP server",
+                None,
+            ));
+        };
+        sampler
+            .create_message(params)
+            .await
+            .map_err(|error| ErrorData::new(ErrorCode::INVALID_REQUEST, error, None))
+    }
+
+    async fn create_elicitation(
+        &self,
+        params: CreateElicitationRequestParams,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateElicitationResult, ErrorData> {
+        let Some(elicitor) = &self.elicitation else {
+            return Err(ErrorData::new(
+                ErrorCode::METHOD_NOT_FOUND,
+                "Elicitation is not enabled for this MCP server",
+                None,
+            ));
+        };
+        // Replies are checked against the schema as JSON, the form in which
+        // Goose hands Maple the elicitations of the servers it connects.
+        let params = serde_json::to_value(&params)
+            .map_err(|error| ErrorData::new(ErrorCode::INVALID_PARAMS, error.to_string(), None))?;
+        let message = params
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let requested_schema = params.get("requestedSchema").cloned().ok_or_else(|| {
+            ErrorData::new(
+                ErrorCode::INVALID_PARAMS,
+                "Maple only supports form elicitation",
+                None,
+            )
+        })?;
+        let (action, content) = elicitor
+            .host
+            .elicit(&elicitor.server, message, requested_schema)
+            .await
+            .map_err(|error| ErrorData::new(ErrorCode::INVALID_REQUEST, error, None))?;
+        Ok(CreateElicitationResult {
+            action: action.mcp_action(),
+            content,
+        })
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        let mut info = ClientInfo::default();
+        info.capabilities = match (self.sampling.is_some(), self.elicitation.is_some()) {
+            (true, true) => ClientCapabilities::builder()
+                .enable_sampling()
+                .enable_elicitation()
+                .build(),
+            (true, false) => ClientCapabilities::builder().enable_sampling().build(),
+            (false, true) => ClientCapabilities::builder().enable_elicitation().build(),
+            (false, false) => ClientCapabilities::default(),
+        };
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rmcp::model::ModelHint;
+
+    #[test]
+    fn sampling_policies_are_trimmed_and_bounded() {
+        let mut sampling = AgentMcpSampling {
+            allowed_models: vec![
+                " gpt-oss-120b ".to_string(),
+                "gpt-oss-120b".to_string(),
+                "llama3-3-70b".to_string(),
+            ],
+            max_tokens: 512,
+        };
+        normalize_mcp_sampling(&mut sampling, "Search").unwrap();
+        assert_eq!(sampling.allowed_models, ["gpt-oss-120b", "llama3-3-70b"]);
+
+        for (models, max_tokens) in [
+            (vec!["whisper-large-v3".to_string()], 512),
+            (Vec::new(), 0),
+            (Vec::new(), MAX_MCP_SAMPLING_MAX_TOKENS + 1),
+        ] {
+            assert!(normalize_mcp_sampling(
+                &mut AgentMcpSampling {
+                    allowed_models: models,
+                    max_tokens,
+                },
+                "Search"
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn model_hints_pick_from_the_allowlist_only() {
+        let allowed = ["gpt-oss-120b".to_string(), "llama3-3-70b".to_string()];
+        let preferences = |names: &[&str]| ModelPreferences {
+            hints: Some(
+                names
+                    .iter()
+                    .map(|name| ModelHint {
+                        name: Some(name.to_string()),
+                    })
+                    .collect(),
+            ),
+            cost_priority: None,
+            speed_priority: None,
+            intelligence_priority: None,
+        };
+        assert_eq!(
+            select_sampling_model(&allowed, Some(&preferences(&["claude", "Llama3"]))),
+            Some("llama3-3-70b")
+        );
+        assert_eq!(
+            select_sampling_model(&allowed, Some(&preferences(&["claude"]))),
+            Some("gpt-oss-120b")
+        );
+        assert_eq!(
+            select_sampling_model(&[], Some(&preferences(&["gpt"]))),
+            None
+        );
+    }
+}
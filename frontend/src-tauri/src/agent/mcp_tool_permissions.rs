@@ -0,0 +1,291 @@
+//! Per-tool permission policy for user-configured MCP servers.
+//!
+//! Goose routes every tool call through its `PermissionManager`, which Maple
+//! seeds with the built-in tool policy at runtime start. User MCP tools are
+//! added to the same manager under their model-visible `<server>__<tool>` names:
+//! `always_allow` runs without a prompt, `never_allow` is refused by Goose, and
+//! `ask_before` reaches Maple's permission routing like a built-in tool.
+
+use goose::agents::Agent;
+use goose::config::permission::PermissionLevel;
+use goose::config::PermissionManager;
+use rmcp::model::ToolAnnotations;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub(crate) const MAX_MCP_TOOL_PERMISSIONS: usize = 1_024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentMcpToolPermissionLevel {
+    AlwaysAllow,
+    AskBefore,
+    NeverAllow,
+}
+
+impl AgentMcpToolPermissionLevel {
+    /// The level used until the user chooses one. Only tools that declare
+    /// themselves read-only, and not destructive, run without asking.
+    pub(crate) fn default_for(annotations: Option<&ToolAnnotations>) -> Self {
+        let read_only = annotations.and_then(|hints| hints.read_only_hint) == Some(true);
+        let destructive = annotations.and_then(|hints| hints.destructive_hint) == Some(true);
+        if read_only && !destructive {
+            Self::AlwaysAllow
+        } else {
+            Self::AskBefore
+        }
+    }
+
+    fn goose_level(self) -> PermissionLevel {
+        match self {
+            Self::AlwaysAllow => PermissionLevel::AlwaysAllow,
+            Self::AskBefore => PermissionLevel::AskBefore,
+            Self::NeverAllow => PermissionLevel::NeverAllow,
+        }
+    }
+}
+
+/// A user's choice for one tool of one configured MCP server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentMcpToolPermission {
+    pub server: String,
+    pub tool: String,
+    pub level: AgentMcpToolPermissionLevel,
+}
+
+/// Validate saved choices against the configured servers. A later entry for
+/// the same server and tool replaces an earlier one.
+pub(crate) fn normalize_mcp_tool_permissions(
+    permissions: Vec<AgentMcpToolPermission>,
+    configured_servers: &[String],
+) -> Result<Vec<AgentMcpToolPermission>, String> {
+    let configured = configured_servers
+        .iter()
+        .map(|name| (goose::config::extensions::name_to_key(name), name))
+        .collect::<HashMap<_, _>>();
+    let mut normalized: Vec<AgentMcpToolPermission> = Vec::with_capacity(permissions.len());
+    for mut permission in permissions {
+        permission.tool = permission.tool.trim().to_string();
+        if permission.tool.is_empty() {
+            return Err("MCP tool permissions require a tool name".to_string());
+        }
+        let key = goose::config::extensions::name_to_key(permission.server.trim());
+        let Some(server) = configured.get(&key) else {
+            return Err(format!(
+                "MCP tool permission for '{}' refers to a server that is not configured",
+                permission.server.trim()
+            ));
+        };
+        permission.server = (*server).clone();
+        if let Some(existing) = normalized.iter_mut().find(|existing| {
+            existing.server == permission.server && existing.tool == permission.tool
+        }) {
+            existing.level = permission.level;
+        } else {
+            normalized.push(permission);
+        }
+    }
+    if normalized.len() > MAX_MCP_TOOL_PERMISSIONS {
+        return Err(format!(
+            "Maple can keep at most {MAX_MCP_TOOL_PERMISSIONS} MCP tool permissions"
+        ));
+    }
+    Ok(normalized)
+}
+
+fn goose_tool_name(server: &str, tool: &str) -> String {
+    format!("{}__{tool}", goose::config::extensions::name_to_key(server))
+}
+
+struct McpToolPermissionState {
+    manager: Arc<PermissionManager>,
+    chosen: HashMap<String, AgentMcpToolPermissionLevel>,
+    /// Annotation defaults for tools seen in a configured session.
+    defaults: HashMap<String, AgentMcpToolPermissionLevel>,
+}
+
+impl McpToolPermissionState {
+    fn effective(&self, name: &str) -> AgentMcpToolPermissionLevel {
+        self.chosen
+            .get(name)
+            .or_else(|| self.defaults.get(name))
+            .copied()
+            .unwrap_or(AgentMcpToolPermissionLevel::AskBefore)
+    }
+
+    fn publish(&self, names: impl IntoIterator<Item = String>) {
+        for name in names {
+            self.manager
+                .update_user_permission(&name, self.effective(&name).goose_level());
+        }
+    }
+}
+
+/// The running account's MCP tool policy, mirrored into its Goose
+/// `PermissionManager`. Maple's config file stays authoritative.
+#[derive(Clone, Default)]
+pub(super) struct McpToolPermissions {
+    inner: Arc<Mutex<Option<McpToolPermissionState>>>,
+}
+
+impl McpToolPermissions {
+    pub(super) async fn install(
+        &self,
+        manager: Arc<PermissionManager>,
+        permissions: &[AgentMcpToolPermission],
+    ) {
+        let state = McpToolPermissionState {
+            manager,
+            chosen: chosen_levels(permissions),
+            defaults: HashMap::new(),
+        };
+        state.publish(state.chosen.keys().cloned().collect::<Vec<_>>());
+        *self.inner.lock().await = Some(state);
+    }
+
+    /// Apply new choices. A tool whose choice was removed returns to its
+    /// annotation default, or to ask-before if no session has listed it yet.
+    pub(super) async fn replace(&self, permissions: &[AgentMcpToolPermission]) {
+        let mut guard = self.inner.lock().await;
+        let Some(state) = guard.as_mut() else {
+            return;
+        };
+        let previous = std::mem::replace(&mut state.chosen, chosen_levels(permissions));
+        let names = previous
+            .into_keys()
+            .chain(state.chosen.keys().cloned())
+            .collect::<HashSet<_>>();
+        state.publish(names);
+    }
+
+    pub(super) async fn clear(&self) {
+        self.inner.lock().await.take();
+    }
+
+    /// Record annotation defaults for the user MCP tools a session exposes and
+    /// publish their effective levels before the session runs.
+    pub(super) async fn apply_to_session(
+        &self,
+        agent: &Agent,
+        session_id: &str,
+        mcp_keys: &HashSet<String>,
+    ) {
+        if mcp_keys.is_empty() {
+            return;
+        }
+        let tools = agent.list_tools(session_id, None).await;
+        let mut guard = self.inner.lock().await;
+        let Some(state) = guard.as_mut() else {
+            return;
+        };
+        let mut names = Vec::new();
+        for tool in tools {
+            let owned_by_user_mcp = goose::agents::extension_manager::get_tool_owner(&tool)
+                .is_some_and(|owner| mcp_keys.contains(&owner));
+            if !owned_by_user_mcp {
+                continue;
+            }
+            let name = tool.name.to_string();
+            state.defaults.insert(
+                name.clone(),
+                AgentMcpToolPermissionLevel::default_for(tool.annotations.as_ref()),
+            );
+            names.push(name);
+        }
+        state.publish(names);
+    }
+}
+
+fn chosen_levels(
+    permissions: &[AgentMcpToolPermission],
+) -> HashMap<String, AgentMcpToolPermissionLevel> {
+    permissions
+        .iter()
+        .map(|permission| {
+            (
+                goose_tool_name(&permission.server, &permission.tool),
+                permission.level,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_follow_read_only_and_destructive_hints() {
+        assert_eq!(
+            AgentMcpToolPermissionLevel::default_for(None),
+            AgentMcpToolPermissionLevel::AskBefore
+        );
+        assert_eq!(
+            AgentMcpToolPermissionLevel::default_for(Some(&ToolAnnotations::new().read_only(true))),
+            AgentMcpToolPermissionLevel::AlwaysAllow
+        );
+        assert_eq!(
+            AgentMcpToolPermissionLevel::default_for(Some(
+                &ToolAnnotations::new().read_only(true).destructive(true)
+            )),
+            AgentMcpToolPermissionLevel::AskBefore
+        );
+    }
+
+    #[tokio::test]
+    async fn choices_are_published_and_removed_choices_fall_back() {
+        let root = std::env::temp_dir().join(format!(
+            "maple-mcp-tool-permissions-{}-{}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        let manager = Arc::new(PermissionManager::new(root.clone()));
+        let servers = ["GitHub".to_string()];
+        let permissions = normalize_mcp_tool_permissions(
+            vec![
+                AgentMcpToolPermission {
+                    server: " github ".to_string(),
+                    tool: "delete_repository".to_string(),
+                    level: AgentMcpToolPermissionLevel::AskBefore,
+                },
+                AgentMcpToolPermission {
+                    server: "GitHub".to_string(),
+                    tool: " delete_repository ".to_string(),
+                    level: AgentMcpToolPermissionLevel::NeverAllow,
+                },
+            ],
+            &servers,
+        )
+        .unwrap();
+        assert_eq!(permissions.len(), 1);
+        assert_eq!(permissions[0].server, "GitHub");
+
+        let policy = McpToolPermissions::default();
+        policy.install(Arc::clone(&manager), &permissions).await;
+        assert_eq!(
+            manager.get_user_permission("github__delete_repository"),
+            Some(PermissionLevel::NeverAllow)
+        );
+
+        policy.replace(&[]).await;
+        assert_eq!(
+            manager.get_user_permission("github__delete_repository"),
+            Some(PermissionLevel::AskBefore)
+        );
+
+        assert!(normalize_mcp_tool_permissions(
+            vec![AgentMcpToolPermission {
+                server: "Jira".to_string(),
+                tool: "create_issue".to_string(),
+                level: AgentMcpToolPermissionLevel::AlwaysAllow,
+            }],
+            &servers,
+        )
+        .is_err());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,477 @@
+//! Persistent per-project permission rules.
+//!
+//! Rules live in Maple's Agent config and answer tool confirmations for one
+//! project root: shell command prefixes, write/edit path globs, and
+//! `open_url` hosts. Deny beats allow, and a rule never covers a request Goose
+//! flagged with its own warning.
+
+use globset::GlobBuilder;
+use goose::conversation::message::{ActionRequired, ActionRequiredData};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::web_tools::OPEN_URL_TOOL_NAME;
+
+pub(crate) const MAX_PERMISSION_RULES: usize = 512;
+const MAX_RULE_PATTERN_CHARS: usize = 1_000;
+const MAX_HOST_CHARS: usize = 253;
+
+/// Shell syntax that can chain, substitute, or redirect. A command containing
+/// any of these is never covered by an allow rule, so `cargo test` cannot be
+/// stretched into `cargo test && curl ... | sh`.
+const SHELL_CONTROL_CHARS: &[char] = &[
+    ';', '&', '|', '<', '>', '`', '$', '(', ')', '{', '}', '\n', '\r',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentPermissionRuleTool {
+    Shell,
+    Write,
+    Edit,
+    OpenUrl,
+}
+
+impl AgentPermissionRuleTool {
+    fn tool_name(self) -> &'static str {
+        match self {
+            Self::Shell => "shell",
+            Self::Write => "write",
+            Self::Edit => "edit",
+            Self::OpenUrl => OPEN_URL_TOOL_NAME,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentPermissionRuleDecision {
+    Allow,
+    Deny,
+}
+
+/// A durable answer to matching permission requests in one project.
+///
+/// `pattern` is interpreted per tool: a shell command prefix matched on word
+/// boundaries, a project-relative path glob for `write`/`edit`, or a host
+/// (optionally `*.`-prefixed for subdomains) for `open_url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPermissionRule {
+    pub id: String,
+    pub project_root: String,
+    pub tool: AgentPermissionRuleTool,
+    pub pattern: String,
+    pub decision: AgentPermissionRuleDecision,
+    pub created_ms: u128,
+}
+
+/// The running account's rules. Maple's config file stays authoritative; this
+/// copy only lets permission automation avoid a disk read per tool request.
+#[derive(Clone, Default)]
+pub(super) struct ProjectPermissionRules {
+    inner: Arc<Mutex<Vec<AgentPermissionRule>>>,
+}
+
+impl ProjectPermissionRules {
+    pub(super) async fn replace(&self, rules: Vec<AgentPermissionRule>) {
+        *self.inner.lock().await = rules;
+    }
+
+    pub(super) async fn clear(&self) {
+        self.inner.lock().await.clear();
+    }
+
+    pub(super) async fn decision_for_action(
+        &self,
+        project_root: &Path,
+        action: &ActionRequired,
+    ) -> Option<AgentPermissionRuleDecision> {
+        rule_decision_for_action(&self.inner.lock().await, project_root, action)
+    }
+}
+
+/// Validate a user-supplied pattern and return the form Maple stores.
+pub(crate) fn normalize_rule_pattern(
+    tool: AgentPermissionRuleTool,
+    pattern: &str,
+) -> Result<String, String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err("Permission rule pattern cannot be empty".to_string());
+    }
+    if pattern.chars().count() > MAX_RULE_PATTERN_CHARS {
+        return Err(format!(
+            "Permission rule pattern cannot exceed {MAX_RULE_PATTERN_CHARS} characters"
+        ));
+    }
+    if pattern.chars().any(char::is_control) {
+        return Err("Permission rule pattern cannot contain control characters".to_string());
+    }
+    match tool {
+        AgentPermissionRuleTool::Shell => Ok(normalize_command(pattern)),
+        AgentPermissionRuleTool::Write | AgentPermissionRuleTool::Edit => {
+            normalize_path_glob(pattern)
+        }
+        AgentPermissionRuleTool::OpenUrl => normalize_host_pattern(pattern),
+    }
+}
+
+fn normalize_command(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalize_path_glob(pattern: &str) -> Result<String, String> {
+    let pattern = pattern.replace('\\', "/");
+    let pattern = pattern.trim_start_matches("./");
+    if pattern.starts_with('/') || Path::new(pattern).is_absolute() || has_drive_prefix(pattern) {
+        return Err("Path rules must be relative to the project folder".to_string());
+    }
+    if pattern.split('/').any(|segment| segment == "..") {
+        return Err("Path rules cannot leave the project folder".to_string());
+    }
+    compile_path_glob(pattern)?;
+    Ok(pattern.to_string())
+}
+
+fn has_drive_prefix(pattern: &str) -> bool {
+    let bytes = pattern.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+fn compile_path_glob(pattern: &str) -> Result<globset::GlobMatcher, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|error| format!("Invalid path pattern: {error}"))
+}
+
+fn normalize_host_pattern(pattern: &str) -> Result<String, String> {
+    let pattern = pattern.to_ascii_lowercase();
+    let pattern = if pattern.contains("://") {
+        reqwest::Url::parse(&pattern)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .ok_or_else(|| "Website rules need a host such as example.com".to_string())?
+    } else {
+        pattern
+    };
+    let (wildcard, host) = match pattern.strip_prefix("*.") {
+        Some(host) => (true, host),
+        None => (false, pattern.as_str()),
+    };
+    let valid = !host.is_empty()
+        && host.len() <= MAX_HOST_CHARS
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+        });
+    if !valid {
+        return Err("Website rules need a host such as example.com".to_string());
+    }
+    Ok(if wildcard {
+        format!("*.{host}")
+    } else {
+        host.to_string()
+    })
+}
+
+/// Resolve a tool confirmation against the project's saved rules. Deny rules
+/// take precedence over allow rules, and allow rules never cover requests that
+/// Goose flagged with its own warning prompt.
+pub(crate) fn rule_decision_for_action(
+    rules: &[AgentPermissionRule],
+    project_root: &Path,
+    action: &ActionRequired,
+) -> Option<AgentPermissionRuleDecision> {
+    let ActionRequiredData::ToolConfirmation {
+        tool_name,
+        arguments,
+        prompt,
+        ..
+    } = &action.data
+    else {
+        return None;
+    };
+    let project_root_text = project_root.to_string_lossy();
+    let mut allowed = false;
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.project_root == project_root_text && rule.tool.tool_name() == tool_name)
+    {
+        match rule.decision {
+            AgentPermissionRuleDecision::Deny => {
+                if rule_matches(rule, project_root, arguments, false) {
+                    return Some(AgentPermissionRuleDecision::Deny);
+                }
+            }
+            AgentPermissionRuleDecision::Allow => {
+                allowed |= prompt.is_none() && rule_matches(rule, project_root, arguments, true);
+            }
+        }
+    }
+    allowed.then_some(AgentPermissionRuleDecision::Allow)
+}
+
+fn rule_matches(
+    rule: &AgentPermissionRule,
+    project_root: &Path,
+    arguments: &Map<String, Value>,
+    strict: bool,
+) -> bool {
+    match rule.tool {
+        AgentPermissionRuleTool::Shell => {
+            let Some(command) = arguments.get("command").and_then(Value::as_str) else {
+                return false;
+            };
+            if strict {
+                return !command.contains(SHELL_CONTROL_CHARS)
+                    && command_has_prefix(&normalize_command(command), &rule.pattern);
+            }
+            // Deny rules also cover commands hidden behind chaining or
+            // substitution, e.g. `cargo publish` inside `make && cargo publish`.
+            command
+                .split(SHELL_CONTROL_CHARS)
+                .any(|segment| command_has_prefix(&normalize_command(segment), &rule.pattern))
+        }
+        AgentPermissionRuleTool::Write | AgentPermissionRuleTool::Edit => {
+            let Some(path) = arguments.get("path").and_then(Value::as_str) else {
+                return false;
+            };
+            let Some(relative) = project_relative_path(project_root, path) else {
+                return false;
+            };
+            compile_path_glob(&rule.pattern).is_ok_and(|glob| glob.is_match(relative))
+        }
+        AgentPermissionRuleTool::OpenUrl => {
+            let Some(host) = arguments
+                .get("url")
+                .and_then(Value::as_str)
+                .and_then(|url| reqwest::Url::parse(url.trim()).ok())
+                .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+            else {
+                return false;
+            };
+            match rule.pattern.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.ends_with('.')),
+                None => host == rule.pattern,
+            }
+        }
+    }
+}
+
+fn command_has_prefix(command: &str, prefix: &str) -> bool {
+    command
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+/// Lexically resolve `path` against the project root. Paths that leave the
+/// project never match; symlinks are not followed because the file may not
+/// exist yet.
+fn project_relative_path(project_root: &Path, path: &str) -> Option<String> {
+    let path = Path::new(path.trim());
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        project_root.join(path)
+    };
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    let relative = resolved.strip_prefix(project_root).ok()?;
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    Some(
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goose::conversation::message::MessageContent;
+    use rmcp::object;
+
+    fn rule(
+        tool: AgentPermissionRuleTool,
+        pattern: &str,
+        decision: AgentPermissionRuleDecision,
+    ) -> AgentPermissionRule {
+        AgentPermissionRule {
+            id: format!("{tool:?}-{pattern}"),
+            project_root: "/work/project".to_string(),
+            tool,
+            pattern: normalize_rule_pattern(tool, pattern).expect("valid pattern"),
+            decision,
+            created_ms: 0,
+        }
+    }
+
+    fn action(
+        tool_name: &str,
+        arguments: Map<String, Value>,
+        prompt: Option<&str>,
+    ) -> ActionRequired {
+        let MessageContent::ActionRequired(action) = MessageContent::action_required(
+            "request-1",
+            tool_name.to_string(),
+            arguments,
+            prompt.map(str::to_string),
+        ) else {
+            unreachable!();
+        };
+        action
+    }
+
+    fn decide(rules: &[AgentPermissionRule], action: &ActionRequired) -> Option<&'static str> {
+        rule_decision_for_action(rules, Path::new("/work/project"), action).map(|decision| {
+            match decision {
+                AgentPermissionRuleDecision::Allow => "allow",
+                AgentPermissionRuleDecision::Deny => "deny",
+            }
+        })
+    }
+
+    #[test]
+    fn shell_rules_match_word_prefixes_and_never_allow_chained_commands() {
+        let rules = vec![rule(
+            AgentPermissionRuleTool::Shell,
+            "  cargo   test ",
+            AgentPermissionRuleDecision::Allow,
+        )];
+        let shell = |command: &str| action("shell", object!({ "command": command }), None);
+
+        assert_eq!(decide(&rules, &shell("cargo test")), Some("allow"));
+        assert_eq!(
+            decide(&rules, &shell("cargo  test -p maple")),
+            Some("allow")
+        );
+        assert_eq!(decide(&rules, &shell("cargo tests")), None);
+        assert_eq!(decide(&rules, &shell("cargo test && rm -rf .")), None);
+        assert_eq!(decide(&rules, &shell("cargo test $(whoami)")), None);
+        assert_eq!(
+            decide(
+                &rules,
+                &action(
+                    "shell",
+                    object!({ "command": "cargo test" }),
+                    Some("warning")
+                )
+            ),
+            None
+        );
+
+        let mut rules = rules;
+        rules.push(rule(
+            AgentPermissionRuleTool::Shell,
+            "cargo test --release",
+            AgentPermissionRuleDecision::Deny,
+        ));
+        assert_eq!(
+            decide(&rules, &shell("cargo test --release; echo")),
+            Some("deny")
+        );
+        assert_eq!(
+            decide(&rules, &shell("make && cargo test --release")),
+            Some("deny")
+        );
+        assert_eq!(decide(&rules, &shell("cargo test --release")), Some("deny"));
+        assert_eq!(
+            decide(&rules, &action("write", object!({ "path": "a" }), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn path_rules_are_project_relative_and_cannot_escape() {
+        let rules = vec![rule(
+            AgentPermissionRuleTool::Write,
+            "./src/**/*.rs",
+            AgentPermissionRuleDecision::Allow,
+        )];
+        let write = |path: &str| action("write", object!({ "path": path }), None);
+
+        assert_eq!(decide(&rules, &write("src/agent/mod.rs")), Some("allow"));
+        assert_eq!(
+            decide(&rules, &write("/work/project/src/lib.rs")),
+            Some("allow")
+        );
+        assert_eq!(decide(&rules, &write("src/../../other/src/a.rs")), None);
+        assert_eq!(decide(&rules, &write("/work/other/src/lib.rs")), None);
+        assert_eq!(decide(&rules, &write("README.md")), None);
+        assert_eq!(
+            decide(
+                &rules,
+                &action("edit", object!({ "path": "src/lib.rs" }), None)
+            ),
+            None
+        );
+
+        assert!(normalize_rule_pattern(AgentPermissionRuleTool::Edit, "/etc/*").is_err());
+        assert!(normalize_rule_pattern(AgentPermissionRuleTool::Edit, "../*.rs").is_err());
+        assert!(normalize_rule_pattern(AgentPermissionRuleTool::Edit, "src/[").is_err());
+    }
+
+    #[test]
+    fn host_rules_match_exact_hosts_or_explicit_subdomains() {
+        let rules = vec![
+            rule(
+                AgentPermissionRuleTool::OpenUrl,
+                "https://Docs.RS/serde",
+                AgentPermissionRuleDecision::Allow,
+            ),
+            rule(
+                AgentPermissionRuleTool::OpenUrl,
+                "*.example.com",
+                AgentPermissionRuleDecision::Allow,
+            ),
+        ];
+        assert_eq!(rules[0].pattern, "docs.rs");
+        let open = |url: &str| {
+            action(
+                OPEN_URL_TOOL_NAME,
+                object!({ "url": url, "purpose": "read docs" }),
+                None,
+            )
+        };
+
+        assert_eq!(
+            decide(&rules, &open("https://docs.rs/tokio")),
+            Some("allow")
+        );
+        assert_eq!(decide(&rules, &open("https://evildocs.rs/")), None);
+        assert_eq!(
+            decide(&rules, &open("https://api.example.com/v1")),
+            Some("allow")
+        );
+        assert_eq!(decide(&rules, &open("https://example.com/")), None);
+        assert_eq!(decide(&rules, &open("https://badexample.com/")), None);
+        assert!(normalize_rule_pattern(AgentPermissionRuleTool::OpenUrl, "exa mple.com").is_err());
+    }
+}
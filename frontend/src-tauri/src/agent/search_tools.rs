@@ -0,0 +1,462 @@
+//! Native file search for the developer toolset.
+//!
+//! `glob` and `grep` walk the project with the same ignore rules as git
+//! (`.gitignore`, `.ignore`, and the global excludes file) and never follow
+//! symlinks, so a search stays inside the tree it was pointed at. Both only
+//! read, which lets Read only mode approve them without classifying a shell
+//! command.
+
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::{Regex, RegexBuilder};
+use rmcp::model::{Tool, ToolAnnotations};
+use rmcp::object;
+use serde::Deserialize;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+
+pub(crate) const GLOB_TOOL_NAME: &str = "glob";
+pub(crate) const GREP_TOOL_NAME: &str = "grep";
+const DEFAULT_SEARCH_RESULTS: usize = 200;
+const MAX_SEARCH_RESULTS: usize = 1_000;
+/// Matches the `read` tool's output bound.
+const MAX_SEARCH_OUTPUT_BYTES: usize = 50 * 1024;
+const MAX_PATTERN_CHARS: usize = 1_000;
+const MAX_REGEX_SIZE: usize = 1024 * 1024;
+const MAX_GREP_LINE_CHARS: usize = 300;
+const MAX_GREP_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// Files with a NUL byte in this prefix are treated as binary and skipped.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GlobParams {
+    pattern: String,
+    path: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct GrepParams {
+    pattern: String,
+    path: Option<String>,
+    glob: Option<String>,
+    ignore_case: Option<bool>,
+    literal: Option<bool>,
+    limit: Option<usize>,
+}
+
+impl GlobParams {
+    pub(crate) fn path(&self) -> Option<&str> {
+        self.path.as_deref().filter(|path| !path.trim().is_empty())
+    }
+}
+
+impl GrepParams {
+    pub(crate) fn path(&self) -> Option<&str> {
+        self.path.as_deref().filter(|path| !path.trim().is_empty())
+    }
+}
+
+pub(crate) fn glob_tool() -> Tool {
+    Tool::new(
+        GLOB_TOOL_NAME.to_string(),
+        format!(
+            "Find files whose path matches a glob pattern, such as `src/**/*.rs` or `*.md`. Paths are matched relative to the search directory, `*` stays within one directory and `**` crosses directories. Files ignored by .gitignore are skipped. Returns at most {MAX_SEARCH_RESULTS} paths or {}KB.",
+            MAX_SEARCH_OUTPUT_BYTES / 1024
+        ),
+        object!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "minLength": 1,
+                    "maxLength": MAX_PATTERN_CHARS,
+                    "description": "Glob pattern matched against paths relative to the search directory"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory to search (relative or absolute); defaults to the project root"
+                },
+                "limit": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_SEARCH_RESULTS,
+                    "default": DEFAULT_SEARCH_RESULTS,
+                    "description": "Maximum number of paths to return"
+                }
+            },
+            "required": ["pattern"]
+        }),
+    )
+    .annotate(ToolAnnotations::from_raw(
+        Some("Find Files".to_string()),
+        Some(true),
+        Some(false),
+        Some(true),
+        Some(false),
+    ))
+}
+
+pub(crate) fn grep_tool() -> Tool {
+    Tool::new(
+        GREP_TOOL_NAME.to_string(),
+        format!(
+            "Search file contents with a regular expression and return matching lines as `path:line: text`. Files ignored by .gitignore, binary files, and files over {}MB are skipped. Returns at most {MAX_SEARCH_RESULTS} matches or {}KB.",
+            MAX_GREP_FILE_BYTES / (1024 * 1024),
+            MAX_SEARCH_OUTPUT_BYTES / 1024
+        ),
+        object!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "minLength": 1,
+                    "maxLength": MAX_PATTERN_CHARS,
+                    "description": "Rust regular expression, or plain text when literal is true"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to search (relative or absolute); defaults to the project root"
+                },
+                "glob": {
+                    "type": "string",
+                    "maxLength": MAX_PATTERN_CHARS,
+                    "description": "Only search files whose relative path matches this glob, such as `**/*.ts`"
+                },
+                "ignoreCase": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Match without regard to case"
+                },
+                "literal": {
+                    "type": "boolean",
+                    "default": false,
+                    "description": "Treat pattern as plain text instead of a regular expression"
+                },
+                "limit": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_SEARCH_RESULTS,
+                    "default": DEFAULT_SEARCH_RESULTS,
+                    "description": "Maximum number of matching lines to return"
+                }
+            },
+            "required": ["pattern"]
+        }),
+    )
+    .annotate(ToolAnnotations::from_raw(
+        Some("Search".to_string()),
+        Some(true),
+        Some(false),
+        Some(true),
+        Some(false),
+    ))
+}
+
+pub(crate) fn execute_glob(
+    params: GlobParams,
+    root: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<String, String> {
+    let matcher = glob_matcher(&params.pattern)?;
+    let limit = result_limit(params.limit)?;
+    let mut output = SearchOutput::new(limit);
+    for entry in walk(root) {
+        if cancel_token.is_cancelled() {
+            return Err("Search cancelled".to_string());
+        }
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let relative = relative_path(root, entry.path());
+        if matcher.is_match(&relative) && !output.push(relative) {
+            break;
+        }
+    }
+    Ok(output.finish("files", "No files matched"))
+}
+
+pub(crate) fn execute_grep(
+    params: GrepParams,
+    root: &Path,
+    cancel_token: &CancellationToken,
+) -> Result<String, String> {
+    let regex = grep_regex(&params)?;
+    let filter = params.glob.as_deref().map(glob_matcher).transpose()?;
+    let limit = result_limit(params.limit)?;
+    let mut output = SearchOutput::new(limit);
+    'files: for entry in walk(root) {
+        if cancel_token.is_cancelled() {
+            return Err("Search cancelled".to_string());
+        }
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let relative = relative_path(root, entry.path());
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !filter.is_match(&relative))
+        {
+            continue;
+        }
+        let Some(content) = read_searchable_text(entry.path()) else {
+            continue;
+        };
+        for (index, line) in content.lines().enumerate() {
+            if !regex.is_match(line) {
+                continue;
+            }
+            let line = truncate_line(line.trim_end_matches('\r'));
+            if !output.push(format!("{relative}:{}: {line}", index + 1)) {
+                break 'files;
+            }
+        }
+    }
+    Ok(output.finish("matches", "No matches found"))
+}
+
+fn walk(root: &Path) -> ignore::Walk {
+    WalkBuilder::new(root)
+        // Dotfiles such as `.github/` are often exactly what a search is
+        // after; `.git` itself never is.
+        .hidden(false)
+        .require_git(false)
+        .follow_links(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .sort_by_file_name(|left, right| left.cmp(right))
+        .build()
+}
+
+fn glob_matcher(pattern: &str) -> Result<GlobMatcher, String> {
+    let pattern = pattern.trim();
+    validate_pattern(pattern)?;
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|error| format!("Invalid glob pattern: {error}"))
+}
+
+fn grep_regex(params: &GrepParams) -> Result<Regex, String> {
+    validate_pattern(&params.pattern)?;
+    let pattern = if params.literal.unwrap_or(false) {
+        regex::escape(&params.pattern)
+    } else {
+        params.pattern.clone()
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(params.ignore_case.unwrap_or(false))
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|error| format!("Invalid regular expression: {error}"))
+}
+
+fn validate_pattern(pattern: &str) -> Result<(), String> {
+    if pattern.is_empty() {
+        return Err("pattern must not be empty".to_string());
+    }
+    if pattern.chars().count() > MAX_PATTERN_CHARS {
+        return Err(format!(
+            "pattern must be at most {MAX_PATTERN_CHARS} characters"
+        ));
+    }
+    Ok(())
+}
+
+fn result_limit(limit: Option<usize>) -> Result<usize, String> {
+    match limit {
+        Some(0) => Err("limit must be at least 1".to_string()),
+        Some(limit) => Ok(limit.min(MAX_SEARCH_RESULTS)),
+        None => Ok(DEFAULT_SEARCH_RESULTS),
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path
+        .strip_prefix(root)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        // A file searched directly is reported by its own name.
+        .or_else(|| path.file_name().map(Path::new))
+        .unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn read_searchable_text(path: &Path) -> Option<String> {
+    let file = fs::File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_GREP_FILE_BYTES {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    file.take(MAX_GREP_FILE_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn truncate_line(line: &str) -> String {
+    if line.chars().count() <= MAX_GREP_LINE_CHARS {
+        return line.to_string();
+    }
+    let mut truncated = line.chars().take(MAX_GREP_LINE_CHARS).collect::<String>();
+    truncated.push_str(" [...]");
+    truncated
+}
+
+struct SearchOutput {
+    text: String,
+    count: usize,
+    limit: usize,
+    truncated: bool,
+}
+
+impl SearchOutput {
+    fn new(limit: usize) -> Self {
+        Self {
+            text: String::new(),
+            count: 0,
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Returns false once the result or byte limit is reached.
+    fn push(&mut self, line: String) -> bool {
+        if self.count == self.limit || self.text.len() + line.len() + 1 > MAX_SEARCH_OUTPUT_BYTES {
+            self.truncated = true;
+            return false;
+        }
+        self.text.push_str(&line);
+        self.text.push('\n');
+        self.count += 1;
+        true
+    }
+
+    fn finish(mut self, noun: &str, empty: &str) -> String {
+        if self.count == 0 {
+            return empty.to_string();
+        }
+        if self.truncated {
+            self.text.push_str(&format!(
+                "[Showing the first {} {noun}; narrow the pattern or path to see more]\n",
+                self.count
+            ));
+        }
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn grep(pattern: &str) -> GrepParams {
+        GrepParams {
+            pattern: pattern.to_string(),
+            path: None,
+            glob: None,
+            ignore_case: None,
+            literal: None,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn glob_respects_gitignore_and_directory_separators() {
+        let root = tempdir().unwrap();
+        fs::create_dir_all(root.path().join("src/nested")).unwrap();
+        fs::create_dir_all(root.path().join("target")).unwrap();
+        fs::create_dir_all(root.path().join(".git")).unwrap();
+        fs::write(root.path().join(".gitignore"), "target/\n").unwrap();
+        fs::write(root.path().join("lib.rs"), "").unwrap();
+        fs::write(root.path().join("src/main.rs"), "").unwrap();
+        fs::write(root.path().join("src/nested/deep.rs"), "").unwrap();
+        fs::write(root.path().join("target/build.rs"), "").unwrap();
+        fs::write(root.path().join(".git/config.rs"), "").unwrap();
+        let cancel = CancellationToken::new();
+
+        let all = GlobParams {
+            pattern: "**/*.rs".to_string(),
+            path: None,
+            limit: None,
+        };
+        assert_eq!(
+            execute_glob(all, root.path(), &cancel).unwrap(),
+            "lib.rs\nsrc/main.rs\nsrc/nested/deep.rs\n"
+        );
+
+        let top = GlobParams {
+            pattern: "*.rs".to_string(),
+            path: None,
+            limit: None,
+        };
+        assert_eq!(execute_glob(top, root.path(), &cancel).unwrap(), "lib.rs\n");
+
+        let limited = GlobParams {
+            pattern: "**/*.rs".to_string(),
+            path: None,
+            limit: Some(1),
+        };
+        let output = execute_glob(limited, root.path(), &cancel).unwrap();
+        assert!(output.starts_with("lib.rs\n[Showing the first 1 files;"));
+    }
+
+    #[test]
+    fn grep_matches_regex_literal_and_case_insensitive_patterns() {
+        let root = tempdir().unwrap();
+        fs::write(root.path().join("a.txt"), "alpha\r\nfn main() {}\nBeta\n").unwrap();
+        fs::write(root.path().join("b.md"), "beta (literal)\n").unwrap();
+        fs::write(root.path().join("blob.bin"), b"beta\0beta").unwrap();
+        let cancel = CancellationToken::new();
+
+        assert_eq!(
+            execute_grep(grep(r"fn \w+\("), root.path(), &cancel).unwrap(),
+            "a.txt:2: fn main() {}\n"
+        );
+
+        let mut case_insensitive = grep("beta");
+        case_insensitive.ignore_case = Some(true);
+        assert_eq!(
+            execute_grep(case_insensitive, root.path(), &cancel).unwrap(),
+            "a.txt:3: Beta\nb.md:1: beta (literal)\n"
+        );
+
+        let mut literal = grep("(literal)");
+        literal.literal = Some(true);
+        literal.glob = Some("*.md".to_string());
+        assert_eq!(
+            execute_grep(literal, root.path(), &cancel).unwrap(),
+            "b.md:1: beta (literal)\n"
+        );
+
+        assert_eq!(
+            execute_grep(grep("missing"), root.path(), &cancel).unwrap(),
+            "No matches found"
+        );
+        assert!(execute_grep(grep("("), root.path(), &cancel)
+            .unwrap_err()
+            .starts_with("Invalid regular expression"));
+    }
+}
@@ -0,0 +1,620 @@
+//! Versioned, portable archives of one Agent task.
+//!
+//! An archive is a ZIP container holding a JSON manifest, the persisted Goose
+//! conversation, the timeline projection Maple showed at export time, and the
+//! image attachments the conversation references. Imported archives are
+//! untrusted and receive the same container checks as Word uploads before any
+//! entry is decompressed into memory.
+
+use super::attachments::{AgentImageAttachment, MAX_AGENT_IMAGE_BYTES};
+use super::{AgentRunUsage, AgentSessionSummary, AgentTimelineItem};
+use crate::word_extractor::{preflight_zip_central_directory, ZipDirectoryIssue};
+use goose::conversation::message::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub(super) const SESSION_ARCHIVE_FORMAT: &str = "maple-agent-session";
+pub(super) const SESSION_ARCHIVE_VERSION: u32 = 1;
+
+const ARCHIVE_READ_ERROR: &str = "This file is not a readable Maple task archive.";
+const ARCHIVE_ENCRYPTED_ERROR: &str = "Encrypted Maple task archives are not supported.";
+const ARCHIVE_TOO_LARGE_ERROR: &str = "This Maple task archive is too large to import.";
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONVERSATION_ENTRY: &str = "conversation.json";
+const TIMELINE_ENTRY: &str = "timeline.json";
+const ATTACHMENT_ENTRY_PREFIX: &str = "attachments/";
+const JSON_ENTRY_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct SessionArchiveLimits {
+    max_archive_bytes: u64,
+    max_attachments: usize,
+    max_json_entry_bytes: u64,
+    max_attachment_bytes: u64,
+    max_total_bytes: u64,
+    max_compression_ratio: u64,
+    compression_ratio_allowance: u64,
+}
+
+const SESSION_ARCHIVE_LIMITS: SessionArchiveLimits = SessionArchiveLimits {
+    max_archive_bytes: 256 * 1024 * 1024,
+    max_attachments: 1_000,
+    max_json_entry_bytes: 64 * 1024 * 1024,
+    max_attachment_bytes: MAX_AGENT_IMAGE_BYTES as u64,
+    max_total_bytes: 512 * 1024 * 1024,
+    max_compression_ratio: 200,
+    compression_ratio_allowance: 1024 * 1024,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(super) struct SessionArchiveUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cached_read_tokens: u64,
+    pub cached_write_tokens: u64,
+}
+
+impl From<AgentRunUsage> for SessionArchiveUsage {
+    fn from(usage: AgentRunUsage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+            cached_read_tokens: usage.cached_read_tokens,
+            cached_write_tokens: usage.cached_write_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(super) struct SessionArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    pub exported_ms: u128,
+    pub session: AgentSessionSummary,
+    pub usage: SessionArchiveUsage,
+    pub attachments: Vec<AgentImageAttachment>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct SessionArchive {
+    pub manifest: SessionArchiveManifest,
+    pub conversation: Vec<Message>,
+    /// Written for other readers of the archive. Import rebuilds the
+    /// timeline from the conversation, so a read archive leaves this empty.
+    pub timeline: Vec<AgentTimelineItem>,
+    pub attachments: Vec<(AgentImageAttachment, Vec<u8>)>,
+}
+
+/// Write `archive` to `path`, replacing any existing file only after the
+/// complete archive has been synced next to it.
+pub(super) fn write_session_archive(path: &Path, archive: &SessionArchive) -> Result<(), String> {
+    let bytes = encode_session_archive(archive, SESSION_ARCHIVE_LIMITS)?;
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .ok_or_else(|| "Choose a folder for the exported Agent task".to_string())?;
+    let mut temporary = tempfile::NamedTempFile::new_in(parent)
+        .map_err(|error| format!("Failed to export Agent task: {error}"))?;
+    temporary
+        .write_all(&bytes)
+        .and_then(|_| temporary.as_file_mut().sync_all())
+        .map_err(|error| format!("Failed to export Agent task: {error}"))?;
+    temporary
+        .persist(path)
+        .map_err(|error| format!("Failed to export Agent task: {}", error.error))?;
+    super::set_owner_only_permissions(path);
+    Ok(())
+}
+
+/// Read and fully validate an untrusted archive from `path`.
+pub(super) fn read_session_archive(path: &Path) -> Result<SessionArchive, String> {
+    let limits = SESSION_ARCHIVE_LIMITS;
+    let metadata = fs::metadata(path)
+        .map_err(|error| format!("Failed to open Maple task archive: {error}"))?;
+    if !metadata.is_file() {
+        return Err("Maple task archive is not a regular file".to_string());
+    }
+    if metadata.len() > limits.max_archive_bytes {
+        return Err(ARCHIVE_TOO_LARGE_ERROR.to_string());
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .map_err(|error| format!("Failed to open Maple task archive: {error}"))?;
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    file.take(limits.max_archive_bytes + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|error| format!("Failed to read Maple task archive: {error}"))?;
+    if bytes.len() as u64 > limits.max_archive_bytes {
+        return Err(ARCHIVE_TOO_LARGE_ERROR.to_string());
+    }
+    decode_session_archive(&bytes, limits)
+}
+
+/// Encode `archive`, holding it to the same limits import applies so every
+/// archive Maple writes can be imported again.
+fn encode_session_archive(
+    archive: &SessionArchive,
+    limits: SessionArchiveLimits,
+) -> Result<Vec<u8>, String> {
+    if archive.attachments.len() > limits.max_attachments {
+        return Err(format!(
+            "This Agent task references {} images; a Maple task archive holds at most {}.",
+            archive.attachments.len(),
+            limits.max_attachments
+        ));
+    }
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let json_options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(0o600);
+    // Supported image formats are already compressed.
+    let image_options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .unix_permissions(0o600);
+
+    let manifest = serde_json::to_vec_pretty(&archive.manifest)
+        .map_err(|error| format!("Failed to encode Agent task archive: {error}"))?;
+    let conversation = serde_json::to_vec(&archive.conversation)
+        .map_err(|error| format!("Failed to encode Agent task history: {error}"))?;
+    let timeline = serde_json::to_vec(&archive.timeline)
+        .map_err(|error| format!("Failed to encode Agent task timeline: {error}"))?;
+    let mut entries = vec![
+        (
+            MANIFEST_ENTRY.to_string(),
+            manifest.as_slice(),
+            json_options,
+        ),
+        (
+            CONVERSATION_ENTRY.to_string(),
+            conversation.as_slice(),
+            json_options,
+        ),
+        (
+            TIMELINE_ENTRY.to_string(),
+            timeline.as_slice(),
+            json_options,
+        ),
+    ];
+    for (attachment, bytes) in &archive.attachments {
+        entries.push((
+            format!("{ATTACHMENT_ENTRY_PREFIX}{}", attachment.id),
+            bytes.as_slice(),
+            image_options,
+        ));
+    }
+
+    let mut total_bytes = 0_u64;
+    for (name, contents, _) in &entries {
+        let (limit, what) = match name.as_str() {
+            MANIFEST_ENTRY => (limits.max_json_entry_bytes, "its summary"),
+            CONVERSATION_ENTRY => (limits.max_json_entry_bytes, "its history"),
+            TIMELINE_ENTRY => (limits.max_json_entry_bytes, "its timeline"),
+            _ => (limits.max_attachment_bytes, "one of its images"),
+        };
+        if contents.len() as u64 > limit {
+            return Err(too_large_to_export(what, limit));
+        }
+        total_bytes = total_bytes.saturating_add(contents.len() as u64);
+    }
+    if total_bytes > limits.max_total_bytes {
+        return Err(too_large_to_export("its contents", limits.max_total_bytes));
+    }
+
+    for (name, contents, options) in entries {
+        writer
+            .start_file(name, options)
+            .map_err(|error| format!("Failed to write Agent task archive: {error}"))?;
+        writer
+            .write_all(contents)
+            .map_err(|error| format!("Failed to write Agent task archive: {error}"))?;
+    }
+    let bytes = writer
+        .finish()
+        .map(Cursor::into_inner)
+        .map_err(|error| format!("Failed to finish Agent task archive: {error}"))?;
+    if bytes.len() as u64 > limits.max_archive_bytes {
+        return Err(too_large_to_export("the archive", limits.max_archive_bytes));
+    }
+    Ok(bytes)
+}
+
+fn decode_session_archive(
+    file_bytes: &[u8],
+    limits: SessionArchiveLimits,
+) -> Result<SessionArchive, String> {
+    let max_entries = JSON_ENTRY_COUNT + limits.max_attachments;
+    preflight_zip_central_directory(file_bytes, max_entries).map_err(|issue| match issue {
+        ZipDirectoryIssue::Unreadable(reason) => unreadable_archive(reason),
+        ZipDirectoryIssue::TooComplex(reason) => too_large(reason),
+        ZipDirectoryIssue::Encrypted => ARCHIVE_ENCRYPTED_ERROR.to_string(),
+    })?;
+    let mut archive = ZipArchive::new(Cursor::new(file_bytes))
+        .map_err(|error| unreadable_archive(format!("invalid ZIP container: {error}")))?;
+    if archive.len() > max_entries {
+        return Err(too_large(format!(
+            "archive contains {} entries (limit {max_entries})",
+            archive.len()
+        )));
+    }
+
+    let mut entries = HashMap::with_capacity(archive.len());
+    let mut total_metadata_bytes = 0_u64;
+    let mut total_actual_bytes = 0_u64;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|error| {
+            unreadable_archive(format!("could not inspect ZIP entry {index}: {error}"))
+        })?;
+        if entry.encrypted() {
+            return Err(ARCHIVE_ENCRYPTED_ERROR.to_string());
+        }
+        if entry.is_symlink() || entry.is_dir() {
+            return Err(unreadable_archive("archive contains a non-file entry"));
+        }
+
+        let entry_name = entry.name().to_string();
+        let entry_limit = match archive_entry_kind(&entry_name) {
+            Some(ArchiveEntryKind::Json) => limits.max_json_entry_bytes,
+            Some(ArchiveEntryKind::Attachment) => limits.max_attachment_bytes,
+            None => {
+                return Err(unreadable_archive(format!(
+                    "archive contains an unexpected entry: {entry_name}"
+                )));
+            }
+        };
+        if entries.contains_key(&entry_name) {
+            return Err(unreadable_archive(format!(
+                "archive contains a duplicate entry: {entry_name}"
+            )));
+        }
+        if !matches!(
+            entry.compression(),
+            CompressionMethod::Stored | CompressionMethod::Deflated
+        ) {
+            return Err(unreadable_archive(format!(
+                "archive entry uses unsupported compression: {entry_name}"
+            )));
+        }
+
+        let declared_size = entry.size();
+        if declared_size > entry_limit {
+            return Err(too_large(format!(
+                "archive entry {entry_name} expands to {declared_size} bytes"
+            )));
+        }
+        total_metadata_bytes = total_metadata_bytes
+            .checked_add(declared_size)
+            .ok_or_else(|| too_large("archive expanded-size total overflowed"))?;
+        if total_metadata_bytes > limits.max_total_bytes {
+            return Err(too_large(format!(
+                "archive expands to more than {} bytes",
+                limits.max_total_bytes
+            )));
+        }
+        let permitted_expansion = entry
+            .compressed_size()
+            .saturating_mul(limits.max_compression_ratio)
+            .saturating_add(limits.compression_ratio_allowance);
+        if declared_size > permitted_expansion {
+            return Err(too_large(format!(
+                "archive entry has an unsafe compression ratio: {entry_name}"
+            )));
+        }
+
+        let initial_capacity = usize::try_from(declared_size.min(1024 * 1024)).unwrap_or(0);
+        let mut contents = Vec::with_capacity(initial_capacity);
+        entry
+            .by_ref()
+            .take(entry_limit + 1)
+            .read_to_end(&mut contents)
+            .map_err(|error| {
+                unreadable_archive(format!("could not decompress {entry_name}: {error}"))
+            })?;
+        let actual_size = contents.len() as u64;
+        if actual_size != declared_size {
+            return Err(unreadable_archive(format!(
+                "archive entry size disagrees with its directory entry: {entry_name}"
+            )));
+        }
+        total_actual_bytes = total_actual_bytes
+            .checked_add(actual_size)
+            .ok_or_else(|| too_large("archive actual-size total overflowed"))?;
+        if total_actual_bytes > limits.max_total_bytes {
+            return Err(too_large(format!(
+                "archive actually expands to more than {} bytes",
+                limits.max_total_bytes
+            )));
+        }
+        entries.insert(entry_name, contents);
+    }
+
+    let manifest: SessionArchiveManifest = parse_json_entry(&mut entries, MANIFEST_ENTRY)?;
+    if manifest.format != SESSION_ARCHIVE_FORMAT {
+        return Err(unreadable_archive(format!(
+            "unexpected archive format: {}",
+            manifest.format
+        )));
+    }
+    if manifest.version != SESSION_ARCHIVE_VERSION {
+        return Err(format!(
+            "This Maple task archive uses format version {}, which this version of Maple cannot import.",
+            manifest.version
+        ));
+    }
+    let conversation: Vec<Message> = parse_json_entry(&mut entries, CONVERSATION_ENTRY)?;
+    // The exported timeline is part of the format, but import rebuilds the
+    // timeline from the conversation rather than trusting it.
+    if entries.remove(TIMELINE_ENTRY).is_none() {
+        return Err(unreadable_archive(format!(
+            "archive is missing {TIMELINE_ENTRY}"
+        )));
+    }
+
+    let mut attachment_ids = HashSet::with_capacity(manifest.attachments.len());
+    let mut attachments = Vec::with_capacity(manifest.attachments.len());
+    for attachment in &manifest.attachments {
+        if !attachment_ids.insert(attachment.id.clone()) {
+            return Err(unreadable_archive(format!(
+                "manifest lists attachment {} more than once",
+                attachment.id
+            )));
+        }
+        let bytes = entries
+            .remove(&format!("{ATTACHMENT_ENTRY_PREFIX}{}", attachment.id))
+            .ok_or_else(|| {
+                unreadable_archive(format!("attachment {} is missing", attachment.id))
+            })?;
+        attachments.push((attachment.clone(), bytes));
+    }
+    if let Some(name) = entries.keys().next() {
+        return Err(unreadable_archive(format!(
+            "archive entry is not listed in its manifest: {name}"
+        )));
+    }
+
+    Ok(SessionArchive {
+        manifest,
+        conversation,
+        timeline: Vec::new(),
+        attachments,
+    })
+}
+
+enum ArchiveEntryKind {
+    Json,
+    Attachment,
+}
+
+/// Accept only the exact entry names Maple writes. Attachment entries use
+/// the store's lowercase 32-hex IDs, so no path or case normalization can map
+/// two entries onto one file.
+fn archive_entry_kind(name: &str) -> Option<ArchiveEntryKind> {
+    if matches!(name, MANIFEST_ENTRY | CONVERSATION_ENTRY | TIMELINE_ENTRY) {
+        return Some(ArchiveEntryKind::Json);
+    }
+    name.strip_prefix(ATTACHMENT_ENTRY_PREFIX)
+        .filter(|id| {
+            id.len() == 32
+                && id
+                    .bytes()
+                    .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+        })
+        .map(|_| ArchiveEntryKind::Attachment)
+}
+
+fn parse_json_entry<T: serde::de::DeserializeOwned>(
+    entries: &mut HashMap<String, Vec<u8>>,
+    name: &str,
+) -> Result<T, String> {
+    let contents = entries
+        .remove(name)
+        .ok_or_else(|| unreadable_archive(format!("archive is missing {name}")))?;
+    serde_json::from_slice(&contents)
+        .map_err(|error| unreadable_archive(format!("could not parse {name}: {error}")))
+}
+
+fn unreadable_archive(reason: impl std::fmt::Display) -> String {
+    log::warn!("Maple task archive import failed: {reason}");
+    ARCHIVE_READ_ERROR.to_string()
+}
+
+fn too_large(reason: impl std::fmt::Display) -> String {
+    log::warn!("Maple task archive exceeded import limits: {reason}");
+    ARCHIVE_TOO_LARGE_ERROR.to_string()
+}
+
+fn too_large_to_export(what: &str, limit: u64) -> String {
+    format!(
+        "This Agent task is too large to export: {what} exceeds the {} MB limit for Maple task archives.",
+        limit / (1024 * 1024)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const PNG_BYTES: &[u8] = b"\x89PNG\r\n\x1a\nnot-a-real-image";
+
+    fn sample_archive() -> SessionArchive {
+        let id = "0123456789abcdef0123456789abcdef".to_string();
+        let attachment = AgentImageAttachment {
+            source: format!("maple-attachment://{id}"),
+            id,
+            name: "screen.png".to_string(),
+            mime_type: "image/png".to_string(),
+        };
+        SessionArchive {
+            manifest: SessionArchiveManifest {
+                format: SESSION_ARCHIVE_FORMAT.to_string(),
+                version: SESSION_ARCHIVE_VERSION,
+                exported_ms: 1_700_000_000_000,
+                session: AgentSessionSummary {
+                    id: "20260101_1".to_string(),
+                    title: "Archived task".to_string(),
+                    project_root: "/tmp/project".to_string(),
+                    created_ms: 1_700_000_000_000,
+                    updated_ms: 1_700_000_000_500,
+                    message_count: 1,
+                    model: Some("model-a".to_string()),
+                    mode: "smart_approve".to_string(),
+                },
+                usage: SessionArchiveUsage {
+                    input_tokens: 10,
+                    output_tokens: 5,
+                    total_tokens: 15,
+                    ..SessionArchiveUsage::default()
+                },
+                attachments: vec![attachment.clone()],
+            },
+            conversation: vec![Message::user().with_text("Summarize the project")],
+            timeline: Vec::new(),
+            attachments: vec![(attachment, PNG_BYTES.to_vec())],
+        }
+    }
+
+    fn zip_with(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn archives_round_trip_through_a_file() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("task.maple-task");
+        let archive = sample_archive();
+
+        write_session_archive(&path, &archive).unwrap();
+        let restored = read_session_archive(&path).unwrap();
+
+        assert_eq!(restored.manifest.session.title, "Archived task");
+        assert_eq!(restored.manifest.usage, archive.manifest.usage);
+        assert_eq!(restored.conversation.len(), 1);
+        assert_eq!(
+            restored.conversation[0].as_concat_text(),
+            "Summarize the project"
+        );
+        assert_eq!(restored.attachments, archive.attachments);
+    }
+
+    #[test]
+    fn rejects_unexpected_unlisted_and_missing_entries() {
+        let archive = sample_archive();
+        let bytes = encode_session_archive(&archive, SESSION_ARCHIVE_LIMITS).unwrap();
+        assert!(decode_session_archive(&bytes, SESSION_ARCHIVE_LIMITS).is_ok());
+
+        let manifest = serde_json::to_vec(&archive.manifest).unwrap();
+        let traversal = zip_with(&[
+            (MANIFEST_ENTRY, &manifest),
+            (CONVERSATION_ENTRY, b"[]"),
+            (TIMELINE_ENTRY, b"[]"),
+            ("attachments/../../escape", PNG_BYTES),
+        ]);
+        assert_eq!(
+            decode_session_archive(&traversal, SESSION_ARCHIVE_LIMITS).unwrap_err(),
+            ARCHIVE_READ_ERROR
+        );
+
+        let missing_attachment = zip_with(&[
+            (MANIFEST_ENTRY, &manifest),
+            (CONVERSATION_ENTRY, b"[]"),
+            (TIMELINE_ENTRY, b"[]"),
+        ]);
+        assert_eq!(
+            decode_session_archive(&missing_attachment, SESSION_ARCHIVE_LIMITS).unwrap_err(),
+            ARCHIVE_READ_ERROR
+        );
+
+        let mut unlisted_manifest = archive.manifest.clone();
+        unlisted_manifest.attachments.clear();
+        let unlisted_manifest = serde_json::to_vec(&unlisted_manifest).unwrap();
+        let unlisted = zip_with(&[
+            (MANIFEST_ENTRY, &unlisted_manifest),
+            (CONVERSATION_ENTRY, b"[]"),
+            (TIMELINE_ENTRY, b"[]"),
+            ("attachments/0123456789abcdef0123456789abcdef", PNG_BYTES),
+        ]);
+        assert_eq!(
+            decode_session_archive(&unlisted, SESSION_ARCHIVE_LIMITS).unwrap_err(),
+            ARCHIVE_READ_ERROR
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_versions_and_oversized_entries() {
+        let mut archive = sample_archive();
+        archive.manifest.version = SESSION_ARCHIVE_VERSION + 1;
+        let bytes = encode_session_archive(&archive, SESSION_ARCHIVE_LIMITS).unwrap();
+        assert!(decode_session_archive(&bytes, SESSION_ARCHIVE_LIMITS)
+            .unwrap_err()
+            .contains("format version"));
+
+        let bytes = encode_session_archive(&sample_archive(), SESSION_ARCHIVE_LIMITS).unwrap();
+        let limits = SessionArchiveLimits {
+            max_attachment_bytes: 4,
+            ..SESSION_ARCHIVE_LIMITS
+        };
+        assert_eq!(
+            decode_session_archive(&bytes, limits).unwrap_err(),
+            ARCHIVE_TOO_LARGE_ERROR
+        );
+
+        let limits = SessionArchiveLimits {
+            max_attachments: 0,
+            ..SESSION_ARCHIVE_LIMITS
+        };
+        assert_eq!(
+            decode_session_archive(&bytes, limits).unwrap_err(),
+            ARCHIVE_TOO_LARGE_ERROR
+        );
+        assert_eq!(
+            decode_session_archive(b"not a zip", SESSION_ARCHIVE_LIMITS).unwrap_err(),
+            ARCHIVE_READ_ERROR
+        );
+    }
+
+    #[test]
+    fn exports_stay_within_the_import_limits() {
+        let archive = sample_archive();
+        let limits = SessionArchiveLimits {
+            max_attachments: 0,
+            ..SESSION_ARCHIVE_LIMITS
+        };
+        assert!(encode_session_archive(&archive, limits)
+            .unwrap_err()
+            .contains("at most 0"));
+
+        let limits = SessionArchiveLimits {
+            max_attachment_bytes: 4,
+            ..SESSION_ARCHIVE_LIMITS
+        };
+        assert!(encode_session_archive(&archive, limits)
+            .unwrap_err()
+            .contains("one of its images"));
+
+        let limits = SessionArchiveLimits {
+            max_archive_bytes: 64,
+            ..SESSION_ARCHIVE_LIMITS
+        };
+        assert!(encode_session_archive(&archive, limits)
+            .unwrap_err()
+            .contains("the archive exceeds"));
+    }
+}
@@ -0,0 +1,471 @@
+//! Account-scoped full-text search over Agent task timelines.
+//!
+//! The index lives in memory for the account whose tasks it describes. A
+//! search first reconciles it with Goose's persisted task list, reloading only
+//! tasks whose `updated_ms` changed, and live timeline rows recorded during a
+//! run are folded in immediately so an in-progress task is searchable before
+//! Goose finishes persisting it.
+
+use super::{AgentSessionSummary, AgentTimelineItem};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub(super) const DEFAULT_SEARCH_RESULTS: usize = 20;
+pub(super) const MAX_SEARCH_RESULTS: usize = 100;
+pub(super) const MAX_SEARCH_QUERY_CHARS: usize = 200;
+const MAX_SEARCH_TERMS: usize = 16;
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+const MAX_TERM_HITS_PER_ITEM: usize = 10;
+const TITLE_HIT_WEIGHT: usize = 5;
+
+/// Tool argument keys that name files a tool touched.
+const PATH_ARGUMENT_KEYS: &[&str] = &["path", "file_path", "file", "paths", "files"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSessionSearchHit {
+    pub session: AgentSessionSummary,
+    /// The best matching timeline row, or `None` when only the title matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeline_item_id: Option<String>,
+    pub snippet: String,
+    pub score: usize,
+}
+
+#[derive(Clone, Default)]
+pub(super) struct SessionSearchIndex {
+    inner: Arc<Mutex<Option<AccountSearchIndex>>>,
+}
+
+struct AccountSearchIndex {
+    account_scope: String,
+    sessions: HashMap<String, IndexedSession>,
+}
+
+struct IndexedSession {
+    summary: AgentSessionSummary,
+    items: Vec<IndexedItem>,
+}
+
+struct IndexedItem {
+    id: String,
+    text: String,
+    folded: String,
+}
+
+impl SessionSearchIndex {
+    /// Discard any index that belongs to another account. Runtime start calls
+    /// this so live rows are only ever folded into the running account.
+    pub(super) async fn reset_for_account(&self, account_scope: &str) {
+        let mut index = self.inner.lock().await;
+        if index
+            .as_ref()
+            .is_none_or(|current| current.account_scope != account_scope)
+        {
+            *index = Some(AccountSearchIndex {
+                account_scope: account_scope.to_string(),
+                sessions: HashMap::new(),
+            });
+        }
+    }
+
+    pub(super) async fn clear(&self) {
+        *self.inner.lock().await = None;
+    }
+
+    /// Fold one merged live timeline row into an already indexed task.
+    /// Unindexed tasks are picked up from Goose history on the next search.
+    pub(super) async fn record_item(&self, session_id: &str, item: &AgentTimelineItem) {
+        let mut index = self.inner.lock().await;
+        let Some(session) = index
+            .as_mut()
+            .and_then(|index| index.sessions.get_mut(session_id))
+        else {
+            return;
+        };
+        let indexed = indexed_item(item);
+        match session
+            .items
+            .iter()
+            .position(|existing| existing.id == item.id)
+        {
+            Some(position) => match indexed {
+                Some(indexed) => session.items[position] = indexed,
+                None => {
+                    session.items.remove(position);
+                }
+            },
+            None => session.items.extend(indexed),
+        }
+    }
+
+    pub(super) async fn forget_session(&self, session_id: &str) {
+        if let Some(index) = self.inner.lock().await.as_mut() {
+            index.sessions.remove(session_id);
+        }
+    }
+
+    /// Align the index with the account's persisted tasks and return the IDs
+    /// whose timelines must be reloaded.
+    pub(super) async fn stale_sessions(
+        &self,
+        account_scope: &str,
+        sessions: &[AgentSessionSummary],
+    ) -> Vec<String> {
+        self.reset_for_account(account_scope).await;
+        let mut index = self.inner.lock().await;
+        let Some(index) = index.as_mut() else {
+            return Vec::new();
+        };
+        let listed = sessions
+            .iter()
+            .map(|session| session.id.as_str())
+            .collect::<HashSet<_>>();
+        index
+            .sessions
+            .retain(|session_id, _| listed.contains(session_id.as_str()));
+
+        let mut stale = Vec::new();
+        for summary in sessions {
+            match index.sessions.get_mut(&summary.id) {
+                Some(indexed) if indexed.summary.updated_ms == summary.updated_ms => {
+                    // Renames do not change history; keep the title current.
+                    indexed.summary = summary.clone();
+                }
+                _ => stale.push(summary.id.clone()),
+            }
+        }
+        stale
+    }
+
+    pub(super) async fn replace_session(
+        &self,
+        account_scope: &str,
+        summary: AgentSessionSummary,
+        timeline: &[AgentTimelineItem],
+    ) {
+        let mut index = self.inner.lock().await;
+        let Some(index) = index
+            .as_mut()
+            .filter(|index| index.account_scope == account_scope)
+        else {
+            return;
+        };
+        index.sessions.insert(
+            summary.id.clone(),
+            IndexedSession {
+                summary,
+                items: timeline.iter().filter_map(indexed_item).collect(),
+            },
+        );
+    }
+
+    pub(super) async fn search(
+        &self,
+        account_scope: &str,
+        query: &str,
+        project_root: Option<&Path>,
+        limit: usize,
+    ) -> Vec<AgentSessionSearchHit> {
+        let terms = search_terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let index = self.inner.lock().await;
+        let Some(index) = index
+            .as_ref()
+            .filter(|index| index.account_scope == account_scope)
+        else {
+            return Vec::new();
+        };
+        let project_root = project_root.map(|root| root.to_string_lossy().into_owned());
+        let mut hits = index
+            .sessions
+            .values()
+            .filter(|session| {
+                project_root
+                    .as_deref()
+                    .is_none_or(|root| session.summary.project_root == root)
+            })
+            .filter_map(|session| score_session(session, &terms))
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.session.updated_ms.cmp(&a.session.updated_ms))
+        });
+        hits.truncate(limit);
+        hits
+    }
+}
+
+fn score_session(session: &IndexedSession, terms: &[String]) -> Option<AgentSessionSearchHit> {
+    let folded_title = fold(&session.summary.title);
+    let mut matched = vec![false; terms.len()];
+    let mut score = 0;
+    for (term_index, term) in terms.iter().enumerate() {
+        let hits = folded_title.matches(term.as_str()).count();
+        if hits > 0 {
+            matched[term_index] = true;
+            score += hits.min(MAX_TERM_HITS_PER_ITEM) * TITLE_HIT_WEIGHT;
+        }
+    }
+
+    // Prefer the row that matches the most distinct terms, then the most
+    // occurrences, then the most recent row.
+    let mut best: Option<(&IndexedItem, usize, usize)> = None;
+    for item in &session.items {
+        let mut distinct = 0;
+        let mut occurrences = 0;
+        for (term_index, term) in terms.iter().enumerate() {
+            let hits = item.folded.matches(term.as_str()).count();
+            if hits > 0 {
+                matched[term_index] = true;
+                distinct += 1;
+                occurrences += hits.min(MAX_TERM_HITS_PER_ITEM);
+            }
+        }
+        if distinct == 0 {
+            continue;
+        }
+        score += occurrences;
+        if best.is_none_or(|(_, best_distinct, best_occurrences)| {
+            (distinct, occurrences) >= (best_distinct, best_occurrences)
+        }) {
+            best = Some((item, distinct, occurrences));
+        }
+    }
+    if !matched.iter().all(|matched| *matched) {
+        return None;
+    }
+
+    let (timeline_item_id, snippet) = match best {
+        Some((item, _, _)) => (Some(item.id.clone()), snippet(item, terms)),
+        None => (None, session.summary.title.clone()),
+    };
+    Some(AgentSessionSearchHit {
+        session: session.summary.clone(),
+        timeline_item_id,
+        snippet,
+        score,
+    })
+}
+
+fn indexed_item(item: &AgentTimelineItem) -> Option<IndexedItem> {
+    let text = match item.item_type.as_str() {
+        "message" => item.text.clone().unwrap_or_default(),
+        "tool" => {
+            let mut parts = item.title.iter().cloned().collect::<Vec<_>>();
+            if let Some(Value::Object(arguments)) = item.input.as_ref() {
+                for key in PATH_ARGUMENT_KEYS {
+                    match arguments.get(*key) {
+                        Some(Value::String(path)) => parts.push(path.clone()),
+                        Some(Value::Array(paths)) => parts.extend(
+                            paths
+                                .iter()
+                                .filter_map(Value::as_str)
+                                .map(ToOwned::to_owned),
+                        ),
+                        _ => {}
+                    }
+                }
+            }
+            parts.dedup();
+            parts.join("\n")
+        }
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    Some(IndexedItem {
+        id: item.id.clone(),
+        folded: fold(&text),
+        text,
+    })
+}
+
+fn search_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    fold(query)
+        .split_whitespace()
+        .filter(|term| seen.insert(term.to_string()))
+        .take(MAX_SEARCH_TERMS)
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Lowercase one character at a time so character offsets in the folded text
+/// line up with the original text when building snippets.
+fn fold(text: &str) -> String {
+    text.chars()
+        .map(|character| character.to_lowercase().next().unwrap_or(character))
+        .collect()
+}
+
+fn snippet(item: &IndexedItem, terms: &[String]) -> String {
+    let Some(byte_offset) = terms
+        .iter()
+        .filter_map(|term| item.folded.find(term.as_str()))
+        .min()
+    else {
+        return single_line(
+            &item
+                .text
+                .chars()
+                .take(SNIPPET_CONTEXT_CHARS * 2)
+                .collect::<String>(),
+        );
+    };
+    let match_start = item.folded[..byte_offset].chars().count();
+    let start = match_start.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let total = item.text.chars().count();
+    let end = (match_start + SNIPPET_CONTEXT_CHARS * 2).min(total);
+    let mut snippet = item
+        .text
+        .chars()
+        .skip(start)
+        .take(end - start)
+        .collect::<String>();
+    snippet = single_line(&snippet);
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < total {
+        snippet.push('…');
+    }
+    snippet
+}
+
+fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn summary(id: &str, title: &str, updated_ms: i64) -> AgentSessionSummary {
+        AgentSessionSummary {
+            id: id.to_string(),
+            title: title.to_string(),
+            project_root: "/work/app".to_string(),
+            created_ms: 1,
+            updated_ms,
+            message_count: 2,
+            model: None,
+            mode: "smart_approve".to_string(),
+        }
+    }
+
+    fn item(id: &str, item_type: &str, text: &str) -> AgentTimelineItem {
+        AgentTimelineItem {
+            id: id.to_string(),
+            item_type: item_type.to_string(),
+            role: Some("assistant".to_string()),
+            title: None,
+            text: Some(text.to_string()),
+            status: None,
+            input: None,
+            output: None,
+            created_ms: 1,
+            merge: "replace".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn ranks_sessions_and_points_at_the_matching_item() {
+        let index = SessionSearchIndex::default();
+        let sessions = vec![
+            summary("a", "Fix the migration", 10),
+            summary("b", "Unrelated", 20),
+        ];
+        assert_eq!(index.stale_sessions("acct", &sessions).await, ["a", "b"]);
+        index
+            .replace_session(
+                "acct",
+                sessions[0].clone(),
+                &[item(
+                    "a-1-text",
+                    "message",
+                    "The migration fails on Postgres",
+                )],
+            )
+            .await;
+        index
+            .replace_session(
+                "acct",
+                sessions[1].clone(),
+                &[
+                    item("b-1-text", "message", "Update the README"),
+                    AgentTimelineItem {
+                        title: Some("Editor: db/migrations/001.sql".to_string()),
+                        input: Some(json!({ "path": "db/migrations/001.sql" })),
+                        ..item("call-1", "tool", "")
+                    },
+                ],
+            )
+            .await;
+        assert!(index.stale_sessions("acct", &sessions).await.is_empty());
+
+        let hits = index.search("acct", "MIGRATION", None, 10).await;
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].session.id, "a");
+        assert_eq!(hits[0].timeline_item_id.as_deref(), Some("a-1-text"));
+        assert_eq!(hits[0].snippet, "The migration fails on Postgres");
+        assert_eq!(hits[1].timeline_item_id.as_deref(), Some("call-1"));
+
+        assert!(index
+            .search("acct", "migration readme postgres", None, 10)
+            .await
+            .is_empty());
+        assert!(index
+            .search("other", "migration", None, 10)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn live_items_update_indexed_sessions_until_history_changes() {
+        let index = SessionSearchIndex::default();
+        let sessions = vec![summary("a", "Task", 10)];
+        index.stale_sessions("acct", &sessions).await;
+        index
+            .replace_session("acct", sessions[0].clone(), &[])
+            .await;
+
+        index
+            .record_item("a", &item("a-2-text", "message", "Rotated the signing key"))
+            .await;
+        index
+            .record_item("unknown", &item("x-text", "message", "signing"))
+            .await;
+        let hits = index.search("acct", "signing", None, 10).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].timeline_item_id.as_deref(), Some("a-2-text"));
+
+        let updated = vec![summary("a", "Task", 11)];
+        assert_eq!(index.stale_sessions("acct", &updated).await, ["a"]);
+        index.forget_session("a").await;
+        assert!(index.search("acct", "signing", None, 10).await.is_empty());
+    }
+
+    #[test]
+    fn snippets_stay_on_character_boundaries() {
+        let text = format!("{}İstanbul migration notes", "é".repeat(100));
+        let item = IndexedItem {
+            id: "x".to_string(),
+            folded: fold(&text),
+            text,
+        };
+        let snippet = snippet(&item, &["migration".to_string()]);
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.contains("İstanbul migration notes"));
+    }
+}
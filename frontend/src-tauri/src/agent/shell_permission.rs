@@ -172,6 +172,33 @@ pub(crate) fn local_read_image_request_id<'a>(
     Some(id)
 }
 
+/// `glob` and `grep` only read; without a path they search the working
+/// directory.
+pub(crate) fn local_search_request_id<'a>(
+    mode: &str,
+    action: &'a ActionRequired,
+) -> Option<&'a str> {
+    if mode != READ_ONLY_MODE {
+        return None;
+    }
+    let ActionRequiredData::ToolConfirmation {
+        id,
+        tool_name,
+        arguments,
+        prompt,
+    } = &action.data
+    else {
+        return None;
+    };
+    if !matches!(tool_name.as_str(), "glob" | "grep") || prompt.is_some() {
+        return None;
+    }
+    match arguments.get("path") {
+        None | Some(serde_json::Value::Null) => Some(id),
+        Some(path) => (!is_remote_file_source(path.as_str()?)).then_some(id.as_str()),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum ShellPermissionOutcome {
     ReadOnly,
@@ -459,6 +486,42 @@ mod tests {
         assert!(local_read_image_request_id(READ_ONLY_MODE, &warned).is_none());
     }
 
+    #[test]
+    fn only_local_searches_are_automatically_eligible_in_read_only_mode() {
+        let glob = action("glob", object!({ "pattern": "**/*.rs" }), None);
+        assert_eq!(
+            local_search_request_id(READ_ONLY_MODE, &glob),
+            Some("request-1")
+        );
+        assert!(local_search_request_id("auto", &glob).is_none());
+
+        let grep = action("grep", object!({ "pattern": "TODO", "path": "src" }), None);
+        assert_eq!(
+            local_search_request_id(READ_ONLY_MODE, &grep),
+            Some("request-1")
+        );
+
+        let remote = action(
+            "grep",
+            object!({ "pattern": "TODO", "path": r"\\server\share" }),
+            None,
+        );
+        assert!(local_search_request_id(READ_ONLY_MODE, &remote).is_none());
+
+        let malformed = action("glob", object!({ "pattern": "*", "path": 42 }), None);
+        assert!(local_search_request_id(READ_ONLY_MODE, &malformed).is_none());
+
+        let warned = action(
+            "grep",
+            object!({ "pattern": "TODO" }),
+            Some("Security warning".to_string()),
+        );
+        assert!(local_search_request_id(READ_ONLY_MODE, &warned).is_none());
+
+        let shell = action("shell", object!({ "command": "rg TODO" }), None);
+        assert!(local_search_request_id(READ_ONLY_MODE, &shell).is_none());
+    }
+
     #[test]
     fn parses_exact_structured_decisions() {
         let read_only = response(
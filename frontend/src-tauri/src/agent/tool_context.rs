@@ -1,3 +1,5 @@
+use super::background_shell::BackgroundShells;
+use super::client_tools::AgentClientTools;
 use super::transient_mcp::TransientMcpRouter;
 use std::collections::{BTreeMap, BTreeSet};
 use std::sync::{Arc, Mutex, MutexGuard, RwLock};
@@ -18,6 +20,7 @@ pub(crate) struct AgentToolContextSpec {
     values: BTreeMap<String, String>,
     scrub_from_parent: BTreeSet<String>,
     ephemeral: bool,
+    client_tools: Option<Arc<dyn AgentClientTools>>,
 }
 
 impl AgentToolContextSpec {
@@ -63,8 +66,17 @@ impl AgentToolContextSpec {
             values,
             scrub_from_parent,
             ephemeral,
+            client_tools: None,
         })
     }
+
+    /// Let the surface perform file and terminal operations it advertises.
+    pub(crate) fn with_client_tools(mut self, client_tools: Arc<dyn AgentClientTools>) -> Self {
+        if !client_tools.capabilities().is_empty() {
+            self.client_tools = Some(client_tools);
+        }
+        self
+    }
 }
 
 fn validate_key(key: &str) -> Result<(), String> {
@@ -84,6 +96,7 @@ struct AgentToolContextState {
     scrub_from_parent: BTreeSet<String>,
     ephemeral: bool,
     transient_mcp: Option<TransientMcpRouter>,
+    client_tools: Option<Arc<dyn AgentClientTools>>,
 }
 
 #[derive(Clone)]
@@ -91,6 +104,7 @@ pub(crate) struct SharedAgentToolContext {
     state: Arc<RwLock<AgentToolContextState>>,
     revoked: CancellationToken,
     launch_gate: Arc<Mutex<()>>,
+    background_shells: BackgroundShells,
 }
 
 impl SharedAgentToolContext {
@@ -101,9 +115,11 @@ impl SharedAgentToolContext {
                 scrub_from_parent: spec.scrub_from_parent,
                 ephemeral: spec.ephemeral,
                 transient_mcp: None,
+                client_tools: spec.client_tools,
             })),
             revoked: CancellationToken::new(),
             launch_gate: Arc::new(Mutex::new(())),
+            background_shells: BackgroundShells::default(),
         }
     }
 
@@ -125,6 +141,12 @@ impl SharedAgentToolContext {
         self.revoked.clone()
     }
 
+    /// Background shell commands started from this context. They are stopped
+    /// when the context is revoked.
+    pub(crate) fn background_shells(&self) -> &BackgroundShells {
+        &self.background_shells
+    }
+
     pub(crate) fn install_transient_mcp(&self, router: TransientMcpRouter) -> Result<(), String> {
         let mut state = self
             .state
@@ -150,6 +172,15 @@ impl SharedAgentToolContext {
             .clone()
     }
 
+    /// The surface's own file and terminal backend, until revocation.
+    pub(crate) fn client_tools(&self) -> Option<Arc<dyn AgentClientTools>> {
+        self.state
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .client_tools
+            .clone()
+    }
+
     pub(crate) fn revoke(&self) {
         // Linearize revocation with command construction and spawn. Once this
         // method returns, no snapshot taken before revocation can launch a new
@@ -159,6 +190,7 @@ impl SharedAgentToolContext {
             .lock()
             .unwrap_or_else(std::sync::PoisonError::into_inner);
         self.revoked.cancel();
+        self.background_shells.clear();
         let mut state = self
             .state
             .write()
@@ -166,6 +198,7 @@ impl SharedAgentToolContext {
         state.values.clear();
         state.ephemeral = false;
         state.transient_mcp.take();
+        state.client_tools.take();
         // Retain inherited-key scrubbing after revocation. A removed explicit
         // credential must never reveal a same-named ambient process value.
     }
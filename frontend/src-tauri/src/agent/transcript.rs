@@ -0,0 +1,535 @@
+//! Shareable Markdown and HTML transcripts of an Agent task.
+//!
+//! Transcripts are rendered from the same timeline projection the Desktop
+//! shows, so tool calls, permission cards, and notices read the way they did
+//! in Maple. HTML output is a single self-contained document: every piece of
+//! task text is escaped and images are inlined as data URLs.
+
+use super::attachments::AgentImageAttachment;
+use super::{bounded_timeline_text, AgentSessionSummary, AgentTimelineItem, AgentTranscriptFormat};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+const MAX_TRANSCRIPT_TOOL_CHARS: usize = 20_000;
+
+pub(super) struct TranscriptOptions<'a> {
+    pub format: AgentTranscriptFormat,
+    pub include_thinking: bool,
+    /// Data URLs for the `maple-attachment://` images the timeline
+    /// references, keyed by attachment ID. Missing images render as a note.
+    pub images: &'a HashMap<String, String>,
+}
+
+/// Attachment IDs referenced by message rows, in first-seen order.
+pub(super) fn transcript_attachment_ids(timeline: &[AgentTimelineItem]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    timeline
+        .iter()
+        .flat_map(item_image_attachments)
+        .map(|attachment| attachment.id)
+        .filter(|id| seen.insert(id.clone()))
+        .collect()
+}
+
+pub(super) fn render_transcript(
+    session: &AgentSessionSummary,
+    timeline: &[AgentTimelineItem],
+    options: &TranscriptOptions<'_>,
+) -> String {
+    let blocks = timeline
+        .iter()
+        .filter_map(|item| transcript_block(item, options.include_thinking))
+        .collect::<Vec<_>>();
+    match options.format {
+        AgentTranscriptFormat::Markdown => render_markdown(session, &blocks, options.images),
+        AgentTranscriptFormat::Html => render_html(session, &blocks, options.images),
+    }
+}
+
+/// One timeline row reduced to what a transcript shows.
+enum TranscriptBlock {
+    Message {
+        role: String,
+        text: String,
+        images: Vec<AgentImageAttachment>,
+    },
+    Thinking(String),
+    Tool {
+        title: String,
+        status: String,
+        input: Option<String>,
+        output: Option<String>,
+    },
+    Permission {
+        title: String,
+        prompt: Option<String>,
+        decision: &'static str,
+    },
+    Notice {
+        label: String,
+        text: String,
+    },
+}
+
+fn transcript_block(item: &AgentTimelineItem, include_thinking: bool) -> Option<TranscriptBlock> {
+    let text = item.text.clone().unwrap_or_default();
+    match item.item_type.as_str() {
+        "message" => {
+            let images = item_image_attachments(item);
+            if text.trim().is_empty() && images.is_empty() {
+                return None;
+            }
+            Some(TranscriptBlock::Message {
+                role: match item.role.as_deref() {
+                    Some("user") => "User",
+                    _ => "Assistant",
+                }
+                .to_string(),
+                text,
+                images,
+            })
+        }
+        "thinking" if include_thinking && !text.trim().is_empty() => {
+            Some(TranscriptBlock::Thinking(text))
+        }
+        "thinking" => None,
+        "tool" => {
+            let input = item
+                .input
+                .as_ref()
+                .filter(|input| !input.is_null())
+                .map(|input| serde_json::to_string_pretty(input).unwrap_or_default());
+            let output = item
+                .output
+                .as_ref()
+                .and_then(|output| output.get("text"))
+                .and_then(Value::as_str)
+                .filter(|output| !output.is_empty())
+                .map(ToOwned::to_owned)
+                .or_else(|| (!text.is_empty()).then(|| text.clone()));
+            Some(TranscriptBlock::Tool {
+                title: item.title.clone().unwrap_or_else(|| "Tool".to_string()),
+                status: item.status.clone().unwrap_or_else(|| "running".to_string()),
+                input: input.map(|input| bounded_timeline_text(&input, MAX_TRANSCRIPT_TOOL_CHARS)),
+                output: output
+                    .map(|output| bounded_timeline_text(&output, MAX_TRANSCRIPT_TOOL_CHARS)),
+            })
+        }
+        "permission" => Some(TranscriptBlock::Permission {
+            title: item
+                .title
+                .clone()
+                .unwrap_or_else(|| "Permission".to_string()),
+            prompt: item.text.clone().filter(|prompt| !prompt.trim().is_empty()),
+            decision: permission_decision_label(item.status.as_deref()),
+        }),
+        "system" | "error" if !text.trim().is_empty() => Some(TranscriptBlock::Notice {
+            label: item.title.clone().unwrap_or_else(|| {
+                if item.item_type == "error" {
+                    "Error".to_string()
+                } else {
+                    "Agent notice".to_string()
+                }
+            }),
+            text,
+        }),
+        _ => None,
+    }
+}
+
+fn permission_decision_label(status: Option<&str>) -> &'static str {
+    match status {
+        Some("allow_once") => "Allowed once",
+        Some("deny_once") => "Denied",
+        Some("cancelled") => "Cancelled",
+        Some("controlled_externally") => "Answered by another Agent surface",
+        Some("completed") => "Answered",
+        Some("boundary_blocked") => "Blocked by the filesystem boundary",
+        _ => "Awaiting a decision",
+    }
+}
+
+fn item_image_attachments(item: &AgentTimelineItem) -> Vec<AgentImageAttachment> {
+    if item.item_type != "message" {
+        return Vec::new();
+    }
+    item.input
+        .as_ref()
+        .and_then(|input| input.get("imageAttachments"))
+        .cloned()
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn render_markdown(
+    session: &AgentSessionSummary,
+    blocks: &[TranscriptBlock],
+    images: &HashMap<String, String>,
+) -> String {
+    let mut out = format!("# {}\n\n", single_line(&session.title));
+    out.push_str(&format!(
+        "- Project: {}\n",
+        inline_code(&session.project_root)
+    ));
+    if let Some(model) = &session.model {
+        out.push_str(&format!("- Model: {}\n", inline_code(model)));
+    }
+    out.push_str(&format!("- Task ID: {}\n", inline_code(&session.id)));
+
+    for block in blocks {
+        out.push('\n');
+        match block {
+            TranscriptBlock::Message {
+                role,
+                text,
+                images: attachments,
+            } => {
+                out.push_str(&format!("## {role}\n\n"));
+                if !text.trim().is_empty() {
+                    out.push_str(text.trim_end());
+                    out.push('\n');
+                }
+                for attachment in attachments {
+                    let alt = attachment.name.replace(['[', ']', '\n'], " ");
+                    match images.get(&attachment.id) {
+                        Some(data_url) => out.push_str(&format!("\n![{alt}]({data_url})\n")),
+                        None => out.push_str(&format!("\n_Image unavailable: {alt}_\n")),
+                    }
+                }
+            }
+            TranscriptBlock::Thinking(text) => {
+                out.push_str("> **Thinking**\n>\n");
+                for line in text.trim_end().lines() {
+                    out.push_str(&format!("> {line}\n"));
+                }
+            }
+            TranscriptBlock::Tool {
+                title,
+                status,
+                input,
+                output,
+            } => {
+                out.push_str(&format!("**Tool:** {} ({status})\n", single_line(title)));
+                if let Some(input) = input {
+                    out.push('\n');
+                    out.push_str(&fenced_block("json", input));
+                }
+                if let Some(output) = output {
+                    out.push('\n');
+                    out.push_str(&fenced_block("text", output));
+                }
+            }
+            TranscriptBlock::Permission {
+                title,
+                prompt,
+                decision,
+            } => {
+                out.push_str(&format!(
+                    "> **Permission:** {} — _{decision}_\n",
+                    single_line(title)
+                ));
+                if let Some(prompt) = prompt {
+                    out.push_str(">\n");
+                    for line in prompt.trim_end().lines() {
+                        out.push_str(&format!("> {line}\n"));
+                    }
+                }
+            }
+            TranscriptBlock::Notice { label, text } => {
+                out.push_str(&format!(
+                    "> **{}:** {}\n",
+                    single_line(label),
+                    single_line(text)
+                ));
+            }
+        }
+    }
+    out
+}
+
+fn render_html(
+    session: &AgentSessionSummary,
+    blocks: &[TranscriptBlock],
+    images: &HashMap<String, String>,
+) -> String {
+    let title = escape_html(&session.title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"Content-Security-Policy\" content=\"default-src 'none'; img-src data:; style-src 'unsafe-inline'\">\n\
+         <title>{title}</title>\n<style>{TRANSCRIPT_CSS}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<dl class=\"meta\">\n"
+    );
+    out.push_str(&format!(
+        "<dt>Project</dt><dd><code>{}</code></dd>\n",
+        escape_html(&session.project_root)
+    ));
+    if let Some(model) = &session.model {
+        out.push_str(&format!(
+            "<dt>Model</dt><dd><code>{}</code></dd>\n",
+            escape_html(model)
+        ));
+    }
+    out.push_str(&format!(
+        "<dt>Task ID</dt><dd><code>{}</code></dd>\n</dl>\n",
+        escape_html(&session.id)
+    ));
+
+    for block in blocks {
+        match block {
+            TranscriptBlock::Message {
+                role,
+                text,
+                images: attachments,
+            } => {
+                out.push_str(&format!(
+                    "<section class=\"message {}\">\n<h2>{role}</h2>\n",
+                    role.to_ascii_lowercase()
+                ));
+                if !text.trim().is_empty() {
+                    out.push_str(&format!(
+                        "<div class=\"text\">{}</div>\n",
+                        escape_html(text.trim_end())
+                    ));
+                }
+                for attachment in attachments {
+                    let alt = escape_html(&attachment.name);
+                    match images.get(&attachment.id) {
+                        Some(data_url) => out.push_str(&format!(
+                            "<img alt=\"{alt}\" src=\"{}\">\n",
+                            escape_html(data_url)
+                        )),
+                        None => out.push_str(&format!(
+                            "<p class=\"note\">Image unavailable: {alt}</p>\n"
+                        )),
+                    }
+                }
+                out.push_str("</section>\n");
+            }
+            TranscriptBlock::Thinking(text) => out.push_str(&format!(
+                "<details class=\"thinking\">\n<summary>Thinking</summary>\n<div class=\"text\">{}</div>\n</details>\n",
+                escape_html(text.trim_end())
+            )),
+            TranscriptBlock::Tool {
+                title,
+                status,
+                input,
+                output,
+            } => {
+                out.push_str(&format!(
+                    "<details class=\"tool {}\">\n<summary>{} <span class=\"status\">{}</span></summary>\n",
+                    escape_html(status),
+                    escape_html(title),
+                    escape_html(status)
+                ));
+                if let Some(input) = input {
+                    out.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(input)));
+                }
+                if let Some(output) = output {
+                    out.push_str(&format!(
+                        "<pre class=\"output\"><code>{}</code></pre>\n",
+                        escape_html(output)
+                    ));
+                }
+                out.push_str("</details>\n");
+            }
+            TranscriptBlock::Permission {
+                title,
+                prompt,
+                decision,
+            } => {
+                out.push_str(&format!(
+                    "<aside class=\"permission\"><strong>Permission:</strong> {} — <em>{decision}</em>",
+                    escape_html(title)
+                ));
+                if let Some(prompt) = prompt {
+                    out.push_str(&format!(
+                        "<div class=\"text\">{}</div>",
+                        escape_html(prompt.trim_end())
+                    ));
+                }
+                out.push_str("</aside>\n");
+            }
+            TranscriptBlock::Notice { label, text } => out.push_str(&format!(
+                "<aside class=\"notice\"><strong>{}:</strong> {}</aside>\n",
+                escape_html(label),
+                escape_html(text)
+            )),
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+const TRANSCRIPT_CSS: &str = "body{font-family:system-ui,sans-serif;max-width:860px;margin:2rem auto;padding:0 1rem;line-height:1.5;color:#1f2328}\
+.meta dt{font-weight:600;float:left;clear:left;width:6rem}.meta dd{margin:0 0 .25rem 6rem}\
+section{margin:1.5rem 0}h2{font-size:1rem;margin:0 0 .5rem}.text{white-space:pre-wrap}\
+details{border:1px solid #d0d7de;border-radius:6px;padding:.5rem .75rem;margin:.75rem 0}\
+summary{cursor:pointer;font-weight:600}.status{font-weight:400;color:#59636e;margin-left:.5rem}\
+pre{background:#f6f8fa;padding:.75rem;overflow-x:auto;white-space:pre-wrap}\
+aside{border-left:3px solid #d0d7de;padding:.25rem .75rem;margin:.75rem 0;color:#59636e}\
+img{max-width:100%;display:block;margin:.5rem 0}.note{color:#59636e;font-style:italic}";
+
+/// Wrap `content` in a code fence longer than any backtick run it contains.
+fn fenced_block(language: &str, content: &str) -> String {
+    let longest_run = content
+        .split(|character| character != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!(
+        "{fence}{language}\n{}\n{fence}\n",
+        content.trim_end_matches('\n')
+    )
+}
+
+fn inline_code(value: &str) -> String {
+    let value = single_line(value);
+    let longest_run = value
+        .split(|character| character != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let ticks = "`".repeat(longest_run + 1);
+    if longest_run > 0 {
+        format!("{ticks} {value} {ticks}")
+    } else {
+        format!("{ticks}{value}{ticks}")
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(id: &str, item_type: &str, role: &str) -> AgentTimelineItem {
+        AgentTimelineItem {
+            id: id.to_string(),
+            item_type: item_type.to_string(),
+            role: Some(role.to_string()),
+            title: None,
+            text: None,
+            status: None,
+            input: None,
+            output: None,
+            created_ms: 1,
+            merge: "replace".to_string(),
+        }
+    }
+
+    fn session() -> AgentSessionSummary {
+        AgentSessionSummary {
+            id: "20260101_1".to_string(),
+            title: "Fix the <migration>".to_string(),
+            project_root: "/work/app".to_string(),
+            created_ms: 1,
+            updated_ms: 2,
+            message_count: 4,
+            model: Some("model-a".to_string()),
+            mode: "smart_approve".to_string(),
+        }
+    }
+
+    fn timeline() -> Vec<AgentTimelineItem> {
+        let id = "0123456789abcdef0123456789abcdef";
+        vec![
+            AgentTimelineItem {
+                text: Some("Run the tests".to_string()),
+                input: Some(json!({
+                    "imageAttachments": [{
+                        "id": id,
+                        "name": "failure.png",
+                        "mimeType": "image/png",
+                        "source": format!("maple-attachment://{id}"),
+                    }],
+                })),
+                ..item("m1-text", "message", "user")
+            },
+            AgentTimelineItem {
+                text: Some("Consider ```fences```".to_string()),
+                title: Some("Thinking".to_string()),
+                ..item("m2-thinking", "thinking", "thought")
+            },
+            AgentTimelineItem {
+                title: Some("Run tests".to_string()),
+                status: Some("deny_once".to_string()),
+                text: Some("Allow cargo test?".to_string()),
+                ..item("permission-call-1", "permission", "system")
+            },
+            AgentTimelineItem {
+                title: Some("Terminal: cargo test".to_string()),
+                status: Some("completed".to_string()),
+                input: Some(json!({ "command": "cargo test" })),
+                output: Some(json!({ "text": "```\nok\n```" })),
+                ..item("call-1", "tool", "assistant")
+            },
+        ]
+    }
+
+    #[test]
+    fn markdown_fences_tools_and_annotates_permissions() {
+        let images = HashMap::from([(
+            "0123456789abcdef0123456789abcdef".to_string(),
+            "data:image/png;base64,AAAA".to_string(),
+        )]);
+        let markdown = render_transcript(
+            &session(),
+            &timeline(),
+            &TranscriptOptions {
+                format: AgentTranscriptFormat::Markdown,
+                include_thinking: false,
+                images: &images,
+            },
+        );
+
+        assert!(markdown.starts_with("# Fix the <migration>\n"));
+        assert!(markdown.contains("## User\n\nRun the tests\n"));
+        assert!(markdown.contains("![failure.png](data:image/png;base64,AAAA)"));
+        assert!(markdown.contains("> **Permission:** Run tests — _Denied_"));
+        assert!(markdown.contains("**Tool:** Terminal: cargo test (completed)"));
+        assert!(markdown.contains("````text\n```\nok\n```\n````\n"));
+        assert!(!markdown.contains("Thinking"));
+    }
+
+    #[test]
+    fn html_is_escaped_and_thinking_is_optional() {
+        let html = render_transcript(
+            &session(),
+            &timeline(),
+            &TranscriptOptions {
+                format: AgentTranscriptFormat::Html,
+                include_thinking: true,
+                images: &HashMap::new(),
+            },
+        );
+
+        assert!(html.contains("<title>Fix the &lt;migration&gt;</title>"));
+        assert!(!html.contains("<migration>"));
+        assert!(html.contains("<summary>Thinking</summary>"));
+        assert!(html.contains("Image unavailable: failure.png"));
+        assert!(html.contains("<em>Denied</em>"));
+        assert_eq!(
+            transcript_attachment_ids(&timeline()),
+            vec!["0123456789abcdef0123456789abcdef".to_string()]
+        );
+    }
+}
@@ -6,6 +6,7 @@
 //! cancellation token; revoking it fails in-flight calls closed and tears down
 //! the transport.
 
+use super::mcp_sampling::MapleMcpClientHandler;
 use goose::agents::mcp_client::{Error as McpError, McpClientTrait};
 use goose::agents::ToolCallContext;
 use goose::session_context::{SESSION_ID_HEADER, TOOL_CALL_REQUEST_ID_HEADER, WORKING_DIR_HEADER};
@@ -18,7 +19,7 @@ use rmcp::model::{
 };
 use rmcp::service::{PeerRequestOptions, RoleClient, RunningService};
 use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
-use rmcp::transport::StreamableHttpClientTransport;
+use rmcp::transport::{StreamableHttpClientTransport, Transport};
 use rmcp::{Peer, ServiceError, ServiceExt};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
@@ -49,6 +50,10 @@ pub(crate) struct TransientMcpConfig {
     pub(crate) request_timeout: Duration,
     pub(crate) url: String,
     pub(crate) headers: Vec<(String, String)>,
+    /// When non-empty, only these tools enter the catalog.
+    pub(crate) allowed_tools: Vec<String>,
+    /// Tools that never enter the catalog and therefore cannot be routed.
+    pub(crate) blocked_tools: Vec<String>,
 }
 
 /// A cloneable, secret-free descriptor used internally by the frozen router.
@@ -59,6 +64,18 @@ pub(crate) struct TransientMcpConfig {
 struct TransientMcpDescriptor {
     key: Arc<str>,
     client: Arc<TransientMcpClient>,
+    tool_filter: Arc<TransientMcpToolFilter>,
+}
+
+struct TransientMcpToolFilter {
+    allowed: HashSet<String>,
+    blocked: HashSet<String>,
+}
+
+impl TransientMcpToolFilter {
+    fn permits(&self, name: &str) -> bool {
+        !self.blocked.contains(name) && (self.allowed.is_empty() || self.allowed.contains(name))
+    }
 }
 
 impl fmt::Debug for TransientMcpDescriptor {
@@ -99,6 +116,10 @@ impl TransientMcpDescriptor {
             ));
         }
 
+        let tool_filter = TransientMcpToolFilter {
+            allowed: config.allowed_tools.into_iter().collect(),
+            blocked: config.blocked_tools.into_iter().collect(),
+        };
         let client = TransientMcpClient::connect_streamable_http(
             config.url,
             config.headers,
@@ -110,6 +131,7 @@ impl TransientMcpDescriptor {
         Ok(Self {
             key: key.into(),
             client,
+            tool_filter: Arc::new(tool_filter),
         })
     }
 
@@ -353,6 +375,11 @@ async fn discover_tools(
                     TransientMcpConnectError::DuplicateTool
                 });
             }
+            // Filtered tools are never advertised, so no public name routes
+            // to them either.
+            if !descriptor.tool_filter.permits(original_name) {
+                continue;
+            }
             if tools.len() >= TRANSIENT_MCP_MAX_TOOLS_PER_SERVER {
                 return Err(TransientMcpConnectError::CatalogTooLarge);
             }
@@ -493,7 +520,7 @@ pub(crate) struct TransientMcpClient {
     server_info: Option<InitializeResult>,
     request_timeout: Duration,
     closed: CancellationToken,
-    running: Mutex<Option<RunningService<RoleClient, ()>>>,
+    running: Mutex<Option<RunningService<RoleClient, MapleMcpClientHandler>>>,
 }
 
 impl TransientMcpClient {
@@ -522,8 +549,29 @@ impl TransientMcpClient {
             // response; callers must make retry decisions at a higher layer.
             .reinit_on_expired_session(false);
         let transport = StreamableHttpClientTransport::with_client(client, transport_config);
+        Self::serve(
+            transport,
+            MapleMcpClientHandler::default(),
+            request_timeout,
+            lease_cancel,
+        )
+        .await
+    }
+
+    /// Initialize an MCP session over an already-open transport, answering
+    /// server requests with `handler`. The client shuts down when
+    /// `lease_cancel` fires or the last reference is dropped.
+    pub(crate) async fn serve<T>(
+        transport: T,
+        handler: MapleMcpClientHandler,
+        request_timeout: Duration,
+        lease_cancel: CancellationToken,
+    ) -> Result<Arc<Self>, TransientMcpConnectError>
+    where
+        T: Transport<RoleClient> + Send + 'static,
+    {
         let closed = lease_cancel.child_token();
-        let connect = ().serve(transport);
+        let connect = handler.serve(transport);
         tokio::pin!(connect);
         let running = tokio::select! {
             biased;
@@ -0,0 +1,432 @@
+/// Lines of unchanged context around each hunk.
+pub(crate) const DIFF_CONTEXT_LINES: usize = 3;
+/// Edit distance beyond which the diff stops searching for a minimal script
+/// and reports the differing middle as one replacement. The output stays a
+/// correct unified diff; it is only less precise for wholesale rewrites.
+const MAX_EDIT_DISTANCE: usize = 2_000;
+/// Inputs larger than this, combined, are not worth diffing interactively.
+pub(crate) const MAX_DIFF_INPUT_BYTES: usize = 2 * 1024 * 1024;
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file\n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnifiedDiff {
+    pub(crate) text: String,
+    pub(crate) truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Render a unified diff between two texts, bounded to `max_chars`. Lines are
+/// compared with their terminators so a missing final newline is reported the
+/// way `diff -u` does.
+pub(crate) fn unified_diff(
+    old: &str,
+    new: &str,
+    old_label: &str,
+    new_label: &str,
+    max_chars: usize,
+) -> UnifiedDiff {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = diff_ops(&old_lines, &new_lines);
+    let mut output = BoundedOutput::new(max_chars);
+    if ops.iter().all(|op| matches!(op, DiffOp::Equal(..))) {
+        return output.finish();
+    }
+    output.push(&format!("--- {old_label}\n+++ {new_label}\n"));
+
+    for hunk in hunk_ranges(&ops) {
+        let ops = &ops[hunk];
+        let (old_start, old_count) = hunk_span(ops, |op| match op {
+            DiffOp::Equal(old, _) | DiffOp::Delete(old) => Some(*old),
+            DiffOp::Insert(_) => None,
+        });
+        let (new_start, new_count) = hunk_span(ops, |op| match op {
+            DiffOp::Equal(_, new) | DiffOp::Insert(new) => Some(*new),
+            DiffOp::Delete(_) => None,
+        });
+        let old_start = old_start.unwrap_or_else(|| position_before(ops, true));
+        let new_start = new_start.unwrap_or_else(|| position_before(ops, false));
+        if !output.push(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        )) {
+            break;
+        }
+        for op in ops {
+            let (prefix, line) = match *op {
+                DiffOp::Equal(old, _) => (' ', old_lines[old]),
+                DiffOp::Delete(old) => ('-', old_lines[old]),
+                DiffOp::Insert(new) => ('+', new_lines[new]),
+            };
+            let body = line.strip_suffix('\n').unwrap_or(line);
+            let mut rendered = format!("{prefix}{body}\n");
+            if !line.ends_with('\n') {
+                rendered.push_str(NO_NEWLINE_MARKER);
+            }
+            if !output.push(&rendered) {
+                return output.finish();
+            }
+        }
+    }
+    output.finish()
+}
+
+/// Recover the old text of a diff rendered by [`unified_diff`] from its new
+/// text. Returns `None` when `new` does not match the hunks, so a file that
+/// changed after the diff was taken is never paired with a wrong original.
+pub(crate) fn reverse_unified_diff(new: &str, diff: &str) -> Option<String> {
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let mut old = String::with_capacity(new.len());
+    let mut next = 0;
+    let mut lines = diff
+        .split_inclusive('\n')
+        .skip_while(|line| !line.starts_with("@@ "))
+        .peekable();
+    while let Some(header) = lines.next() {
+        let start = hunk_new_start(header)?;
+        if start < next || start > new_lines.len() {
+            return None;
+        }
+        old.extend(new_lines[next..start].iter().copied());
+        next = start;
+        while let Some(line) = lines.next_if(|line| !line.starts_with("@@ ")) {
+            let (prefix, body) = line.split_at_checked(1)?;
+            let body = match lines.next_if_eq(&NO_NEWLINE_MARKER) {
+                Some(_) => body.strip_suffix('\n')?,
+                None => body,
+            };
+            match prefix {
+                " " | "+" => {
+                    if new_lines.get(next) != Some(&body) {
+                        return None;
+                    }
+                    next += 1;
+                    if prefix == " " {
+                        old.push_str(body);
+                    }
+                }
+                "-" => old.push_str(body),
+                _ => return None,
+            }
+        }
+    }
+    old.extend(new_lines[next..].iter().copied());
+    Some(old)
+}
+
+/// The zero-based index of the first new-side line a hunk header covers. An
+/// empty side names the line before it, which is already that index.
+fn hunk_new_start(header: &str) -> Option<usize> {
+    let range = header
+        .strip_prefix("@@ -")?
+        .split_once(" +")?
+        .1
+        .split_once(" @@")?
+        .0;
+    let (start, count) = match range.split_once(',') {
+        Some((start, count)) => (start.parse::<usize>().ok()?, count.parse::<usize>().ok()?),
+        None => (range.parse::<usize>().ok()?, 1),
+    };
+    if count == 0 {
+        Some(start)
+    } else {
+        start.checked_sub(1)
+    }
+}
+
+struct BoundedOutput {
+    text: String,
+    max_chars: usize,
+    chars: usize,
+    truncated: bool,
+}
+
+impl BoundedOutput {
+    fn new(max_chars: usize) -> Self {
+        Self {
+            text: String::new(),
+            max_chars,
+            chars: 0,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, value: &str) -> bool {
+        if self.truncated {
+            return false;
+        }
+        let chars = value.chars().count();
+        if self.chars + chars > self.max_chars {
+            self.truncated = true;
+            return false;
+        }
+        self.chars += chars;
+        self.text.push_str(value);
+        true
+    }
+
+    fn finish(self) -> UnifiedDiff {
+        UnifiedDiff {
+            text: self.text,
+            truncated: self.truncated,
+        }
+    }
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    // Unified diff line numbers are 1-based; an empty side names the line
+    // before the insertion point.
+    let start = if count == 0 { start } else { start + 1 };
+    if count == 1 {
+        start.to_string()
+    } else {
+        format!("{start},{count}")
+    }
+}
+
+fn hunk_span(ops: &[DiffOp], line: impl Fn(&DiffOp) -> Option<usize>) -> (Option<usize>, usize) {
+    let mut lines = ops.iter().filter_map(line);
+    let first = lines.next();
+    (first, first.map_or(0, |_| 1 + lines.count()))
+}
+
+fn position_before(ops: &[DiffOp], old_side: bool) -> usize {
+    // A side with no lines in this hunk is anchored after the last line that
+    // precedes it; without context that is where the opposite side begins.
+    ops.iter()
+        .find_map(|op| match (*op, old_side) {
+            (DiffOp::Insert(new), true) => Some(new),
+            (DiffOp::Delete(old), false) => Some(old),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+fn hunk_ranges(ops: &[DiffOp]) -> Vec<std::ops::Range<usize>> {
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(..)))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+    for index in changes {
+        let start = index.saturating_sub(DIFF_CONTEXT_LINES);
+        let end = (index + 1 + DIFF_CONTEXT_LINES).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+fn diff_ops(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops = (0..prefix)
+        .map(|index| DiffOp::Equal(index, index))
+        .collect::<Vec<_>>();
+    match myers(old_middle, new_middle) {
+        Some(middle) => ops.extend(middle.into_iter().map(|op| match op {
+            DiffOp::Equal(old, new) => DiffOp::Equal(old + prefix, new + prefix),
+            DiffOp::Delete(old) => DiffOp::Delete(old + prefix),
+            DiffOp::Insert(new) => DiffOp::Insert(new + prefix),
+        })),
+        None => {
+            ops.extend((0..old_middle.len()).map(|old| DiffOp::Delete(old + prefix)));
+            ops.extend((0..new_middle.len()).map(|new| DiffOp::Insert(new + prefix)));
+        }
+    }
+    let old_suffix_start = old.len() - suffix;
+    let new_suffix_start = new.len() - suffix;
+    ops.extend(
+        (0..suffix).map(|index| DiffOp::Equal(old_suffix_start + index, new_suffix_start + index)),
+    );
+    ops
+}
+
+/// Myers' O(ND) shortest edit script. Each step keeps only the diagonals it
+/// can reach, so memory grows with the square of the edit distance rather
+/// than with the file size.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<DiffOp>> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max.min(MAX_EDIT_DISTANCE) as isize {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+            k += 2;
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<DiffOp> {
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        if d == 0 {
+            while x > 0 && y > 0 {
+                x -= 1;
+                y -= 1;
+                ops.push(DiffOp::Equal(x as usize, y as usize));
+            }
+            break;
+        }
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            ops.push(DiffOp::Equal(x as usize, y as usize));
+        }
+        if x == previous_x {
+            ops.push(DiffOp::Insert(previous_y as usize));
+        } else {
+            ops.push(DiffOp::Delete(previous_x as usize));
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_minimal_hunks_with_context_and_line_numbers() {
+        let old = (1..=12)
+            .map(|line| format!("line {line}\n"))
+            .collect::<String>();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 11\n", "line 11\nline 11.5\n");
+
+        let diff = unified_diff(&old, &new, "a/notes.txt", "b/notes.txt", 10_000);
+
+        assert!(!diff.truncated);
+        assert_eq!(
+            diff.text,
+            "--- a/notes.txt\n+++ b/notes.txt\n\
+             @@ -1,5 +1,5 @@\n line 1\n-line 2\n+line two\n line 3\n line 4\n line 5\n\
+             @@ -9,4 +9,5 @@\n line 9\n line 10\n line 11\n+line 11.5\n line 12\n"
+        );
+    }
+
+    #[test]
+    fn reports_created_files_missing_newlines_and_truncation() {
+        let created = unified_diff("", "one\ntwo", "/dev/null", "b/new.txt", 10_000);
+        assert_eq!(
+            created.text,
+            "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+one\n+two\n\\ No newline at end of file\n"
+        );
+
+        assert_eq!(unified_diff("same\n", "same\n", "a", "b", 10).text, "");
+
+        let large = (0..1_000)
+            .map(|line| format!("{line}\n"))
+            .collect::<String>();
+        let bounded = unified_diff("", &large, "a", "b", 200);
+        assert!(bounded.truncated);
+        assert!(bounded.text.chars().count() <= 200);
+    }
+
+    #[test]
+    fn reverses_hunks_onto_the_new_text() {
+        let old = (1..=12)
+            .map(|line| format!("line {line}\n"))
+            .collect::<String>();
+        let new = old
+            .replace("line 2\n", "line two\n")
+            .replace("line 11\n", "line 11\nline 11.5\n")
+            .replace("line 12\n", "line 12");
+        let diff = unified_diff(&old, &new, "a/notes.txt", "b/notes.txt", 10_000);
+        assert_eq!(
+            reverse_unified_diff(&new, &diff.text).as_deref(),
+            Some(old.as_str())
+        );
+
+        let created = unified_diff("", "one\ntwo", "/dev/null", "b/new.txt", 10_000);
+        assert_eq!(
+            reverse_unified_diff("one\ntwo", &created.text).as_deref(),
+            Some("")
+        );
+        let emptied = unified_diff("one\n", "", "a/old.txt", "b/old.txt", 10_000);
+        assert_eq!(
+            reverse_unified_diff("", &emptied.text).as_deref(),
+            Some("one\n")
+        );
+
+        // A file edited again since the diff no longer matches its hunks.
+        let changed = new.replace("line two\n", "line 2b\n");
+        assert_eq!(reverse_unified_diff(&changed, &diff.text), None);
+    }
+
+    #[test]
+    fn falls_back_to_a_replacement_for_wholesale_rewrites() {
+        let old = (0..MAX_EDIT_DISTANCE + 10)
+            .map(|line| format!("old {line}\n"))
+            .collect::<String>();
+        let new = (0..MAX_EDIT_DISTANCE + 10)
+            .map(|line| format!("new {line}\n"))
+            .collect::<String>();
+
+        let diff = unified_diff(&old, &new, "a", "b", usize::MAX);
+
+        assert!(diff
+            .text
+            .starts_with("--- a\n+++ b\n@@ -1,2010 +1,2010 @@\n-old 0\n"));
+        assert_eq!(diff.text.matches("\n-old").count(), MAX_EDIT_DISTANCE + 10);
+    }
+}
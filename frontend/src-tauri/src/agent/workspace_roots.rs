@@ -0,0 +1,167 @@
+//! Secondary workspace roots of an Agent task.
+//!
+//! A task keeps one primary project root as its Goose working directory. Extra
+//! roots, such as shared libraries checked out beside a monorepo, live in the
+//! session's extension data so every surface that reopens the task sees the
+//! same set.
+
+use super::path_string;
+use goose::session::extension_data::ExtensionData;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const WORKSPACE_ROOTS_STATE: &str = "maple_workspace_roots";
+const WORKSPACE_ROOTS_VERSION: &str = "v0";
+const MAX_WORKSPACE_ROOTS: usize = 16;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WorkspaceRootsState {
+    roots: Vec<PathBuf>,
+}
+
+/// The secondary roots persisted for a task, in the order they were given.
+pub(super) fn session_workspace_roots(extension_data: &ExtensionData) -> Vec<PathBuf> {
+    extension_data
+        .get_extension_state(WORKSPACE_ROOTS_STATE, WORKSPACE_ROOTS_VERSION)
+        .and_then(|value| serde_json::from_value::<WorkspaceRootsState>(value.clone()).ok())
+        .map(|state| state.roots)
+        .unwrap_or_default()
+}
+
+pub(super) fn set_session_workspace_roots(
+    extension_data: &mut ExtensionData,
+    roots: &[PathBuf],
+) -> Result<(), String> {
+    let state = serde_json::to_value(WorkspaceRootsState {
+        roots: roots.to_vec(),
+    })
+    .map_err(|error| format!("Failed to encode Agent workspace roots: {error}"))?;
+    extension_data.set_extension_state(WORKSPACE_ROOTS_STATE, WORKSPACE_ROOTS_VERSION, state);
+    Ok(())
+}
+
+/// The persisted secondary roots that are still admissible when a task is
+/// attached. A root removed from Maple, deleted from disk, or now covered by
+/// the primary root is dropped for this attachment; the stored set is kept.
+pub(super) fn admitted_session_workspace_roots(
+    extension_data: &ExtensionData,
+    primary: &Path,
+    removed_project_roots: &[String],
+) -> Vec<PathBuf> {
+    let mut admitted = Vec::new();
+    for root in session_workspace_roots(extension_data) {
+        match normalize_workspace_roots(primary, &[path_string(&root)], removed_project_roots) {
+            Ok(roots) => {
+                for root in roots {
+                    if !admitted.contains(&root) {
+                        admitted.push(root);
+                    }
+                }
+            }
+            Err(error) => log::warn!("Dropped a stale Agent workspace root: {error}"),
+        }
+    }
+    admitted
+}
+
+/// Canonicalize requested secondary roots. Roots already covered by the
+/// primary root or listed twice are dropped; removed projects are rejected.
+pub(super) fn normalize_workspace_roots(
+    primary: &Path,
+    roots: &[String],
+    removed_project_roots: &[String],
+) -> Result<Vec<PathBuf>, String> {
+    if roots.len() > MAX_WORKSPACE_ROOTS {
+        return Err(format!(
+            "Agent tasks support at most {MAX_WORKSPACE_ROOTS} additional workspace roots"
+        ));
+    }
+    let mut normalized = Vec::with_capacity(roots.len());
+    for root in roots {
+        let root = super::normalize_project_root(Path::new(root.trim()))?;
+        let root_text = path_string(&root);
+        if removed_project_roots
+            .iter()
+            .any(|removed| removed == &root_text)
+        {
+            return Err(format!(
+                "{root_text} was removed from Maple and cannot be a workspace root"
+            ));
+        }
+        if !root.starts_with(primary) && !normalized.contains(&root) {
+            normalized.push(root);
+        }
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_roots_round_trip_through_extension_data() {
+        let mut extension_data = ExtensionData::default();
+        assert!(session_workspace_roots(&extension_data).is_empty());
+
+        let roots = vec![PathBuf::from("/work/shared"), PathBuf::from("/work/tools")];
+        set_session_workspace_roots(&mut extension_data, &roots).unwrap();
+        assert_eq!(session_workspace_roots(&extension_data), roots);
+
+        set_session_workspace_roots(&mut extension_data, &[]).unwrap();
+        assert!(session_workspace_roots(&extension_data).is_empty());
+    }
+
+    #[test]
+    fn attach_drops_removed_and_missing_persisted_roots() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path().canonicalize().unwrap();
+        let primary = base.join("app");
+        let shared = base.join("shared");
+        let removed = base.join("removed");
+        std::fs::create_dir_all(&primary).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::create_dir_all(&removed).unwrap();
+
+        let mut extension_data = ExtensionData::default();
+        set_session_workspace_roots(
+            &mut extension_data,
+            &[shared.clone(), removed.clone(), base.join("missing")],
+        )
+        .unwrap();
+        assert_eq!(
+            admitted_session_workspace_roots(&extension_data, &primary, &[path_string(&removed)]),
+            vec![shared.clone()]
+        );
+        assert_eq!(session_workspace_roots(&extension_data).len(), 3);
+    }
+
+    #[test]
+    fn normalization_drops_covered_and_duplicate_roots() {
+        let temp = tempfile::tempdir().unwrap();
+        let base = temp.path().canonicalize().unwrap();
+        let primary = base.join("app");
+        let shared = base.join("shared");
+        std::fs::create_dir_all(primary.join("nested")).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+
+        let requested = [
+            path_string(&shared),
+            path_string(&primary.join("nested")),
+            path_string(&shared.join(".")),
+        ];
+        assert_eq!(
+            normalize_workspace_roots(&primary, &requested, &[]).unwrap(),
+            vec![shared.clone()]
+        );
+
+        let error =
+            normalize_workspace_roots(&primary, &[path_string(&shared)], &[path_string(&shared)])
+                .unwrap_err();
+        assert!(error.contains("was removed from Maple"));
+        assert!(
+            normalize_workspace_roots(&primary, &[path_string(&base.join("missing"))], &[])
+                .is_err()
+        );
+    }
+}
@@ -1,12 +1,17 @@
 use crate::agent::{
-    AgentConfig, AgentCreateSessionRequest, AgentDesktopQueueSnapshot, AgentEventSink,
-    AgentMcpServer, AgentPermissionModeRequest, AgentPermissionResponse,
-    AgentProjectRootRegistration, AgentProjectTrustStatus, AgentQueueControlRequest,
-    AgentQueueUpdateRequest, AgentQueuedMessage, AgentRenameSessionRequest, AgentRunEvent,
+    AgentAddPermissionRuleRequest, AgentConfig, AgentCreateSessionRequest,
+    AgentDesktopQueueSnapshot, AgentElicitationResponse, AgentEventSink, AgentExportSessionRequest,
+    AgentFileChange, AgentFilesystemBoundary, AgentForkSessionRequest, AgentGetMcpPromptRequest,
+    AgentImportSessionRequest, AgentMcpComposerText, AgentMcpContextCatalog, AgentMcpImportReport,
+    AgentMcpImportRequest, AgentMcpServer, AgentMcpServerTest, AgentPermissionModeRequest,
+    AgentPermissionResponse, AgentPermissionRule, AgentProjectRootRegistration,
+    AgentProjectTrustStatus, AgentQueueControlRequest, AgentQueueUpdateRequest, AgentQueuedMessage,
+    AgentReadMcpResourceRequest, AgentRenameSessionRequest, AgentRunChanges, AgentRunEvent,
     AgentRunResponse, AgentRunTerminal, AgentRuntimeHandle, AgentRuntimeStatus,
-    AgentSendMessageRequest, AgentServiceEvent, AgentSessionDetail, AgentSessionMcpServer,
-    AgentSessionSummary, AgentSetSessionMcpServerRequest, AgentStartRequest, AgentTimelineItem,
-    MapleAgentService, RecentProjectRoot,
+    AgentSearchSessionsRequest, AgentSendMessageRequest, AgentServiceEvent, AgentSessionDetail,
+    AgentSessionMcpServer, AgentSessionSearchHit, AgentSessionSummary,
+    AgentSetSessionMcpServerRequest, AgentStartRequest, AgentTimelineItem, AgentTranscript,
+    AgentTranscriptRequest, MapleAgentService, RecentProjectRoot,
 };
 use crate::agent_host::{AgentHostLifecycle, AgentRuntimeLifecycleOutcome};
 use crate::maple_api::MapleApiAuthState;
@@ -98,7 +103,8 @@ pub(crate) fn project_agent_event(event: &AgentServiceEvent) -> AgentEventEnvelo
                     envelope.event_type = "timelineItem".to_string();
                     envelope.item = Some(item.clone());
                 }
-                AgentRunEvent::PermissionRequested { item, .. } => {
+                AgentRunEvent::PermissionRequested { item, .. }
+                | AgentRunEvent::ElicitationRequested { item, .. } => {
                     // Keep the Desktop wire contract unchanged while the shared
                     // service exposes a typed permission request to non-Tauri
                     // callers through the run-local event stream.
@@ -309,6 +315,74 @@ pub async fn agent_save_mcp_servers(
         .await
 }
 
+#[tauri::command]
+pub async fn agent_list_mcp_tool_permissions(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+) -> Result<Vec<AgentMcpToolPermission>, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .list_mcp_tool_permissions()
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_save_mcp_tool_permissions(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    permissions: Vec<AgentMcpToolPermission>,
+) -> Result<Vec<AgentMcpToolPermission>, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .save_mcp_tool_permissions(permissions)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_import_mcp_servers(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentMcpImportRequest,
+) -> Result<AgentMcpImportReport, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .import_mcp_servers(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_export_mcp_servers(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+) -> Result<String, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .export_mcp_servers()
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_test_mcp_server(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    server: AgentMcpServer,
+) -> Result<AgentMcpServerTest, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .test_mcp_server(server)
+        .await
+}
+
 #[tauri::command]
 pub async fn agent_list_recent_project_roots(
     app_handle: AppHandle,
@@ -380,6 +454,120 @@ pub async fn agent_set_project_trust(
         .await
 }
 
+#[tauri::command]
+pub async fn agent_get_filesystem_boundary(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+) -> Result<AgentFilesystemBoundary, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .get_filesystem_boundary()
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_set_filesystem_boundary(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    boundary: AgentFilesystemBoundary,
+) -> Result<AgentFilesystemBoundary, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .set_filesystem_boundary(boundary)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_list_run_changes(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    session_id: String,
+    run_id: Option<String>,
+) -> Result<AgentRunChanges, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .list_run_changes(session_id, run_id)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_revert_run_changes(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    session_id: String,
+    run_id: String,
+) -> Result<Vec<AgentFileChange>, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .revert_run_changes(session_id, run_id)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_revert_file_change(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    session_id: String,
+    change_id: String,
+) -> Result<AgentFileChange, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .revert_file_change(session_id, change_id)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_list_permission_rules(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    project_root: Option<String>,
+) -> Result<Vec<AgentPermissionRule>, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .list_permission_rules(project_root)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_add_permission_rule(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentAddPermissionRuleRequest,
+) -> Result<AgentPermissionRule, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .add_permission_rule(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_remove_permission_rule(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    rule_id: String,
+) -> Result<(), String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .remove_permission_rule(rule_id)
+        .await
+}
+
 #[tauri::command]
 pub async fn agent_save_project_root_order(
     app_handle: AppHandle,
@@ -470,6 +658,76 @@ pub async fn agent_rename_session(
     handle.rename_session(api_session, request).await
 }
 
+#[tauri::command]
+pub async fn agent_fork_session(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentForkSessionRequest,
+) -> Result<AgentSessionDetail, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .fork_session(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_export_session(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentExportSessionRequest,
+) -> Result<AgentSessionSummary, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .export_session(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_import_session(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentImportSessionRequest,
+) -> Result<AgentSessionDetail, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .import_session(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_search_sessions(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentSearchSessionsRequest,
+) -> Result<Vec<AgentSessionSearchHit>, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .search_sessions(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_render_session_transcript(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentTranscriptRequest,
+) -> Result<AgentTranscript, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .render_session_transcript(request)
+        .await
+}
+
 #[tauri::command]
 pub async fn agent_list_session_mcp_servers(
     app_handle: AppHandle,
@@ -498,6 +756,48 @@ pub async fn agent_set_session_mcp_server_enabled(
         .await
 }
 
+#[tauri::command]
+pub async fn agent_list_session_mcp_context(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    session_id: String,
+) -> Result<AgentMcpContextCatalog, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .list_session_mcp_context(session_id)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_read_session_mcp_resource(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentReadMcpResourceRequest,
+) -> Result<AgentMcpComposerText, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .read_session_mcp_resource(request)
+        .await
+}
+
+#[tauri::command]
+pub async fn agent_get_session_mcp_prompt(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    request: AgentGetMcpPromptRequest,
+) -> Result<AgentMcpComposerText, String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .get_session_mcp_prompt(request)
+        .await
+}
+
 #[tauri::command]
 pub async fn agent_delete_session(
     app_handle: AppHandle,
@@ -643,6 +943,20 @@ pub async fn agent_permission_respond(
         .await
 }
 
+#[tauri::command]
+pub async fn agent_elicitation_respond(
+    app_handle: AppHandle,
+    state: State<'_, MapleAgentService>,
+    user_id: String,
+    response: AgentElicitationResponse,
+) -> Result<(), String> {
+    let _ = app_handle;
+    handle_for_user(&state, &user_id)
+        .await?
+        .elicitation_respond(response)
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
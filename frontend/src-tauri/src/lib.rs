@@ -236,19 +236,40 @@ pub fn run() {
             agent_tauri::agent_save_config,
             agent_tauri::agent_list_mcp_servers,
             agent_tauri::agent_save_mcp_servers,
+            agent_tauri::agent_import_mcp_servers,
+            agent_tauri::agent_export_mcp_servers,
+            agent_tauri::agent_test_mcp_server,
+            agent_tauri::agent_list_mcp_tool_permissions,
+            agent_tauri::agent_save_mcp_tool_permissions,
             agent_tauri::agent_list_recent_project_roots,
             agent_tauri::agent_save_recent_project_root,
             agent_tauri::agent_remove_project_root,
             agent_tauri::agent_get_project_trust,
             agent_tauri::agent_set_project_trust,
+            agent_tauri::agent_get_filesystem_boundary,
+            agent_tauri::agent_set_filesystem_boundary,
+            agent_tauri::agent_list_run_changes,
+            agent_tauri::agent_revert_run_changes,
+            agent_tauri::agent_revert_file_change,
+            agent_tauri::agent_list_permission_rules,
+            agent_tauri::agent_add_permission_rule,
+            agent_tauri::agent_remove_permission_rule,
             agent_tauri::agent_save_project_root_order,
             agent_tauri::agent_create_session,
             agent_tauri::agent_list_sessions,
             agent_tauri::agent_load_session,
             agent_tauri::agent_load_image_attachment,
             agent_tauri::agent_rename_session,
+            agent_tauri::agent_fork_session,
+            agent_tauri::agent_export_session,
+            agent_tauri::agent_import_session,
+            agent_tauri::agent_render_session_transcript,
+            agent_tauri::agent_search_sessions,
             agent_tauri::agent_list_session_mcp_servers,
             agent_tauri::agent_set_session_mcp_server_enabled,
+            agent_tauri::agent_list_session_mcp_context,
+            agent_tauri::agent_read_session_mcp_resource,
+            agent_tauri::agent_get_session_mcp_prompt,
             agent_tauri::agent_delete_session,
             agent_tauri::agent_send_message,
             agent_tauri::agent_cancel_queued_message,
@@ -259,6 +280,7 @@ pub fn run() {
             agent_tauri::agent_cancel_run,
             agent_tauri::agent_set_permission_mode,
             agent_tauri::agent_permission_respond,
+            agent_tauri::agent_elicitation_respond,
             agent_tauri::agent_clear_user_history,
             agent_tauri::agent_clear_user_data,
             agent_acp::agent_acp_load_config,
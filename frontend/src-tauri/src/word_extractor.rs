@@ -307,13 +307,38 @@ fn build_text_only_docx(main_document_xml: &[u8]) -> Result<Vec<u8>, String> {
         .map_err(|error| unreadable_word(format!("could not finish safe DOCX package: {error}")))
 }
 
+/// Structural problems found by [`preflight_zip_central_directory`]. Callers
+/// map these to their own user-facing messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ZipDirectoryIssue {
+    Unreadable(String),
+    TooComplex(String),
+    Encrypted,
+}
+
 fn preflight_zip_directory(file_bytes: &[u8], max_entries: usize) -> Result<(), String> {
+    preflight_zip_central_directory(file_bytes, max_entries).map_err(|issue| match issue {
+        ZipDirectoryIssue::Unreadable(reason) => unreadable_word(format!("DOCX {reason}")),
+        ZipDirectoryIssue::TooComplex(reason) => too_complex(format!("DOCX {reason}")),
+        ZipDirectoryIssue::Encrypted => WORD_PASSWORD_ERROR.to_string(),
+    })
+}
+
+/// Validate a ZIP end record and central directory before the `zip` crate
+/// parses it, so hostile containers cannot request ZIP64, multi-disk, or
+/// oversized directory layouts.
+pub(crate) fn preflight_zip_central_directory(
+    file_bytes: &[u8],
+    max_entries: usize,
+) -> Result<(), ZipDirectoryIssue> {
     const EOCD_LEN: usize = 22;
     const CENTRAL_HEADER_LEN: usize = 46;
     const MAX_ZIP_COMMENT_LEN: usize = u16::MAX as usize;
 
     if file_bytes.len() < EOCD_LEN {
-        return Err(unreadable_word("DOCX ZIP is missing its end record"));
+        return Err(ZipDirectoryIssue::Unreadable(
+            "ZIP is missing its end record".to_string(),
+        ));
     }
 
     let search_start = file_bytes
@@ -327,7 +352,7 @@ fn preflight_zip_directory(file_bytes: &[u8], max_entries: usize) -> Result<(),
                     .and_then(|length| offset.checked_add(EOCD_LEN + length as usize))
                     == Some(file_bytes.len())
         })
-        .ok_or_else(|| unreadable_word("DOCX ZIP has no valid end record"))?;
+        .ok_or_else(|| ZipDirectoryIssue::Unreadable("ZIP has no valid end record".to_string()))?;
 
     let disk_number = read_le_u16(file_bytes, eocd_offset + 4).unwrap_or(u16::MAX);
     let central_directory_disk = read_le_u16(file_bytes, eocd_offset + 6).unwrap_or(u16::MAX);
@@ -339,52 +364,58 @@ fn preflight_zip_directory(file_bytes: &[u8], max_entries: usize) -> Result<(),
         read_le_u32(file_bytes, eocd_offset + 16).unwrap_or(u32::MAX) as u64;
 
     if disk_number != 0 || central_directory_disk != 0 || entries_on_disk != total_entries {
-        return Err(unreadable_word(
-            "DOCX ZIP uses an unsupported multi-disk layout",
+        return Err(ZipDirectoryIssue::Unreadable(
+            "ZIP uses an unsupported multi-disk layout".to_string(),
         ));
     }
     if total_entries == u16::MAX
         || central_directory_size == u32::MAX as u64
         || central_directory_offset == u32::MAX as u64
     {
-        return Err(too_complex("DOCX ZIP64 packages are not supported"));
+        return Err(ZipDirectoryIssue::TooComplex(
+            "ZIP64 packages are not supported".to_string(),
+        ));
     }
     if total_entries as usize > max_entries {
-        return Err(too_complex(format!(
-            "DOCX ZIP declares {total_entries} entries (limit {max_entries})"
+        return Err(ZipDirectoryIssue::TooComplex(format!(
+            "ZIP declares {total_entries} entries (limit {max_entries})"
         )));
     }
     if central_directory_size > MAX_ZIP_CENTRAL_DIRECTORY_BYTES {
-        return Err(too_complex("DOCX ZIP central directory is too large"));
+        return Err(ZipDirectoryIssue::TooComplex(
+            "ZIP central directory is too large".to_string(),
+        ));
     }
 
-    let central_start = usize::try_from(central_directory_offset)
-        .map_err(|_| unreadable_word("DOCX ZIP central-directory offset is invalid"))?;
-    let central_size = usize::try_from(central_directory_size)
-        .map_err(|_| unreadable_word("DOCX ZIP central-directory size is invalid"))?;
-    let central_end = central_start
-        .checked_add(central_size)
-        .ok_or_else(|| unreadable_word("DOCX ZIP central-directory range overflowed"))?;
+    let central_start = usize::try_from(central_directory_offset).map_err(|_| {
+        ZipDirectoryIssue::Unreadable("ZIP central-directory offset is invalid".to_string())
+    })?;
+    let central_size = usize::try_from(central_directory_size).map_err(|_| {
+        ZipDirectoryIssue::Unreadable("ZIP central-directory size is invalid".to_string())
+    })?;
+    let central_end = central_start.checked_add(central_size).ok_or_else(|| {
+        ZipDirectoryIssue::Unreadable("ZIP central-directory range overflowed".to_string())
+    })?;
     if central_end != eocd_offset || central_end > file_bytes.len() {
-        return Err(unreadable_word(
-            "DOCX ZIP central-directory range is inconsistent",
+        return Err(ZipDirectoryIssue::Unreadable(
+            "ZIP central-directory range is inconsistent".to_string(),
         ));
     }
 
     let mut cursor = central_start;
     for _ in 0..total_entries {
-        let fixed_end = cursor
-            .checked_add(CENTRAL_HEADER_LEN)
-            .ok_or_else(|| unreadable_word("DOCX ZIP central-directory entry overflowed"))?;
+        let fixed_end = cursor.checked_add(CENTRAL_HEADER_LEN).ok_or_else(|| {
+            ZipDirectoryIssue::Unreadable("ZIP central-directory entry overflowed".to_string())
+        })?;
         if fixed_end > central_end || file_bytes.get(cursor..cursor + 4) != Some(b"PK\x01\x02") {
-            return Err(unreadable_word(
-                "DOCX ZIP central directory contains a malformed entry",
+            return Err(ZipDirectoryIssue::Unreadable(
+                "ZIP central directory contains a malformed entry".to_string(),
             ));
         }
 
         let general_purpose_flags = read_le_u16(file_bytes, cursor + 8).unwrap_or(u16::MAX);
         if general_purpose_flags & 0x0001 != 0 {
-            return Err(WORD_PASSWORD_ERROR.to_string());
+            return Err(ZipDirectoryIssue::Encrypted);
         }
         let compressed_size = read_le_u32(file_bytes, cursor + 20).unwrap_or(u32::MAX);
         let uncompressed_size = read_le_u32(file_bytes, cursor + 24).unwrap_or(u32::MAX);
@@ -398,23 +429,27 @@ fn preflight_zip_directory(file_bytes: &[u8], max_entries: usize) -> Result<(),
             || local_header_offset == u32::MAX
             || start_disk != 0
         {
-            return Err(too_complex("DOCX ZIP64 entry metadata is not supported"));
+            return Err(ZipDirectoryIssue::TooComplex(
+                "ZIP64 entry metadata is not supported".to_string(),
+            ));
         }
 
         cursor = fixed_end
             .checked_add(name_len)
             .and_then(|next| next.checked_add(extra_len))
             .and_then(|next| next.checked_add(comment_len))
-            .ok_or_else(|| unreadable_word("DOCX ZIP central-directory entry overflowed"))?;
+            .ok_or_else(|| {
+                ZipDirectoryIssue::Unreadable("ZIP central-directory entry overflowed".to_string())
+            })?;
         if cursor > central_end {
-            return Err(unreadable_word(
-                "DOCX ZIP central-directory entry is truncated",
+            return Err(ZipDirectoryIssue::Unreadable(
+                "ZIP central-directory entry is truncated".to_string(),
             ));
         }
     }
     if cursor != central_end {
-        return Err(unreadable_word(
-            "DOCX ZIP central-directory entry count is inconsistent",
+        return Err(ZipDirectoryIssue::Unreadable(
+            "ZIP central-directory entry count is inconsistent".to_string(),
         ));
     }
 
@@ -95,6 +95,14 @@ import { RenameAgentTaskDialog } from "@/components/RenameAgentTaskDialog";
 import { UpgradePromptDialog } from "@/components/UpgradePromptDialog";
 import { AgentMcpMenu, AgentMcpServersDialog } from "@/components/agent/AgentMcpControls";
 import { AgentSidebarInfoCard } from "@/components/agent/AgentSidebarInfoCard";
+import {
+  AgentElicitationRow,
+  type AgentElicitationResponder
+} from "@/components/agent/AgentElicitationRow";
+import {
+  agentElicitationRequestId,
+  isAgentElicitationItem
+} from "@/components/agent/agentElicitation";
 import { latestAgentSidebarUpdatedMs } from "@/components/agent/agentSidebarInfoCardDate";
 import {
   isDeliberateAgentComposerFocusTarget,
@@ -2839,6 +2847,27 @@ export function AgentMode({ userId }: { userId: string }) {
     [userId]
   );
 
+  const respondToElicitation = useCallback<AgentElicitationResponder>(
+    async (item, action, content) => {
+      const sessionId = activeSessionIdRef.current;
+      try {
+        if (!sessionId) throw new Error("No active task for this input request");
+        await agentRuntimeService.respondToElicitation(
+          userId,
+          sessionId,
+          agentElicitationRequestId(item),
+          action,
+          content
+        );
+      } catch (elicitationError) {
+        if (activeSessionIdRef.current === sessionId) {
+          setError(errorMessage(elicitationError));
+        }
+      }
+    },
+    [userId]
+  );
+
   const handleKeyDown = useCallback(
     (event: React.KeyboardEvent<HTMLTextAreaElement>) => {
       if (event.nativeEvent.isComposing) return;
@@ -3758,6 +3787,7 @@ export function AgentMode({ userId }: { userId: string }) {
                   sessionId={activeSessionId}
                   userId={userId}
                   onPermissionDecision={respondToPermission}
+                  onElicitationResponse={respondToElicitation}
                 />
               )}
             </div>
@@ -5688,7 +5718,8 @@ function AgentTimeline({
   generatedThoughtLabels,
   sessionId,
   userId,
-  onPermissionDecision
+  onPermissionDecision,
+  onElicitationResponse
 }: {
   items: AgentTimelineItem[];
   isResponsePending: boolean;
@@ -5697,6 +5728,7 @@ function AgentTimeline({
   sessionId: string | null;
   userId: string;
   onPermissionDecision: (item: AgentTimelineItem, decision: AgentPermissionDecision) => void;
+  onElicitationResponse: AgentElicitationResponder;
 }) {
   const visibleItems = coalesceAdjacentThinkingItems(items).filter(isRenderableAgentTimelineItem);
   const turns = groupAgentTimelineItems(visibleItems);
@@ -5774,6 +5806,7 @@ function AgentTimeline({
                 isThinking={item.id === activeThinkingItemId}
                 thoughtLabel={thoughtLabel ?? undefined}
                 onPermissionDecision={onPermissionDecision}
+                onElicitationResponse={onElicitationResponse}
               />
             ))}
             {pendingIndicatorTurnId === turn.id ? <ChatAssistantPendingIndicator /> : null}
@@ -5856,12 +5889,14 @@ function AgentAssistantItem({
   item,
   isThinking,
   thoughtLabel,
-  onPermissionDecision
+  onPermissionDecision,
+  onElicitationResponse
 }: {
   item: AgentTimelineItem;
   isThinking: boolean;
   thoughtLabel?: string;
   onPermissionDecision: (item: AgentTimelineItem, decision: AgentPermissionDecision) => void;
+  onElicitationResponse: AgentElicitationResponder;
 }) {
   if (item.itemType === "message") {
     return (
@@ -5881,6 +5916,9 @@ function AgentAssistantItem({
     );
   }
   if (item.itemType === "tool") return <ToolCallRow item={item} />;
+  if (isAgentElicitationItem(item)) {
+    return <AgentElicitationRow item={item} onRespond={onElicitationResponse} />;
+  }
   if (item.itemType === "permission") {
     return <PermissionRow item={item} onPermissionDecision={onPermissionDecision} />;
   }
@@ -0,0 +1,218 @@
+import { useId, useMemo, useState } from "react";
+import { Check, MessageSquare, X } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import {
+  Select,
+  SelectContent,
+  SelectItem,
+  SelectTrigger,
+  SelectValue
+} from "@/components/ui/select";
+import { Switch } from "@/components/ui/switch";
+import { cn } from "@/utils/utils";
+import type { AgentElicitationAction, AgentTimelineItem } from "@/services/agentRuntimeService";
+import {
+  agentElicitationContent,
+  agentElicitationFields,
+  initialAgentElicitationValues,
+  type AgentElicitationField,
+  type AgentElicitationValue
+} from "./agentElicitation";
+
+export type AgentElicitationResponder = (
+  item: AgentTimelineItem,
+  action: AgentElicitationAction,
+  content?: Record<string, AgentElicitationValue>
+) => void;
+
+function formatElicitationStatus(status: string): string {
+  switch (status) {
+    case "pending":
+      return "Waiting for you";
+    case "accepted":
+      return "Submitted";
+    case "declined":
+      return "Declined";
+    case "cancelled":
+      return "Cancelled";
+    case "controlled_externally":
+      return "Answered elsewhere";
+    default:
+      return status;
+  }
+}
+
+export function AgentElicitationRow({
+  item,
+  onRespond
+}: {
+  item: AgentTimelineItem;
+  onRespond: AgentElicitationResponder;
+}) {
+  const formId = useId();
+  const fields = useMemo(() => agentElicitationFields(item.input), [item.input]);
+  const [values, setValues] = useState(() => initialAgentElicitationValues(fields ?? []));
+  const [error, setError] = useState<string | null>(null);
+  const status = item.status || "pending";
+  const resolved = status !== "pending";
+
+  const submit = () => {
+    if (!fields) return;
+    const result = agentElicitationContent(fields, values);
+    if ("error" in result) {
+      setError(result.error);
+      return;
+    }
+    setError(null);
+    onRespond(item, "accept", result.content);
+  };
+
+  return (
+    <div
+      className={cn(
+        "rounded-3xl border border-muted/40 bg-muted/20 px-4 py-3 text-sm",
+        resolved
+          ? "border-muted/40"
+          : "border-[hsl(var(--maple-primary)/0.45)] bg-[hsl(var(--maple-primary)/0.06)]"
+      )}
+    >
+      <div className="flex items-start justify-between gap-3">
+        <div className="flex min-w-0 items-start gap-2">
+          <MessageSquare className="mt-0.5 h-4 w-4 shrink-0 text-[hsl(var(--maple-primary))]" />
+          <div className="min-w-0">
+            <p className="font-medium">{item.title || "Input requested"}</p>
+            {item.text ? (
+              <p className="mt-1 whitespace-pre-wrap break-words text-xs text-muted-foreground">
+                {item.text}
+              </p>
+            ) : null}
+          </div>
+        </div>
+        <span className="shrink-0 text-xs text-muted-foreground" role="status" aria-live="polite">
+          {formatElicitationStatus(status)}
+        </span>
+      </div>
+      {!resolved ? (
+        <form
+          className="mt-3 space-y-3"
+          onSubmit={(event) => {
+            event.preventDefault();
+            submit();
+          }}
+        >
+          {fields ? (
+            fields.map((field) => (
+              <ElicitationField
+                key={field.name}
+                id={`${formId}-${field.name}`}
+                field={field}
+                value={values[field.name]}
+                onChange={(value) => setValues((current) => ({ ...current, [field.name]: value }))}
+              />
+            ))
+          ) : (
+            <p className="text-xs text-muted-foreground">
+              Maple can't show this server's form. You can decline or cancel the request.
+            </p>
+          )}
+          {error ? (
+            <p className="text-xs text-destructive" role="alert">
+              {error}
+            </p>
+          ) : null}
+          <div className="flex flex-wrap items-center gap-2">
+            {fields ? (
+              <Button type="submit" size="sm" className="h-8">
+                <Check className="mr-1 h-4 w-4" />
+                Submit
+              </Button>
+            ) : null}
+            <Button
+              type="button"
+              size="sm"
+              variant="outline"
+              className="h-8"
+              onClick={() => onRespond(item, "decline")}
+            >
+              Decline
+            </Button>
+            <Button
+              type="button"
+              size="sm"
+              variant="ghost"
+              className="h-8"
+              onClick={() => onRespond(item, "cancel")}
+            >
+              <X className="mr-1 h-4 w-4" />
+              Cancel
+            </Button>
+          </div>
+        </form>
+      ) : null}
+    </div>
+  );
+}
+
+function ElicitationField({
+  id,
+  field,
+  value,
+  onChange
+}: {
+  id: string;
+  field: AgentElicitationField;
+  value: string | boolean | undefined;
+  onChange: (value: string | boolean) => void;
+}) {
+  const label = (
+    <Label htmlFor={id} className="text-xs">
+      {field.label}
+      {field.required ? <span className="text-muted-foreground"> (required)</span> : null}
+    </Label>
+  );
+  const description = field.description ? (
+    <p className="text-xs text-muted-foreground">{field.description}</p>
+  ) : null;
+
+  if (field.kind === "boolean") {
+    return (
+      <div className="flex items-start justify-between gap-3">
+        <div className="space-y-1">
+          {label}
+          {description}
+        </div>
+        <Switch id={id} checked={value === true} onCheckedChange={onChange} />
+      </div>
+    );
+  }
+  return (
+    <div className="space-y-1">
+      {label}
+      {field.options ? (
+        <Select value={typeof value === "string" ? value : ""} onValueChange={onChange}>
+          <SelectTrigger id={id} className="h-8">
+            <SelectValue placeholder="Choose" />
+          </SelectTrigger>
+          <SelectContent>
+            {field.options.map((option) => (
+              <SelectItem key={option} value={option}>
+                {option}
+              </SelectItem>
+            ))}
+          </SelectContent>
+        </Select>
+      ) : (
+        <Input
+          id={id}
+          className="h-8"
+          inputMode={field.kind === "string" ? undefined : "decimal"}
+          value={typeof value === "string" ? value : ""}
+          onChange={(event) => onChange(event.target.value)}
+        />
+      )}
+      {description}
+    </div>
+  );
+}
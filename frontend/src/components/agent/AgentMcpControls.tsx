@@ -41,11 +41,15 @@ import { Switch } from "@/components/ui/switch";
 import type {
   AgentMcpKeyValue,
   AgentMcpServer,
+  AgentMcpTransport,
   AgentSessionMcpServer
 } from "@/services/agentRuntimeService";
 import { gooseMcpServerKey, isValidMcpTimeoutSeconds } from "@/services/agentMcpServers";
 
 const DEFAULT_TIMEOUT_SECONDS = 300;
+const DEFAULT_UNIX_SOCKET_URL = "http://localhost/mcp";
+const DEFAULT_SAMPLING_MAX_TOKENS = 1024;
+const MAX_SAMPLING_MAX_TOKENS = 8192;
 type PendingDiscardAction = "close_form" | "close_dialog";
 
 export function AgentMcpMenu({
@@ -124,7 +128,11 @@ export function AgentMcpMenu({
                   <p className="line-clamp-2 text-xs text-muted-foreground">
                     {!server.available
                       ? "No longer available in your saved MCP servers"
-                      : server.description || transportLabel(server.transport)}
+                      : server.health.status === "reconnecting"
+                        ? `Reconnecting (attempt ${server.health.attempts})…`
+                        : server.health.status === "failed"
+                          ? "Disconnected. Turn it off and on again to retry."
+                          : server.description || transportLabel(server.transport)}
                   </p>
                 </div>
               </DropdownMenuCheckboxItem>
@@ -510,16 +518,26 @@ function McpServerForm({
   const transportId = `${idPrefix}-transport`;
   const descriptionId = `${idPrefix}-description`;
   const commandId = `${idPrefix}-command`;
+  const workingDirectoryId = `${idPrefix}-working-directory`;
+  const socketPathId = `${idPrefix}-socket-path`;
   const endpointId = `${idPrefix}-endpoint`;
   const timeoutId = `${idPrefix}-timeout`;
-  const setTransportType = (type: "stdio" | "streamable_http") => {
+  const allowedToolsId = `${idPrefix}-allowed-tools`;
+  const blockedToolsId = `${idPrefix}-blocked-tools`;
+  const samplingId = `${idPrefix}-sampling`;
+  const samplingModelsId = `${idPrefix}-sampling-models`;
+  const samplingMaxTokensId = `${idPrefix}-sampling-max-tokens`;
+  const sampling = server.sampling;
+  const setTransportType = (type: AgentMcpTransport["type"]) => {
     const environment = transport.environment;
     onChange({
       ...server,
       transport:
         type === "stdio"
           ? { type, command: "", environment }
-          : { type, url: "", environment, headers: [] }
+          : type === "unix_socket"
+            ? { type, socketPath: "", url: DEFAULT_UNIX_SOCKET_URL, environment, headers: [] }
+            : { type, url: "", environment, headers: [] }
     });
   };
 
@@ -561,7 +579,11 @@ function McpServerForm({
             <Select
               value={transport.type}
               onValueChange={(value) =>
-                setTransportType(value === "streamable_http" ? value : "stdio")
+                setTransportType(
+                  value === "streamable_http" || value === "sse" || value === "unix_socket"
+                    ? value
+                    : "stdio"
+                )
               }
               disabled={disabled}
             >
@@ -571,6 +593,8 @@ function McpServerForm({
               <SelectContent>
                 <SelectItem value="stdio">Standard IO (STDIO)</SelectItem>
                 <SelectItem value="streamable_http">Streamable HTTP</SelectItem>
+                <SelectItem value="sse">HTTP+SSE (legacy)</SelectItem>
+                <SelectItem value="unix_socket">Streamable HTTP (Unix socket)</SelectItem>
               </SelectContent>
             </Select>
           </Field>
@@ -587,43 +611,82 @@ function McpServerForm({
         </Field>
 
         {transport.type === "stdio" ? (
-          <Field
-            label="Command"
-            controlId={commandId}
-            required
-            hint="Executable and arguments, as one command"
-          >
+          <>
+            <Field
+              label="Command"
+              controlId={commandId}
+              required
+              hint="Executable and arguments, as one command"
+            >
+              <Input
+                id={commandId}
+                value={transport.command}
+                onChange={(event) =>
+                  onChange({
+                    ...server,
+                    transport: { ...transport, command: event.target.value }
+                  })
+                }
+                placeholder="npx -y @modelcontextprotocol/server-everything stdio"
+                disabled={disabled}
+                spellCheck={false}
+              />
+            </Field>
+            <Field
+              label="Working directory"
+              controlId={workingDirectoryId}
+              hint="Optional; ${projectRoot} is the task's project folder"
+            >
+              <Input
+                id={workingDirectoryId}
+                value={transport.workingDirectory ?? ""}
+                onChange={(event) =>
+                  onChange({
+                    ...server,
+                    transport: { ...transport, workingDirectory: event.target.value }
+                  })
+                }
+                placeholder="${projectRoot}"
+                disabled={disabled}
+                spellCheck={false}
+              />
+            </Field>
+          </>
+        ) : (
+          <Field label="Endpoint URL" controlId={endpointId} required>
             <Input
-              id={commandId}
-              value={transport.command}
+              id={endpointId}
+              value={transport.url}
               onChange={(event) =>
                 onChange({
                   ...server,
-                  transport: { ...transport, command: event.target.value }
+                  transport: { ...transport, url: event.target.value }
                 })
               }
-              placeholder="npx -y @modelcontextprotocol/server-everything stdio"
+              placeholder="http://127.0.0.1:3000/mcp"
               disabled={disabled}
               spellCheck={false}
             />
           </Field>
-        ) : (
-          <Field label="Endpoint URL" controlId={endpointId} required>
+        )}
+
+        {transport.type === "unix_socket" ? (
+          <Field label="Socket path" controlId={socketPathId} required>
             <Input
-              id={endpointId}
-              value={transport.url}
+              id={socketPathId}
+              value={transport.socketPath}
               onChange={(event) =>
                 onChange({
                   ...server,
-                  transport: { ...transport, url: event.target.value }
+                  transport: { ...transport, socketPath: event.target.value }
                 })
               }
-              placeholder="http://127.0.0.1:3000/mcp"
+              placeholder="/run/user/1000/mcp.sock"
               disabled={disabled}
               spellCheck={false}
             />
           </Field>
-        )}
+        ) : null}
 
         <Field label="Timeout" controlId={timeoutId} required hint="Seconds">
           <Input
@@ -640,6 +703,100 @@ function McpServerForm({
           />
         </Field>
 
+        <div className="grid gap-4 sm:grid-cols-2">
+          <Field
+            label="Allowed tools"
+            controlId={allowedToolsId}
+            hint="Comma-separated; empty allows all"
+          >
+            <Input
+              id={allowedToolsId}
+              value={(server.allowedTools ?? []).join(",")}
+              onChange={(event) =>
+                onChange({ ...server, allowedTools: event.target.value.split(",") })
+              }
+              placeholder="create_issue, search_code"
+              disabled={disabled}
+            />
+          </Field>
+          <Field label="Blocked tools" controlId={blockedToolsId} hint="Comma-separated">
+            <Input
+              id={blockedToolsId}
+              value={(server.blockedTools ?? []).join(",")}
+              onChange={(event) =>
+                onChange({ ...server, blockedTools: event.target.value.split(",") })
+              }
+              placeholder="delete_repository"
+              disabled={disabled}
+            />
+          </Field>
+        </div>
+
+        <div className="space-y-3 rounded-lg border p-3">
+          <div className="flex items-center justify-between gap-3">
+            <div className="space-y-1">
+              <Label htmlFor={samplingId}>Allow sampling</Label>
+              <p className="text-xs text-muted-foreground">
+                Let this server ask Maple's models for completions. Tasks that are not in Allow
+                all mode ask you first.
+              </p>
+            </div>
+            <Switch
+              id={samplingId}
+              checked={Boolean(sampling)}
+              onCheckedChange={(enabled) =>
+                onChange({
+                  ...server,
+                  sampling: enabled
+                    ? { allowedModels: [], maxTokens: DEFAULT_SAMPLING_MAX_TOKENS }
+                    : undefined
+                })
+              }
+              disabled={disabled}
+            />
+          </div>
+          {sampling ? (
+            <div className="grid gap-4 sm:grid-cols-2">
+              <Field
+                label="Allowed models"
+                controlId={samplingModelsId}
+                hint="Comma-separated; empty uses the task's model"
+              >
+                <Input
+                  id={samplingModelsId}
+                  value={sampling.allowedModels.join(",")}
+                  onChange={(event) =>
+                    onChange({
+                      ...server,
+                      sampling: { ...sampling, allowedModels: event.target.value.split(",") }
+                    })
+                  }
+                  placeholder="gpt-oss-120b"
+                  disabled={disabled}
+                />
+              </Field>
+              <Field label="Max tokens" controlId={samplingMaxTokensId} hint="Per request">
+                <Input
+                  id={samplingMaxTokensId}
+                  type="number"
+                  min={1}
+                  max={MAX_SAMPLING_MAX_TOKENS}
+                  step={1}
+                  value={sampling.maxTokens}
+                  onChange={(event) =>
+                    onChange({
+                      ...server,
+                      sampling: { ...sampling, maxTokens: Number(event.target.value) }
+                    })
+                  }
+                  disabled={disabled}
+                  className="max-w-40"
+                />
+              </Field>
+            </div>
+          ) : null}
+        </div>
+
         <KeyValueFields
           title="Environment variables"
           pairs={transport.environment}
@@ -652,7 +809,7 @@ function McpServerForm({
           }
         />
 
-        {transport.type === "streamable_http" ? (
+        {transport.type !== "stdio" ? (
           <KeyValueFields
             title="HTTP headers"
             pairs={transport.headers}
@@ -669,8 +826,7 @@ function McpServerForm({
 
         <p className="text-xs text-muted-foreground">
           Values are masked here and saved in this account’s local Agent data, including each task’s
-          selected server snapshot. OAuth and legacy SSE transports are not supported in this first
-          version.
+          selected server snapshot. OAuth is not supported in this first version.
         </p>
       </div>
 
@@ -817,6 +973,11 @@ function newMcpServer(): AgentMcpServer {
 function cloneServer(server: AgentMcpServer): AgentMcpServer {
   return {
     ...server,
+    allowedTools: server.allowedTools ? [...server.allowedTools] : undefined,
+    blockedTools: server.blockedTools ? [...server.blockedTools] : undefined,
+    sampling: server.sampling
+      ? { ...server.sampling, allowedModels: [...server.sampling.allowedModels] }
+      : undefined,
     transport:
       server.transport.type === "stdio"
         ? {
@@ -838,18 +999,45 @@ function normalizeServer(server: AgentMcpServer): AgentMcpServer {
     name: server.name.trim(),
     description: server.description.trim(),
     timeoutSeconds: server.timeoutSeconds,
+    allowedTools: normalizeToolNames(server.allowedTools),
+    blockedTools: normalizeToolNames(server.blockedTools),
+    sampling: server.sampling
+      ? {
+          ...server.sampling,
+          allowedModels: server.sampling.allowedModels
+            .map((model) => model.trim())
+            .filter(Boolean)
+        }
+      : undefined,
     transport:
       server.transport.type === "stdio"
-        ? { ...server.transport, command: server.transport.command.trim(), environment }
-        : {
+        ? {
             ...server.transport,
-            url: server.transport.url.trim(),
-            environment,
-            headers: normalizePairs(server.transport.headers)
+            command: server.transport.command.trim(),
+            workingDirectory: server.transport.workingDirectory?.trim() || undefined,
+            environment
           }
+        : server.transport.type === "unix_socket"
+          ? {
+              ...server.transport,
+              socketPath: server.transport.socketPath.trim(),
+              url: server.transport.url.trim(),
+              environment,
+              headers: normalizePairs(server.transport.headers)
+            }
+          : {
+              ...server.transport,
+              url: server.transport.url.trim(),
+              environment,
+              headers: normalizePairs(server.transport.headers)
+            }
   };
 }
 
+function normalizeToolNames(names: string[] | undefined): string[] {
+  return (names ?? []).map((name) => name.trim()).filter(Boolean);
+}
+
 function normalizePairs(pairs: AgentMcpKeyValue[]): AgentMcpKeyValue[] {
   return pairs
     .filter((pair) => pair.key.trim() || pair.value)
@@ -874,15 +1062,26 @@ function validateServer(
   if (!isValidMcpTimeoutSeconds(server.timeoutSeconds)) {
     return "Timeout must be a positive whole number of seconds.";
   }
+  if (
+    server.sampling &&
+    (!Number.isInteger(server.sampling.maxTokens) ||
+      server.sampling.maxTokens < 1 ||
+      server.sampling.maxTokens > MAX_SAMPLING_MAX_TOKENS)
+  ) {
+    return `Sampling max tokens must be a whole number from 1 to ${MAX_SAMPLING_MAX_TOKENS}.`;
+  }
   if (server.transport.type === "stdio" && !server.transport.command.trim()) {
     return "Enter the command used to start this STDIO server.";
   }
-  if (server.transport.type === "streamable_http" && !server.transport.url.trim()) {
-    return "Enter the Streamable HTTP endpoint URL.";
+  if (server.transport.type === "unix_socket" && !server.transport.socketPath.trim()) {
+    return "Enter the path of the Unix socket this server listens on.";
+  }
+  if (server.transport.type !== "stdio" && !server.transport.url.trim()) {
+    return `Enter the ${transportLabel(server.transport.type)} endpoint URL.`;
   }
   const pairs = [
     ...server.transport.environment,
-    ...(server.transport.type === "streamable_http" ? server.transport.headers : [])
+    ...(server.transport.type !== "stdio" ? server.transport.headers : [])
   ];
   if (pairs.some((pair) => !pair.key.trim() && Boolean(pair.value))) {
     return "Every environment variable and HTTP header value needs a key.";
@@ -890,6 +1089,15 @@ function validateServer(
   return null;
 }
 
-function transportLabel(transport: "stdio" | "streamable_http"): string {
-  return transport === "stdio" ? "Standard IO (STDIO)" : "Streamable HTTP";
+function transportLabel(transport: AgentMcpTransport["type"]): string {
+  switch (transport) {
+    case "stdio":
+      return "Standard IO (STDIO)";
+    case "streamable_http":
+      return "Streamable HTTP";
+    case "sse":
+      return "HTTP+SSE";
+    case "unix_socket":
+      return "Streamable HTTP (Unix socket)";
+  }
 }
@@ -0,0 +1,62 @@
+import { describe, expect, test } from "bun:test";
+import {
+  agentElicitationContent,
+  agentElicitationFields,
+  initialAgentElicitationValues
+} from "./agentElicitation";
+
+const schema = {
+  type: "object",
+  properties: {
+    name: { type: "string", title: "Name" },
+    plan: { type: "string", enum: ["free", "pro"], default: "free" },
+    seats: { type: "integer" },
+    notify: { type: "boolean", default: true }
+  },
+  required: ["name"]
+};
+
+describe("agentElicitationFields", () => {
+  test("reads flat primitive schemas", () => {
+    const fields = agentElicitationFields(schema);
+    expect(fields?.map((field) => [field.name, field.kind, field.required])).toEqual([
+      ["name", "string", true],
+      ["plan", "string", false],
+      ["seats", "integer", false],
+      ["notify", "boolean", false]
+    ]);
+    expect(fields?.[1].options).toEqual(["free", "pro"]);
+    expect(initialAgentElicitationValues(fields ?? [])).toEqual({
+      name: "",
+      plan: "free",
+      seats: "",
+      notify: true
+    });
+  });
+
+  test("rejects nested schemas", () => {
+    expect(
+      agentElicitationFields({ type: "object", properties: { address: { type: "object" } } })
+    ).toBeNull();
+  });
+});
+
+describe("agentElicitationContent", () => {
+  const fields = agentElicitationFields(schema) ?? [];
+
+  test("converts values and drops blank optional fields", () => {
+    expect(
+      agentElicitationContent(fields, { name: " Ada ", plan: "pro", seats: "3", notify: false })
+    ).toEqual({ content: { name: "Ada", plan: "pro", seats: 3, notify: false } });
+    expect(agentElicitationContent(fields, { name: "Ada", plan: "", seats: "" })).toEqual({
+      content: { name: "Ada", notify: false }
+    });
+  });
+
+  test("reports missing and malformed values", () => {
+    expect(agentElicitationContent(fields, { name: "" })).toEqual({ error: "Name is required" });
+    expect(agentElicitationContent(fields, { name: "Ada", seats: "2.5" })).toEqual({
+      error: "seats must be a whole number"
+    });
+  });
+});
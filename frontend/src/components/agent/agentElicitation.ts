@@ -0,0 +1,115 @@
+import type { AgentTimelineItem } from "@/services/agentRuntimeService";
+
+export type AgentElicitationValue = string | number | boolean;
+
+export interface AgentElicitationField {
+  name: string;
+  label: string;
+  description?: string;
+  kind: "string" | "number" | "integer" | "boolean";
+  required: boolean;
+  options?: string[];
+  defaultValue?: AgentElicitationValue;
+}
+
+const ELICITATION_PREFIX = "elicitation-";
+
+export function isAgentElicitationItem(item: AgentTimelineItem): boolean {
+  return item.itemType === "permission" && item.id.startsWith(ELICITATION_PREFIX);
+}
+
+export function agentElicitationRequestId(item: AgentTimelineItem): string {
+  return item.id.slice(ELICITATION_PREFIX.length);
+}
+
+function isRecord(value: unknown): value is Record<string, unknown> {
+  return typeof value === "object" && value !== null && !Array.isArray(value);
+}
+
+/**
+ * Form fields for an MCP requested schema, or null when the schema is not the
+ * flat object of primitive properties MCP allows. Maple validates the reply
+ * again before it reaches the server.
+ */
+export function agentElicitationFields(schema: unknown): AgentElicitationField[] | null {
+  if (!isRecord(schema)) return null;
+  const properties = schema.properties ?? {};
+  if (!isRecord(properties)) return null;
+  const required = Array.isArray(schema.required)
+    ? schema.required.filter((name): name is string => typeof name === "string")
+    : [];
+  const fields: AgentElicitationField[] = [];
+  for (const [name, property] of Object.entries(properties)) {
+    if (!isRecord(property)) return null;
+    const kind = property.type;
+    if (kind !== "string" && kind !== "number" && kind !== "integer" && kind !== "boolean") {
+      return null;
+    }
+    const options = Array.isArray(property.enum)
+      ? property.enum.filter((option): option is string => typeof option === "string")
+      : undefined;
+    const defaultValue = property.default;
+    fields.push({
+      name,
+      label: typeof property.title === "string" && property.title ? property.title : name,
+      description: typeof property.description === "string" ? property.description : undefined,
+      kind,
+      required: required.includes(name),
+      options: options && options.length > 0 ? options : undefined,
+      defaultValue:
+        typeof defaultValue === "string" ||
+        typeof defaultValue === "number" ||
+        typeof defaultValue === "boolean"
+          ? defaultValue
+          : undefined
+    });
+  }
+  return fields;
+}
+
+export function initialAgentElicitationValues(
+  fields: AgentElicitationField[]
+): Record<string, string | boolean> {
+  return Object.fromEntries(
+    fields.map((field) => [
+      field.name,
+      field.kind === "boolean"
+        ? field.defaultValue === true
+        : field.defaultValue === undefined
+          ? ""
+          : String(field.defaultValue)
+    ])
+  );
+}
+
+/** Convert form values into the reply content, leaving blank optional fields out. */
+export function agentElicitationContent(
+  fields: AgentElicitationField[],
+  values: Record<string, string | boolean>
+): { content: Record<string, AgentElicitationValue> } | { error: string } {
+  const content: Record<string, AgentElicitationValue> = {};
+  for (const field of fields) {
+    const value = values[field.name];
+    if (field.kind === "boolean") {
+      content[field.name] = value === true;
+      continue;
+    }
+    const text = typeof value === "string" ? value.trim() : "";
+    if (!text) {
+      if (field.required) return { error: `${field.label} is required` };
+      continue;
+    }
+    if (field.kind === "string") {
+      content[field.name] = text;
+      continue;
+    }
+    const number = Number(text);
+    if (!Number.isFinite(number) || (field.kind === "integer" && !Number.isInteger(number))) {
+      return {
+        error: `${field.label} must be ${field.kind === "integer" ? "a whole number" : "a number"}`
+      };
+    }
+    content[field.name] = number;
+  }
+  return { content };
+}
@@ -14,6 +14,8 @@ const status: MapleAcpStatus = {
   protocolVersion: 1,
   error: null,
   buzzCredentialsAvailable: false,
+  handshakeTimeouts: 0,
+  idleEvictions: 0,
   harness: {
     command: "/Applications/Maple.app/Contents/MacOS/maple",
     args: ["acp"]
@@ -4,6 +4,8 @@ import { AlertCircle, Loader2, Play, RefreshCw, Server, ShieldCheck, Square } fr
 import { Alert, AlertDescription } from "@/components/ui/alert";
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
+import { Label } from "@/components/ui/label";
+import { Switch } from "@/components/ui/switch";
 import { useSettingsNavigationLock } from "@/contexts/SettingsNavigationLockContext";
 import { awaitAgentAuthUser } from "@/services/agentRuntimeService";
 import {
@@ -26,7 +28,7 @@ export function AgentConnectionsSettings() {
   const [status, setStatus] = useState<MapleAcpStatus | null>(null);
   const [isConfigLoading, setIsConfigLoading] = useState(true);
   const [isStatusLoading, setIsStatusLoading] = useState(true);
-  const [operation, setOperation] = useState<"start" | "stop" | "refresh" | null>(null);
+  const [operation, setOperation] = useState<"start" | "stop" | "refresh" | "save" | null>(null);
   const [error, setError] = useState<string | null>(null);
   const [configLoadError, setConfigLoadError] = useState<string | null>(null);
   const [statusLoadError, setStatusLoadError] = useState<string | null>(null);
@@ -239,6 +241,27 @@ export function AgentConnectionsSettings() {
     }
   };
 
+  const setAllowSessionDelete = async (allowSessionDelete: boolean) => {
+    if (!userId || !config || !savedConfig || configUserId !== userId) return;
+    const operationUserId = userId;
+    setOperation("save");
+    setError(null);
+    try {
+      const nextConfig = await mapleAcpService.saveConfig(userId, {
+        ...savedConfig,
+        enabled: status?.enabled ?? savedConfig.enabled,
+        allowSessionDelete
+      });
+      if (userIdRef.current !== operationUserId) return;
+      setConfig(nextConfig);
+      setSavedConfig(nextConfig);
+    } catch (saveError) {
+      if (userIdRef.current === operationUserId) setError(errorMessage(saveError));
+    } finally {
+      if (userIdRef.current === operationUserId) setOperation(null);
+    }
+  };
+
   const displayedConfig = configUserId === userId ? config : null;
   const displayedSavedConfig = configUserId === userId ? savedConfig : null;
   const running = status?.running === true;
@@ -334,6 +357,12 @@ export function AgentConnectionsSettings() {
                     <StatusMetric label="clients" value={status.connectedClients} />
                     <StatusMetric label="sessions" value={status.activeSessions} />
                     <StatusMetric label="active runs" value={status.activeRuns} />
+                    {status.handshakeTimeouts > 0 ? (
+                      <StatusMetric label="handshake timeouts" value={status.handshakeTimeouts} />
+                    ) : null}
+                    {status.idleEvictions > 0 ? (
+                      <StatusMetric label="idle disconnects" value={status.idleEvictions} />
+                    ) : null}
                   </div>
                 ) : null}
               </div>
@@ -391,6 +420,26 @@ export function AgentConnectionsSettings() {
               only asks before guarded actions but is not an operating-system sandbox.
             </AlertDescription>
           </Alert>
+
+          <div className="flex items-start justify-between gap-4 rounded-xl border border-border/70 bg-background/40 p-4">
+            <div className="space-y-1">
+              <Label htmlFor="agent-acp-allow-session-delete">Let clients delete tasks</Label>
+              <p
+                id="agent-acp-allow-session-delete-description"
+                className="text-xs leading-relaxed text-muted-foreground"
+              >
+                Connected clients can permanently delete Maple Agent tasks. Running tasks and
+                tasks attached to a client stay protected.
+              </p>
+            </div>
+            <Switch
+              id="agent-acp-allow-session-delete"
+              checked={displayedConfig?.allowSessionDelete === true}
+              onCheckedChange={(checked) => void setAllowSessionDelete(checked)}
+              disabled={mutationsDisabled}
+              aria-describedby="agent-acp-allow-session-delete-description"
+            />
+          </div>
         </div>
       </SettingsSection>
 
@@ -7,10 +7,45 @@ import { mapleApiAuthService } from "@/services/mapleApiAuthService";
 export interface AgentConfig {
   defaultProjectRoot?: string | null;
   defaultModel: string;
+  mcpToolPermissions?: AgentMcpToolPermission[];
   projectTrust?: AgentProjectTrust[];
+  permissionRules?: AgentPermissionRule[];
+  filesystemBoundary?: AgentFilesystemBoundary;
   removedProjectRoots?: string[];
 }
 
+export interface AgentFilesystemBoundary {
+  enabled: boolean;
+  extraDirectories?: string[];
+}
+
+export type AgentFileSnapshot =
+  | { state: "missing" }
+  | { state: "stored"; sha256: string }
+  | { state: "unavailable" };
+
+export interface AgentFileChange {
+  id: string;
+  runId: string;
+  path: string;
+  tool: string;
+  before: AgentFileSnapshot;
+  afterSha256: string;
+  recordedMs: number;
+  reverted: boolean;
+}
+
+export interface AgentFileChangeDiff extends AgentFileChange {
+  diff: string | null;
+  diffTruncated: boolean;
+}
+
+export interface AgentRunChanges {
+  sessionId: string;
+  runId: string | null;
+  changes: AgentFileChangeDiff[];
+}
+
 export interface AgentProjectTrust {
   path: string;
   trusted: boolean;
@@ -25,6 +60,26 @@ export interface AgentProjectTrustStatus {
   protectedFeatures: AgentProjectTrustFeature[];
 }
 
+export type AgentPermissionRuleTool = "shell" | "write" | "edit" | "open_url";
+
+export type AgentPermissionRuleDecision = "allow" | "deny";
+
+export interface AgentPermissionRule {
+  id: string;
+  projectRoot: string;
+  tool: AgentPermissionRuleTool;
+  pattern: string;
+  decision: AgentPermissionRuleDecision;
+  createdMs: number;
+}
+
+export interface AgentAddPermissionRuleRequest {
+  projectRoot: string;
+  tool: AgentPermissionRuleTool;
+  pattern: string;
+  decision: AgentPermissionRuleDecision;
+}
+
 export interface AgentMcpKeyValue {
   key: string;
   value: string;
@@ -35,20 +90,43 @@ export type AgentMcpTransport =
       type: "stdio";
       command: string;
       environment: AgentMcpKeyValue[];
+      /** Absolute, or starting with `${projectRoot}` for the task's project folder. */
+      workingDirectory?: string;
+    }
+  | {
+      type: "streamable_http" | "sse";
+      url: string;
+      environment: AgentMcpKeyValue[];
+      headers: AgentMcpKeyValue[];
     }
   | {
-      type: "streamable_http";
+      type: "unix_socket";
+      socketPath: string;
+      /** Supplies the request path and Host header; requests go to the socket. */
       url: string;
       environment: AgentMcpKeyValue[];
       headers: AgentMcpKeyValue[];
     };
 
+export interface AgentMcpSampling {
+  /** Models the server may request. Empty means the task's own model. */
+  allowedModels: string[];
+  /** Upper bound on each completion, whatever the server asks for. */
+  maxTokens: number;
+}
+
 export interface AgentMcpServer {
   name: string;
   description: string;
   enabled: boolean;
   timeoutSeconds: number;
   transport: AgentMcpTransport;
+  /** When non-empty, only these tools are offered to the model. */
+  allowedTools?: string[];
+  /** Tools that are never offered to the model. */
+  blockedTools?: string[];
+  /** Lets the server request completions from Maple's models. */
+  sampling?: AgentMcpSampling;
 }
 
 export interface AgentMcpConnectionError {
@@ -56,12 +134,101 @@ export interface AgentMcpConnectionError {
   error: string;
 }
 
+export type AgentMcpTestStage = "start" | "initialize" | "list_tools" | "complete";
+
+export type AgentMcpToolPermissionLevel = "always_allow" | "ask_before" | "never_allow";
+
+export interface AgentMcpToolPermission {
+  server: string;
+  tool: string;
+  level: AgentMcpToolPermissionLevel;
+}
+
+export interface AgentMcpTestTool {
+  name: string;
+  defaultPermission: AgentMcpToolPermissionLevel;
+}
+
+export type AgentMcpImportStatus =
+  | "added"
+  | "replaced"
+  | "collision"
+  | "reserved"
+  | "rejected_environment"
+  | "invalid";
+
+export interface AgentMcpImportReport {
+  entries: { name: string; status: AgentMcpImportStatus; detail: string | null }[];
+  servers: AgentMcpServer[];
+  saved: boolean;
+}
+
+export interface AgentMcpServerTest {
+  name: string;
+  success: boolean;
+  stage: AgentMcpTestStage;
+  protocolVersion: string | null;
+  serverName: string | null;
+  serverVersion: string | null;
+  toolCount: number;
+  tools: AgentMcpTestTool[];
+  toolsTruncated: boolean;
+  initializeMs: number | null;
+  listToolsMs: number | null;
+  latencyMs: number;
+  error: string | null;
+  stderr: string | null;
+}
+
+export type AgentMcpHealthStatus = "connected" | "reconnecting" | "failed";
+
+export interface AgentMcpServerHealth {
+  status: AgentMcpHealthStatus;
+  attempts: number;
+  lastError: string | null;
+}
+
 export interface AgentSessionMcpServer {
   name: string;
   description: string;
-  transport: "stdio" | "streamable_http";
+  transport: AgentMcpTransport["type"];
   enabled: boolean;
   available: boolean;
+  health: AgentMcpServerHealth;
+}
+
+export interface AgentMcpResource {
+  server: string;
+  uri: string;
+  name: string;
+  title: string | null;
+  description: string | null;
+  mimeType: string | null;
+}
+
+export interface AgentMcpPromptArgument {
+  name: string;
+  description: string | null;
+  required: boolean;
+}
+
+export interface AgentMcpPrompt {
+  server: string;
+  name: string;
+  title: string | null;
+  description: string | null;
+  arguments: AgentMcpPromptArgument[];
+}
+
+export interface AgentMcpContextCatalog {
+  resources: AgentMcpResource[];
+  prompts: AgentMcpPrompt[];
+  errors: AgentMcpConnectionError[];
+}
+
+export interface AgentMcpComposerText {
+  text: string;
+  truncated: boolean;
 }
 
 export interface AgentStartRequest {
@@ -102,6 +269,7 @@ export interface AgentCreateSessionRequest {
   contextLimit?: number | null;
   mode?: string | null;
   mcpServerNames?: string[] | null;
+  workspaceRoots?: string[] | null;
 }
 
 export interface AgentRenameSessionRequest {
@@ -109,6 +277,50 @@ export interface AgentRenameSessionRequest {
   title: string;
 }
 
+export interface AgentForkSessionRequest {
+  sessionId: string;
+  timelineItemId: string;
+  title?: string | null;
+}
+
+export interface AgentExportSessionRequest {
+  sessionId: string;
+  path: string;
+}
+
+export interface AgentImportSessionRequest {
+  path: string;
+  projectRoot?: string | null;
+}
+
+export interface AgentSearchSessionsRequest {
+  query: string;
+  projectRoot?: string | null;
+  limit?: number | null;
+}
+
+export interface AgentSessionSearchHit {
+  session: AgentSessionSummary;
+  timelineItemId?: string;
+  snippet: string;
+  score: number;
+}
+
+export type AgentTranscriptFormat = "markdown" | "html";
+
+export interface AgentTranscriptRequest {
+  sessionId: string;
+  format: AgentTranscriptFormat;
+  includeThinking?: boolean;
+}
+
+export interface AgentTranscript {
+  sessionId: string;
+  title: string;
+  format: AgentTranscriptFormat;
+  content: string;
+}
+
 export interface AgentSessionSummary {
   id: string;
   title: string;
@@ -197,6 +409,8 @@ export interface AgentRunResponse {
 
 export type AgentPermissionDecision = "allow_once" | "deny_once" | "cancel";
 
+export type AgentElicitationAction = "accept" | "decline" | "cancel";
+
 export interface AgentEventEnvelope {
   eventType: string;
   sessionId?: string | null;
@@ -314,6 +528,46 @@ export class AgentRuntimeService {
     });
   }
 
+  async importMcpServers(
+    userId: string,
+    json: string,
+    options: { dryRun: boolean; replaceExisting: boolean }
+  ): Promise<AgentMcpImportReport> {
+    return await this.invokeForUser<AgentMcpImportReport>(userId, "agent_import_mcp_servers", {
+      userId,
+      request: { json, ...options }
+    });
+  }
+
+  async exportMcpServers(userId: string): Promise<string> {
+    return await this.invokeForUser<string>(userId, "agent_export_mcp_servers");
+  }
+
+  async listMcpToolPermissions(userId: string): Promise<AgentMcpToolPermission[]> {
+    return await this.invokeForUser<AgentMcpToolPermission[]>(
+      userId,
+      "agent_list_mcp_tool_permissions"
+    );
+  }
+
+  async saveMcpToolPermissions(
+    userId: string,
+    permissions: AgentMcpToolPermission[]
+  ): Promise<AgentMcpToolPermission[]> {
+    return await this.invokeForUser<AgentMcpToolPermission[]>(
+      userId,
+      "agent_save_mcp_tool_permissions",
+      { userId, permissions }
+    );
+  }
+
+  async testMcpServer(userId: string, server: AgentMcpServer): Promise<AgentMcpServerTest> {
+    return await this.invokeForUser<AgentMcpServerTest>(userId, "agent_test_mcp_server", {
+      userId,
+      server
+    });
+  }
+
   async listSessionMcpServers(userId: string, sessionId: string): Promise<AgentSessionMcpServer[]> {
     return await this.invokeForUser<AgentSessionMcpServer[]>(
       userId,
@@ -335,6 +589,41 @@ export class AgentRuntimeService {
     );
   }
 
+  async listSessionMcpContext(userId: string, sessionId: string): Promise<AgentMcpContextCatalog> {
+    return await this.invokeForUser<AgentMcpContextCatalog>(
+      userId,
+      "agent_list_session_mcp_context",
+      { userId, sessionId }
+    );
+  }
+
+  async readSessionMcpResource(
+    userId: string,
+    sessionId: string,
+    server: string,
+    uri: string
+  ): Promise<AgentMcpComposerText> {
+    return await this.invokeForUser<AgentMcpComposerText>(
+      userId,
+      "agent_read_session_mcp_resource",
+      { userId, request: { sessionId, server, uri } }
+    );
+  }
+
+  async getSessionMcpPrompt(
+    userId: string,
+    sessionId: string,
+    server: string,
+    name: string,
+    args: Record<string, string>
+  ): Promise<AgentMcpComposerText> {
+    return await this.invokeForUser<AgentMcpComposerText>(
+      userId,
+      "agent_get_session_mcp_prompt",
+      { userId, request: { sessionId, server, name, arguments: args } }
+    );
+  }
+
   async listRecentProjectRoots(userId: string): Promise<RecentProjectRoot[]> {
     return await this.invokeForUser<RecentProjectRoot[]>(userId, "agent_list_recent_project_roots");
   }
@@ -381,6 +670,82 @@ export class AgentRuntimeService {
     });
   }
 
+  async getFilesystemBoundary(userId: string): Promise<AgentFilesystemBoundary> {
+    return await this.invokeForUser<AgentFilesystemBoundary>(
+      userId,
+      "agent_get_filesystem_boundary",
+      { userId }
+    );
+  }
+
+  async setFilesystemBoundary(
+    userId: string,
+    boundary: AgentFilesystemBoundary
+  ): Promise<AgentFilesystemBoundary> {
+    return await this.invokeForUser<AgentFilesystemBoundary>(
+      userId,
+      "agent_set_filesystem_boundary",
+      { userId, boundary }
+    );
+  }
+
+  async listRunChanges(
+    userId: string,
+    sessionId: string,
+    runId?: string
+  ): Promise<AgentRunChanges> {
+    return await this.invokeForUser<AgentRunChanges>(userId, "agent_list_run_changes", {
+      userId,
+      sessionId,
+      runId: runId ?? null
+    });
+  }
+
+  async revertRunChanges(
+    userId: string,
+    sessionId: string,
+    runId: string
+  ): Promise<AgentFileChange[]> {
+    return await this.invokeForUser<AgentFileChange[]>(userId, "agent_revert_run_changes", {
+      userId,
+      sessionId,
+      runId
+    });
+  }
+
+  async revertFileChange(
+    userId: string,
+    sessionId: string,
+    changeId: string
+  ): Promise<AgentFileChange> {
+    return await this.invokeForUser<AgentFileChange>(userId, "agent_revert_file_change", {
+      userId,
+      sessionId,
+      changeId
+    });
+  }
+
+  async listPermissionRules(userId: string, projectRoot?: string): Promise<AgentPermissionRule[]> {
+    return await this.invokeForUser<AgentPermissionRule[]>(userId, "agent_list_permission_rules", {
+      userId,
+      projectRoot: projectRoot ?? null
+    });
+  }
+
+  async addPermissionRule(
+    userId: string,
+    request: AgentAddPermissionRuleRequest
+  ): Promise<AgentPermissionRule> {
+    return await this.invokeForUser<AgentPermissionRule>(userId, "agent_add_permission_rule", {
+      userId,
+      request
+    });
+  }
+
+  async removePermissionRule(userId: string, ruleId: string): Promise<void> {
+    await this.invokeForUser<void>(userId, "agent_remove_permission_rule", { userId, ruleId });
+  }
+
   async saveProjectRootOrder(userId: string, paths: string[]): Promise<RecentProjectRoot[]> {
     return await this.invokeForUser<RecentProjectRoot[]>(userId, "agent_save_project_root_order", {
       userId,
@@ -405,6 +770,16 @@ export class AgentRuntimeService {
     });
   }
 
+  async searchSessions(
+    userId: string,
+    request: AgentSearchSessionsRequest
+  ): Promise<AgentSessionSearchHit[]> {
+    return await this.invokeForUser<AgentSessionSearchHit[]>(userId, "agent_search_sessions", {
+      userId,
+      request
+    });
+  }
+
   async loadSession(userId: string, sessionId: string): Promise<AgentSessionDetail> {
     return await this.invokeForUser<AgentSessionDetail>(userId, "agent_load_session", {
       userId,
@@ -422,6 +797,43 @@ export class AgentRuntimeService {
     });
   }
 
+  async forkSession(userId: string, request: AgentForkSessionRequest): Promise<AgentSessionDetail> {
+    return await this.invokeForUser<AgentSessionDetail>(userId, "agent_fork_session", {
+      userId,
+      request
+    });
+  }
+
+  async exportSession(
+    userId: string,
+    request: AgentExportSessionRequest
+  ): Promise<AgentSessionSummary> {
+    return await this.invokeForUser<AgentSessionSummary>(userId, "agent_export_session", {
+      userId,
+      request
+    });
+  }
+
+  async importSession(
+    userId: string,
+    request: AgentImportSessionRequest
+  ): Promise<AgentSessionDetail> {
+    return await this.invokeForUser<AgentSessionDetail>(userId, "agent_import_session", {
+      userId,
+      request
+    });
+  }
+
+  async renderSessionTranscript(
+    userId: string,
+    request: AgentTranscriptRequest
+  ): Promise<AgentTranscript> {
+    return await this.invokeForUser<AgentTranscript>(userId, "agent_render_session_transcript", {
+      userId,
+      request
+    });
+  }
+
   async deleteSession(userId: string, sessionId: string): Promise<void> {
     await this.invokeForUser(userId, "agent_delete_session", { userId, sessionId });
   }
@@ -517,6 +929,19 @@ export class AgentRuntimeService {
     });
   }
 
+  async respondToElicitation(
+    userId: string,
+    sessionId: string,
+    requestId: string,
+    action: AgentElicitationAction,
+    content?: Record<string, string | number | boolean>
+  ): Promise<void> {
+    await this.invokeForUser(userId, "agent_elicitation_respond", {
+      userId,
+      response: { sessionId, requestId, action, content }
+    });
+  }
+
   async listenToEvents(handler: AgentEventHandler): Promise<UnlistenAgentEvents> {
     if (!isTauriDesktop()) {
       return () => {};
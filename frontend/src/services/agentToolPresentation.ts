@@ -1,6 +1,6 @@
 export type AgentToolKind = "shell" | "file-read" | "file-write" | "web" | "mcp" | "generic";
 
-const SHELL_TOOL_NAMES = new Set(["shell"]);
+const SHELL_TOOL_NAMES = new Set(["shell", "shell_output", "shell_kill"]);
 const FILE_READ_TOOL_NAMES = new Set([
   "read",
   "read_file",
@@ -21,7 +21,7 @@ const FILE_WRITE_TOOL_NAMES = new Set([
 ]);
 const WEB_TOOL_NAMES = new Set(["web_search", "open_url"]);
 const MAPLE_EXTENSION_NAMES = new Set(["developer"]);
-const SHELL_TOOL_LABELS = new Set(["terminal", "shell"]);
+const SHELL_TOOL_LABELS = new Set(["terminal", "shell", "shell output", "stop shell process"]);
 const FILE_READ_TOOL_LABELS = new Set([
   "read",
   "read file",
@@ -6,6 +6,8 @@ import {
   BUZZ_MAPLE_HARNESS_NAME,
   DEFAULT_MAPLE_ACP_CONFIG,
   MAX_MAPLE_ACP_CONNECTIONS,
+  MAX_MAPLE_ACP_HANDSHAKE_TIMEOUT_SECS,
+  MIN_MAPLE_ACP_IDLE_TIMEOUT_SECS,
   PASEO_MAPLE_PROVIDER_ID,
   PASEO_MAPLE_PROVIDER_NAME,
   MapleAcpService,
@@ -111,7 +113,10 @@ describe("MapleAcpService", () => {
       enabled: false,
       permissionMode: "read_only",
       allowedProjectRoots: [" /tmp/project ", "/tmp/project"],
-      maxConnections: 1
+      maxConnections: 1,
+      allowSessionDelete: true,
+      handshakeTimeoutSecs: 10,
+      idleTimeoutSecs: 0
     });
 
     expect(bridge.lastArgs).toEqual({
@@ -120,7 +125,10 @@ describe("MapleAcpService", () => {
         enabled: false,
         permissionMode: "read_only",
         allowedProjectRoots: ["/tmp/project"],
-        maxConnections: 1
+        maxConnections: 1,
+        allowSessionDelete: true,
+        handshakeTimeoutSecs: 10,
+        idleTimeoutSecs: 0
       }
     });
   });
@@ -149,13 +157,19 @@ describe("Maple ACP response normalization", () => {
         enabled: "yes",
         permissionMode: "unattended",
         allowedProjectRoots: ["/tmp/a", 42, " /tmp/a ", "/tmp/b"],
-        maxConnections: -3
+        maxConnections: -3,
+        allowSessionDelete: "yes",
+        handshakeTimeoutSecs: "soon",
+        idleTimeoutSecs: -1
       })
     ).toEqual({
       enabled: false,
       permissionMode: "read_only",
       allowedProjectRoots: ["/tmp/a", "/tmp/b"],
-      maxConnections: 1
+      maxConnections: 1,
+      allowSessionDelete: false,
+      handshakeTimeoutSecs: 30,
+      idleTimeoutSecs: 30 * 60
     });
   });
 
@@ -182,10 +196,24 @@ describe("Maple ACP response normalization", () => {
       enabled: true,
       permissionMode: "read_only",
       allowedProjectRoots: [],
-      maxConnections: 1
+      maxConnections: 1,
+      allowSessionDelete: false,
+      handshakeTimeoutSecs: 30,
+      idleTimeoutSecs: 30 * 60
     });
   });
 
+  test("keeps connection timeouts inside the native range", () => {
+    expect(normalizeMapleAcpConfig({ handshakeTimeoutSecs: 0 }).handshakeTimeoutSecs).toBe(1);
+    expect(normalizeMapleAcpConfig({ handshakeTimeoutSecs: 9_999 }).handshakeTimeoutSecs).toBe(
+      MAX_MAPLE_ACP_HANDSHAKE_TIMEOUT_SECS
+    );
+    expect(normalizeMapleAcpConfig({ idleTimeoutSecs: 0 }).idleTimeoutSecs).toBe(0);
+    expect(normalizeMapleAcpConfig({ idleTimeoutSecs: 5 }).idleTimeoutSecs).toBe(
+      MIN_MAPLE_ACP_IDLE_TIMEOUT_SECS
+    );
+  });
+
   test("accepts the native status and tolerates optional future fields", () => {
     expect(
       normalizeMapleAcpStatus({
@@ -199,6 +227,8 @@ describe("Maple ACP response normalization", () => {
         protocolVersion: 1,
         lastError: "",
         buzzCredentialsAvailable: true,
+        handshakeTimeouts: 2,
+        idleEvictions: 1,
         harness,
         ignoredFutureField: "okay"
       })
@@ -213,6 +243,8 @@ describe("Maple ACP response normalization", () => {
       protocolVersion: 1,
       error: null,
       buzzCredentialsAvailable: true,
+      handshakeTimeouts: 2,
+      idleEvictions: 1,
       harness
     });
   });
@@ -272,7 +304,10 @@ describe("Agent connections policy state", () => {
     enabled: true,
     permissionMode: "allow_all" as const,
     allowedProjectRoots: [],
-    maxConnections: 1
+    maxConnections: 1,
+    allowSessionDelete: false,
+    handshakeTimeoutSecs: 30,
+    idleTimeoutSecs: 0
   };
 
   test("keeps mutations locked until the saved config has loaded", () => {
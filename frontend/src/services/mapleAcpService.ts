@@ -9,6 +9,10 @@ export interface MapleAcpConfig {
   permissionMode: MapleAcpPermissionMode;
   allowedProjectRoots: string[];
   maxConnections: number;
+  allowSessionDelete: boolean;
+  handshakeTimeoutSecs: number;
+  /** Zero disables idle eviction. */
+  idleTimeoutSecs: number;
 }
 
 export interface MapleAcpHarness {
@@ -27,6 +31,8 @@ export interface MapleAcpStatus {
   protocolVersion: string | number | null;
   error: string | null;
   buzzCredentialsAvailable: boolean;
+  handshakeTimeouts: number;
+  idleEvictions: number;
   harness: MapleAcpHarness | null;
 }
 
@@ -59,6 +65,9 @@ export const BUZZ_MAPLE_HARNESS_NAME = "Maple" as const;
 export const PASEO_MAPLE_PROVIDER_ID = "maple-acp" as const;
 export const PASEO_MAPLE_PROVIDER_NAME = "Maple Agent" as const;
 export const MAX_MAPLE_ACP_CONNECTIONS = 8 as const;
+export const MAX_MAPLE_ACP_HANDSHAKE_TIMEOUT_SECS = 300 as const;
+export const MIN_MAPLE_ACP_IDLE_TIMEOUT_SECS = 60 as const;
+export const MAX_MAPLE_ACP_IDLE_TIMEOUT_SECS = 86_400 as const;
 export const BUZZ_MAPLE_AGENT_PARALLELISM = MAX_MAPLE_ACP_CONNECTIONS;
 export const BUZZ_DEFAULT_AGENT_PARALLELISM = 10 as const;
 
@@ -73,7 +82,10 @@ export const DEFAULT_MAPLE_ACP_CONFIG: MapleAcpConfig = Object.freeze({
   enabled: false,
   permissionMode: "read_only",
   allowedProjectRoots: [],
-  maxConnections: MAX_MAPLE_ACP_CONNECTIONS
+  maxConnections: MAX_MAPLE_ACP_CONNECTIONS,
+  allowSessionDelete: false,
+  handshakeTimeoutSecs: 30,
+  idleTimeoutSecs: 30 * 60
 });
 
 const defaultBridge: MapleAcpBridge = {
@@ -164,13 +176,33 @@ export function normalizeMapleAcpConfig(value: unknown): MapleAcpConfig {
     typeof record?.maxConnections === "number" && Number.isSafeInteger(record.maxConnections)
       ? record.maxConnections
       : DEFAULT_MAPLE_ACP_CONFIG.maxConnections;
+  const handshakeTimeoutSecs = safeCount(
+    record?.handshakeTimeoutSecs,
+    DEFAULT_MAPLE_ACP_CONFIG.handshakeTimeoutSecs
+  );
+  const idleTimeoutSecs = safeCount(
+    record?.idleTimeoutSecs,
+    DEFAULT_MAPLE_ACP_CONFIG.idleTimeoutSecs
+  );
 
   return {
     enabled:
       typeof record?.enabled === "boolean" ? record.enabled : DEFAULT_MAPLE_ACP_CONFIG.enabled,
     permissionMode: normalizePermissionMode(record?.permissionMode),
     allowedProjectRoots: [...new Set(roots.map((root) => root.trim()))],
-    maxConnections: Math.min(MAX_MAPLE_ACP_CONNECTIONS, Math.max(1, maxConnections))
+    maxConnections: Math.min(MAX_MAPLE_ACP_CONNECTIONS, Math.max(1, maxConnections)),
+    allowSessionDelete: record?.allowSessionDelete === true,
+    handshakeTimeoutSecs: Math.min(
+      MAX_MAPLE_ACP_HANDSHAKE_TIMEOUT_SECS,
+      Math.max(1, handshakeTimeoutSecs)
+    ),
+    idleTimeoutSecs:
+      idleTimeoutSecs === 0
+        ? 0
+        : Math.min(
+            MAX_MAPLE_ACP_IDLE_TIMEOUT_SECS,
+            Math.max(MIN_MAPLE_ACP_IDLE_TIMEOUT_SECS, idleTimeoutSecs)
+          )
   };
 }
 
@@ -197,6 +229,8 @@ export function normalizeMapleAcpStatus(value: unknown): MapleAcpStatus {
     protocolVersion: normalizeProtocolVersion(record?.protocolVersion),
     error: error || null,
     buzzCredentialsAvailable: record?.buzzCredentialsAvailable === true,
+    handshakeTimeouts: safeCount(record?.handshakeTimeouts),
+    idleEvictions: safeCount(record?.idleEvictions),
     harness: command ? { command, args } : null
   };
 }